@@ -44,7 +44,13 @@ pub fn main() -> Result<(), Box<dyn Error>> {
     // Not a string
     attr2.add_nested_attribute(&Nlattr::new(None, 2, 5)?)?;
 
-    let attrs = vec![attr1, attr2];
+    // Or use the builder which also sets the NLA_F_NESTED flag
+    let attr3 = Nlattr::nested(3)
+        .attr(1, "this is a string too")
+        .nested(Nlattr::nested(2).attr(1, 5))
+        .build()?;
+
+    let attrs = vec![attr1, attr2, attr3];
 
     let genlmsg = Genlmsghdr::new(consts::CtrlCmd::Getfamily, 2, attrs)?;
     let nlmsg = Nlmsghdr::new(
@@ -1,8 +1,8 @@
 use std::{env, error::Error};
 
-use neli::{consts, genl::Genlmsghdr, socket};
 #[cfg(feature = "stream")]
-use tokio::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use neli::{consts, genl::Genlmsghdr, socket};
 
 #[cfg(feature = "stream")]
 fn debug_stream() -> Result<(), neli::err::NlError> {
@@ -19,8 +19,11 @@ fn debug_stream() -> Result<(), neli::err::NlError> {
     };
     let mut s = socket::NlSocket::connect(consts::NlFamily::Generic, None, None, true)?;
     let id = s.resolve_nl_mcast_group(&family_name, &mc_group_name)?;
-    s.set_mcast_groups(vec![id])?;
-    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    s.add_mcast_membership(&[id])?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .unwrap();
     runtime.block_on(async {
         let mut ss = match neli::socket::tokio::NlSocket::<u16, Genlmsghdr<u8, u16>>::new(s) {
             Ok(s) => s,
@@ -51,7 +54,7 @@ fn debug_stream() -> Result<(), neli::err::NlError> {
     };
     let mut s = socket::NlSocket::connect(consts::NlFamily::Generic, None, None, true)?;
     let id = s.resolve_nl_mcast_group(&family_name, &mc_group_name)?;
-    s.set_mcast_groups(vec![id])?;
+    s.add_mcast_membership(&[id])?;
     for next in s.iter::<u16, Genlmsghdr<u8, u16>>() {
         println!("{:#?}", next?);
     }
@@ -0,0 +1,399 @@
+//! # Derive macros for `neli`
+//!
+//! This crate is not intended to be used directly. Enable the `derive` feature of `neli` and
+//! use the re-exported macros from there.
+//!
+//! ## `#[derive(Nl)]`
+//!
+//! Generates an implementation of `neli::Nl` for a struct where every field implements `Nl`.
+//! Fields are serialized and deserialized in declaration order with no padding inserted between
+//! them so any padding required by the C representation of the struct must be declared as a field.
+//!
+//! If a size hint is provided to `deserialize`, the hint minus the size of every other field is
+//! passed on to the last field. This allows a struct to end in a variable length field such as
+//! `Vec<u8>` or a `Vec<Nlattr<_, _>>`.
+//!
+//! The container attribute `#[nl(align)]` pads the serialized struct to netlink alignment and
+//! strips the padding when deserializing in the same way as `Nlmsghdr` and `Nlattr`.
+//!
+//! ```ignore
+//! #[derive(Debug, neli::Nl)]
+//! struct MyHeader {
+//!     family: u8,
+//!     pad: u8,
+//!     kind: u16,
+//!     index: i32,
+//! }
+//! ```
+//!
+//! ## `#[derive(FromAttrs)]`
+//!
+//! Generates an implementation of `neli::nlattr::FromAttrs` that extracts a struct from an
+//! `AttrHandle`. The attribute type enum is given with the container attribute
+//! `#[nla(attr_type = "...")]` and every field maps to an attribute type constant with
+//! `#[nla(attr = "...")]`.
+//!
+//! The payload of each attribute is parsed as the field type. A missing attribute is an error
+//! unless the field is an `Option`. Fields marked `#[nla(attr = "...", nested)]` must implement
+//! `FromAttrs` themselves and are extracted from the attributes nested in the payload.
+//!
+//! ```ignore
+//! #[derive(Debug, neli::FromAttrs)]
+//! #[nla(attr_type = "CtrlAttr")]
+//! struct Family {
+//!     #[nla(attr = "CtrlAttr::FamilyId")]
+//!     id: u16,
+//!     #[nla(attr = "CtrlAttr::FamilyName")]
+//!     name: String,
+//!     #[nla(attr = "CtrlAttr::Version")]
+//!     version: Option<u32>,
+//! }
+//! ```
+
+#![deny(missing_docs)]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Expr, Fields, GenericArgument,
+    GenericParam, Generics, Index, Lit, Member, Meta, NestedMeta, PathArguments, Type,
+};
+
+fn add_trait_bounds(mut generics: Generics) -> Generics {
+    for param in generics.params.iter_mut() {
+        if let GenericParam::Type(ref mut type_param) = *param {
+            type_param.bounds.push(parse_quote!(::neli::Nl));
+        }
+    }
+    generics
+}
+
+fn has_align_attr(attrs: &[Attribute]) -> Result<bool, syn::Error> {
+    let mut align = false;
+    for attr in attrs.iter().filter(|a| a.path.is_ident("nl")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => {
+                for nested in list.nested.iter() {
+                    match *nested {
+                        NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("align") => align = true,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                nested,
+                                "Unrecognized nl attribute",
+                            ))
+                        }
+                    }
+                }
+            }
+            meta => return Err(syn::Error::new_spanned(meta, "Expected #[nl(...)]")),
+        }
+    }
+    Ok(align)
+}
+
+/// Derive `neli::Nl` for a struct
+#[proc_macro_derive(Nl, attributes(nl))]
+pub fn derive_nl(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive_nl_impl(input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn derive_nl_impl(input: DeriveInput) -> Result<TokenStream2, syn::Error> {
+    let name = &input.ident;
+    let fields = match input.data {
+        Data::Struct(ref data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "#[derive(Nl)] is only supported for structs",
+            ))
+        }
+    };
+    let align = has_align_attr(&input.attrs)?;
+    let generics = add_trait_bounds(input.generics.clone());
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (members, types): (Vec<Member>, Vec<&Type>) = match *fields {
+        Fields::Named(ref named) => named
+            .named
+            .iter()
+            .map(|f| {
+                (
+                    Member::Named(f.ident.clone().expect("Named field must have an ident")),
+                    &f.ty,
+                )
+            })
+            .unzip(),
+        Fields::Unnamed(ref unnamed) => unnamed
+            .unnamed
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (Member::Unnamed(Index::from(i)), &f.ty))
+            .unzip(),
+        Fields::Unit => (Vec::new(), Vec::new()),
+    };
+    let vars: Vec<_> = (0..members.len())
+        .map(|i| format_ident!("__field_{}", i))
+        .collect();
+
+    let deserialize_fields = vars
+        .iter()
+        .zip(types.iter())
+        .enumerate()
+        .map(|(i, (var, ty))| {
+            if i + 1 == vars.len() && i > 0 {
+                let prev_vars = &vars[..i];
+                quote! {
+                    if let Some(hint) = size_hint {
+                        let consumed = 0 #( + ::neli::Nl::size(&#prev_vars) )*;
+                        mem.set_size_hint(hint.checked_sub(consumed).ok_or_else(|| {
+                            ::neli::err::DeError::new(
+                                "Size hint is smaller than the fixed size fields of the struct",
+                            )
+                        })?);
+                    }
+                    let #var = <#ty as ::neli::Nl>::deserialize(mem)?;
+                }
+            } else if i + 1 == vars.len() {
+                quote! {
+                    if let Some(hint) = size_hint {
+                        mem.set_size_hint(hint);
+                    }
+                    let #var = <#ty as ::neli::Nl>::deserialize(mem)?;
+                }
+            } else {
+                quote! {
+                    let #var = <#ty as ::neli::Nl>::deserialize(mem)?;
+                }
+            }
+        });
+
+    let construct = match *fields {
+        Fields::Named(_) => quote! { #name { #( #members: #vars ),* } },
+        Fields::Unnamed(_) => quote! { #name ( #( #vars ),* ) },
+        Fields::Unit => quote! { #name },
+    };
+    let (pad, strip) = if align {
+        (
+            quote! { ::neli::Nl::pad(self, mem)?; },
+            quote! { ::neli::Nl::strip(&value, mem)?; },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::neli::Nl for #name #ty_generics #where_clause {
+            fn serialize(
+                &self,
+                mem: &mut ::neli::StreamWriteBuffer,
+            ) -> Result<(), ::neli::err::SerError> {
+                #( ::neli::Nl::serialize(&self.#members, mem)?; )*
+                #pad
+                Ok(())
+            }
+
+            fn deserialize<B>(
+                mem: &mut ::neli::StreamReadBuffer<B>,
+            ) -> Result<Self, ::neli::err::DeError>
+            where
+                B: AsRef<[u8]>,
+            {
+                #[allow(unused_variables)]
+                let size_hint = mem.take_size_hint();
+                #( #deserialize_fields )*
+                let value = #construct;
+                #strip
+                Ok(value)
+            }
+
+            fn size(&self) -> usize {
+                0 #( + ::neli::Nl::size(&self.#members) )*
+            }
+
+            fn type_size() -> Option<usize> {
+                Some(0) #( .and_then(|acc| <#types as ::neli::Nl>::type_size().map(|s| acc + s)) )*
+            }
+        }
+    })
+}
+
+fn parse_nla_attrs(attrs: &[Attribute]) -> Result<Vec<NestedMeta>, syn::Error> {
+    let mut metas = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("nla")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => metas.extend(list.nested),
+            meta => return Err(syn::Error::new_spanned(meta, "Expected #[nla(...)]")),
+        }
+    }
+    Ok(metas)
+}
+
+fn parse_str_value<T>(meta: &NestedMeta, lit: &Lit) -> Result<T, syn::Error>
+where
+    T: syn::parse::Parse,
+{
+    match *lit {
+        Lit::Str(ref s) => s.parse(),
+        _ => Err(syn::Error::new_spanned(meta, "Expected a string literal")),
+    }
+}
+
+fn option_inner_type(ty: &Type) -> Option<&Type> {
+    let path = match *ty {
+        Type::Path(ref p) if p.qself.is_none() => &p.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => {
+            match args.args.first() {
+                Some(GenericArgument::Type(ref inner)) => Some(inner),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Derive `neli::nlattr::FromAttrs` for a struct
+#[proc_macro_derive(FromAttrs, attributes(nla))]
+pub fn derive_from_attrs(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive_from_attrs_impl(input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn derive_from_attrs_impl(input: DeriveInput) -> Result<TokenStream2, syn::Error> {
+    let name = &input.ident;
+    let fields = match input.data {
+        Data::Struct(ref data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "#[derive(FromAttrs)] is only supported for structs",
+            ))
+        }
+    };
+
+    let mut attr_type = None;
+    for meta in parse_nla_attrs(&input.attrs)? {
+        match meta {
+            NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("attr_type") => {
+                attr_type = Some(parse_str_value::<Type>(&meta, &nv.lit)?);
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "Unrecognized nla container attribute",
+                ))
+            }
+        }
+    }
+    let attr_type = attr_type.ok_or_else(|| {
+        syn::Error::new_spanned(
+            &input.ident,
+            "#[derive(FromAttrs)] requires #[nla(attr_type = \"...\")]",
+        )
+    })?;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut members = Vec::new();
+    let mut extractions = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let member = match field.ident {
+            Some(ref ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        let mut attr = None;
+        let mut nested = false;
+        for meta in parse_nla_attrs(&field.attrs)? {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("attr") => {
+                    attr = Some(parse_str_value::<Expr>(&meta, &nv.lit)?);
+                }
+                NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("nested") => nested = true,
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        meta,
+                        "Unrecognized nla field attribute",
+                    ))
+                }
+            }
+        }
+        let attr = attr.ok_or_else(|| {
+            syn::Error::new_spanned(field, "Field requires #[nla(attr = \"...\")]")
+        })?;
+        let var = format_ident!("__field_{}", i);
+        let (inner_ty, optional) = match option_inner_type(&field.ty) {
+            Some(inner) => (inner, true),
+            None => (&field.ty, false),
+        };
+        let parse = if nested {
+            quote! {
+                |attr| {
+                    let handle = attr.get_nested_attributes::<
+                        <#inner_ty as ::neli::nlattr::FromAttrs>::Type
+                    >()?;
+                    <#inner_ty as ::neli::nlattr::FromAttrs>::from_attrs(&handle)
+                }
+            }
+        } else {
+            quote! { |attr| attr.get_payload_as::<#inner_ty>() }
+        };
+        let missing = if optional {
+            quote! {}
+        } else {
+            let msg = format!(
+                "Missing required attribute {} for field {}",
+                quote!(#attr).to_string().replace(' ', ""),
+                quote!(#member),
+            );
+            quote! { .ok_or_else(|| ::neli::err::DeError::new(#msg))? }
+        };
+        extractions.push(quote! {
+            let #var = handle
+                .get_attribute(#attr)
+                .map(#parse)
+                .transpose()?
+                #missing;
+        });
+        members.push((member, var));
+    }
+
+    let construct = match *fields {
+        Fields::Named(_) => {
+            let (members, vars): (Vec<_>, Vec<_>) = members.into_iter().unzip();
+            quote! { #name { #( #members: #vars ),* } }
+        }
+        Fields::Unnamed(_) => {
+            let vars = members.into_iter().map(|(_, var)| var);
+            quote! { #name ( #( #vars ),* ) }
+        }
+        Fields::Unit => quote! { #name },
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::neli::nlattr::FromAttrs for #name #ty_generics #where_clause {
+            type Type = #attr_type;
+
+            fn from_attrs(
+                handle: &::neli::nlattr::AttrHandle<'_, Self::Type>,
+            ) -> Result<Self, ::neli::err::DeError> {
+                #( #extractions )*
+                Ok(#construct)
+            }
+        }
+    })
+}
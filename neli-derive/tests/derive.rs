@@ -0,0 +1,156 @@
+use neli::{
+    consts::{CtrlAttr, CtrlAttrMcastGrp},
+    nlattr::{AttrHandle, FromAttrs, Nlattr},
+    FromAttrs, Nl, StreamReadBuffer, StreamWriteBuffer,
+};
+
+#[derive(Debug, PartialEq, Nl)]
+struct Header {
+    family: u8,
+    pad: u8,
+    kind: u16,
+    index: i32,
+}
+
+#[derive(Debug, PartialEq, Nl)]
+struct WithPayload(u16, u16, Vec<u8>);
+
+#[derive(Debug, PartialEq, Nl)]
+struct Generic<T> {
+    inner: T,
+    outer: u32,
+}
+
+#[derive(Debug, PartialEq, Nl)]
+#[nl(align)]
+struct Aligned {
+    kind: u16,
+    flags: u8,
+}
+
+#[derive(Debug, PartialEq, FromAttrs)]
+#[nla(attr_type = "CtrlAttrMcastGrp")]
+struct McastGroup {
+    #[nla(attr = "CtrlAttrMcastGrp::Name")]
+    name: String,
+    #[nla(attr = "CtrlAttrMcastGrp::Id")]
+    id: u32,
+}
+
+#[derive(Debug, PartialEq, FromAttrs)]
+#[nla(attr_type = "CtrlAttr")]
+struct Family {
+    #[nla(attr = "CtrlAttr::FamilyId")]
+    id: u16,
+    #[nla(attr = "CtrlAttr::FamilyName")]
+    name: String,
+    #[nla(attr = "CtrlAttr::Version")]
+    version: Option<u32>,
+    #[nla(attr = "CtrlAttr::McastGroups", nested)]
+    group: Option<McastGroup>,
+}
+
+#[test]
+fn test_derive_named() {
+    let hdr = Header {
+        family: 2,
+        pad: 0,
+        kind: 1,
+        index: -5,
+    };
+    assert_eq!(hdr.size(), 8);
+    assert_eq!(Header::type_size(), Some(8));
+
+    let mut mem = StreamWriteBuffer::new_growable(None);
+    hdr.serialize(&mut mem).unwrap();
+    let mut expected = vec![2, 0];
+    expected.extend_from_slice(&1u16.to_ne_bytes());
+    expected.extend_from_slice(&(-5i32).to_ne_bytes());
+    assert_eq!(mem.as_ref(), expected.as_slice());
+
+    let mut buf = StreamReadBuffer::new(expected);
+    assert_eq!(Header::deserialize(&mut buf).unwrap(), hdr);
+}
+
+#[test]
+fn test_derive_trailing_size_hint() {
+    let msg = WithPayload(1, 2, vec![3, 4, 5, 6]);
+    assert_eq!(msg.size(), 8);
+    assert_eq!(WithPayload::type_size(), None);
+
+    let mut mem = StreamWriteBuffer::new_growable(None);
+    msg.serialize(&mut mem).unwrap();
+    let mut bytes = mem.as_ref().to_vec();
+    bytes.extend_from_slice(&[7, 8]);
+
+    let mut buf = StreamReadBuffer::new(bytes);
+    buf.set_size_hint(8);
+    assert_eq!(WithPayload::deserialize(&mut buf).unwrap(), msg);
+    assert_eq!(u8::deserialize(&mut buf).unwrap(), 7);
+}
+
+#[test]
+fn test_derive_generic() {
+    let msg = Generic {
+        inner: 5u16,
+        outer: 6,
+    };
+    assert_eq!(Generic::<u16>::type_size(), Some(6));
+
+    let mut mem = StreamWriteBuffer::new_growable(None);
+    msg.serialize(&mut mem).unwrap();
+    let mut buf = StreamReadBuffer::new(mem.as_ref().to_vec());
+    assert_eq!(Generic::<u16>::deserialize(&mut buf).unwrap(), msg);
+}
+
+#[test]
+fn test_derive_align() {
+    let msg = Aligned { kind: 1, flags: 2 };
+    assert_eq!(msg.size(), 3);
+    assert_eq!(msg.asize(), 4);
+
+    let mut mem = StreamWriteBuffer::new_growable(None);
+    msg.serialize(&mut mem).unwrap();
+    msg.serialize(&mut mem).unwrap();
+    let mut expected = 1u16.to_ne_bytes().to_vec();
+    expected.extend_from_slice(&[2, 0]);
+    expected.extend_from_slice(&expected.clone());
+    assert_eq!(mem.as_ref(), expected.as_slice());
+
+    let mut buf = StreamReadBuffer::new(expected);
+    assert_eq!(Aligned::deserialize(&mut buf).unwrap(), msg);
+    assert_eq!(Aligned::deserialize(&mut buf).unwrap(), msg);
+}
+
+#[test]
+fn test_derive_from_attrs() {
+    let group = Nlattr::nested(CtrlAttr::McastGroups)
+        .attr(CtrlAttrMcastGrp::Name, "events".to_string())
+        .attr(CtrlAttrMcastGrp::Id, 3u32)
+        .build()
+        .unwrap();
+    let handle = AttrHandle::new(vec![
+        Nlattr::new(None, CtrlAttr::FamilyId, 16u16).unwrap(),
+        Nlattr::new(None, CtrlAttr::FamilyName, "nlctrl".to_string()).unwrap(),
+        group,
+    ]);
+    assert_eq!(
+        Family::from_attrs(&handle).unwrap(),
+        Family {
+            id: 16,
+            name: "nlctrl".to_string(),
+            version: None,
+            group: Some(McastGroup {
+                name: "events".to_string(),
+                id: 3,
+            }),
+        }
+    );
+
+    let handle = AttrHandle::new(vec![Nlattr::new(None, CtrlAttr::FamilyId, 16u16).unwrap()]);
+    let err = Family::from_attrs(&handle).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Missing required attribute CtrlAttr::FamilyName for field name"
+    );
+}
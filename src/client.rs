@@ -0,0 +1,295 @@
+//! Request and response in one call
+//!
+//! `NlClient::talk` sends a request, waits for the reply with the sequence number of the request,
+//! and returns its payload or the error reported by the kernel. Messages that do not answer the
+//! request, such as multicast notifications, are skipped. This covers the common case of
+//! getting, creating, or deleting a single object without handling ACKs and sequence numbers.
+//!
+//! The client is built on `NlRouter` so clones of it can be used from several threads at once.
+//!
+//! Applications using several netlink protocols can get clients from an `NlConnectionManager`,
+//! which opens one socket per protocol when it is first needed and shares it between all
+//! `NlConnection` handles for that protocol.
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io,
+    marker::PhantomData,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    consts::{NlFamily, NlType, NlmF},
+    err::NlError,
+    nl::{add_flags, Nlmsghdr},
+    router::{NlRouter, NlRouterReceiverHandle},
+    socket::{
+        protocol::{NlProtocol, ProtocolPayload, ProtocolType},
+        NlSocket,
+    },
+    Nl,
+};
+
+/// Synchronous client returning the reply to each request
+#[derive(Clone)]
+pub struct NlClient {
+    router: NlRouter,
+}
+
+impl NlClient {
+    /// Send requests over `socket` - see `NlRouter::new`
+    pub fn new(socket: NlSocket) -> Self {
+        NlClient {
+            router: NlRouter::new(socket),
+        }
+    }
+
+    /// Create and bind a socket for `proto` and send requests over it - see
+    /// `NlSocket::connect`
+    pub fn connect(
+        proto: NlFamily,
+        pid: Option<u32>,
+        groups: Option<Vec<u32>>,
+    ) -> Result<Self, io::Error> {
+        Ok(NlClient {
+            router: NlRouter::connect(proto, pid, groups)?,
+        })
+    }
+
+    /// Send a request and return the payload of its reply
+    ///
+    /// `NLM_F_REQUEST` and `NLM_F_ACK` are added to the flags of the request and the reply is
+    /// parsed as a message of the same type as the request with a payload of type `P`. An error
+    /// reported by the kernel is returned as `Err` and a request acknowledged without a reply
+    /// returns an error as well - use `NlSocket::send_and_await_ack` for requests that are only
+    /// acknowledged. Only the first reply is returned so use `NlSocket::dump` for dumps.
+    pub fn talk<T, Q, P>(&self, mut request: Nlmsghdr<T, Q>) -> Result<P, NlError>
+    where
+        T: Nl + NlType + Debug,
+        Q: Nl + Debug,
+        P: Nl + Debug,
+    {
+        add_flags(&mut request, &[NlmF::Request, NlmF::Ack]);
+        let mut reply = None;
+        for msg in self.router.send::<_, _, T, P>(request)? {
+            let msg = msg?;
+            if reply.is_none() {
+                reply = Some(msg.nl_payload);
+            }
+        }
+        reply.ok_or_else(|| NlError::new("The request was acknowledged without a reply"))
+    }
+
+    /// Reference to the underlying socket
+    pub fn get_ref(&self) -> &NlSocket {
+        self.router.get_ref()
+    }
+}
+
+/// Handle for the socket of protocol `F` shared by an `NlConnectionManager`
+///
+/// Message types and payloads are checked against the protocol like with `NlProtoSocket`.
+/// Cloning a handle is cheap and the socket stays open as long as a handle exists.
+pub struct NlConnection<F> {
+    client: NlClient,
+    protocol: PhantomData<F>,
+}
+
+impl<F> Clone for NlConnection<F> {
+    fn clone(&self) -> Self {
+        NlConnection {
+            client: self.client.clone(),
+            protocol: PhantomData,
+        }
+    }
+}
+
+impl<F> NlConnection<F>
+where
+    F: NlProtocol,
+{
+    /// Send a request valid for protocol `F` and return the payload of its reply - see
+    /// `NlClient::talk`
+    pub fn talk<T, Q, P>(&self, request: Nlmsghdr<T, Q>) -> Result<P, NlError>
+    where
+        T: Nl + NlType + ProtocolType<F> + Debug,
+        Q: Nl + ProtocolPayload<F> + Debug,
+        P: Nl + ProtocolPayload<F> + Debug,
+    {
+        self.client.talk(request)
+    }
+
+    /// Send a request valid for protocol `F` and return a handle for its response - see
+    /// `NlRouter::send`
+    pub fn send<ST, SP, T, P>(
+        &self,
+        request: Nlmsghdr<ST, SP>,
+    ) -> Result<NlRouterReceiverHandle<T, P>, NlError>
+    where
+        ST: Nl + NlType + ProtocolType<F> + Debug,
+        SP: Nl + ProtocolPayload<F> + Debug,
+        T: NlType + ProtocolType<F>,
+        P: Nl + ProtocolPayload<F>,
+    {
+        self.client.router.send(request)
+    }
+
+    /// Get the untyped client
+    pub fn as_client(&self) -> &NlClient {
+        &self.client
+    }
+}
+
+struct Connection {
+    client: NlClient,
+    last_used: Instant,
+}
+
+/// Opens and reuses one socket per netlink protocol
+///
+/// Sockets are opened the first time a handle for their protocol is requested. Use
+/// `close_idle` periodically to close the sockets that are no longer used.
+#[derive(Default)]
+pub struct NlConnectionManager {
+    connections: Mutex<HashMap<NlFamily, Connection>>,
+}
+
+impl NlConnectionManager {
+    /// Create a manager without open sockets
+    pub fn new() -> Self {
+        NlConnectionManager::default()
+    }
+
+    fn connections(&self) -> MutexGuard<'_, HashMap<NlFamily, Connection>> {
+        self.connections.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get a handle for the socket of protocol `F`, opening it if it is not open yet
+    pub fn connection<F>(&self) -> Result<NlConnection<F>, io::Error>
+    where
+        F: NlProtocol,
+    {
+        let mut connections = self.connections();
+        let connection = match connections.get_mut(&F::family()) {
+            Some(connection) => connection,
+            None => connections.entry(F::family()).or_insert(Connection {
+                client: NlClient::connect(F::family(), None, None)?,
+                last_used: Instant::now(),
+            }),
+        };
+        connection.last_used = Instant::now();
+        Ok(NlConnection {
+            client: connection.client.clone(),
+            protocol: PhantomData,
+        })
+    }
+
+    /// Close the sockets without handles or outstanding requests that no handle was requested
+    /// for during `idle` and return the number of sockets closed
+    ///
+    /// A socket that is still in use stays open until the last handle is dropped and
+    /// `close_idle` is called again.
+    pub fn close_idle(&self, idle: Duration) -> usize {
+        let mut connections = self.connections();
+        let open = connections.len();
+        connections.retain(|_, connection| {
+            connection.client.router.is_shared() || connection.last_used.elapsed() < idle
+        });
+        open - connections.len()
+    }
+
+    /// Number of open sockets
+    pub fn open_connections(&self) -> usize {
+        self.connections().len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        consts::{CtrlAttr, CtrlCmd, GenlId, Nlmsg, RtAddrFamily, Rtm},
+        genl::{GenlFamily, Genlmsghdr},
+        nl::NlEmpty,
+        nlattr::Nlattr,
+        rtnl::Ifinfomsg,
+    };
+
+    fn getlink(index: i32) -> Nlmsghdr<Rtm, Ifinfomsg> {
+        let mut ifi = Ifinfomsg::dump(RtAddrFamily::Unspecified);
+        ifi.ifi_index = index;
+        Nlmsghdr::request(Rtm::Getlink, ifi)
+    }
+
+    #[test]
+    fn test_client_talk() {
+        let client = NlClient::connect(NlFamily::Route, None, None).unwrap();
+
+        // Unrelated messages received before the reply are skipped
+        let port = client.get_ref().port_id().unwrap();
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        s.send_nl_to(
+            Nlmsghdr::request(Rtm::Newlink, getlink(1).nl_payload),
+            port,
+            0,
+        )
+        .unwrap();
+
+        let link = client.talk::<_, _, Ifinfomsg>(getlink(1)).unwrap();
+        assert_eq!(link.ifi_index, 1);
+        match client.talk::<_, _, Ifinfomsg>(getlink(i32::MAX)) {
+            Err(e) => assert_eq!(e.errno(), Some(libc::ENODEV)),
+            r => panic!("Expected ENODEV, got {:?}", r),
+        }
+        assert!(client
+            .talk::<_, _, NlEmpty>(Nlmsghdr::request(Nlmsg::Noop, NlEmpty))
+            .is_err());
+    }
+
+    #[test]
+    fn test_connection_manager() {
+        use crate::socket::protocol::{Generic, Route};
+
+        let manager = NlConnectionManager::new();
+        let route = manager.connection::<Route>().unwrap();
+        let port = route.as_client().get_ref().port_id().unwrap();
+        let link = route.talk::<_, _, Ifinfomsg>(getlink(1)).unwrap();
+        assert_eq!(link.ifi_index, 1);
+        let route2 = manager.connection::<Route>().unwrap();
+        assert_eq!(route2.as_client().get_ref().port_id().unwrap(), port);
+
+        let genl = manager.connection::<Generic>().unwrap();
+        assert_eq!(manager.open_connections(), 2);
+        drop(genl);
+
+        // Sockets are only closed once unused and idle
+        assert_eq!(manager.close_idle(Duration::from_secs(60)), 0);
+        assert_eq!(manager.close_idle(Duration::from_secs(0)), 1);
+        drop(route);
+        let links = route2
+            .send::<_, _, Rtm, Ifinfomsg>(Nlmsghdr::dump_request(
+                Rtm::Getlink,
+                Ifinfomsg::dump(RtAddrFamily::Unspecified),
+            ))
+            .unwrap();
+        drop(route2);
+        assert_eq!(manager.close_idle(Duration::from_secs(0)), 0);
+        assert!(links.count() > 0);
+        assert_eq!(manager.close_idle(Duration::from_secs(0)), 1);
+        assert_eq!(manager.open_connections(), 0);
+    }
+
+    #[test]
+    fn test_client_talk_genl() {
+        let client = NlClient::connect(NlFamily::Generic, None, None).unwrap();
+        let attrs = vec![Nlattr::new(None, CtrlAttr::FamilyName, "nlctrl").unwrap()];
+        let genl = Genlmsghdr::new(CtrlCmd::Getfamily, 2, attrs).unwrap();
+        let reply = client
+            .talk::<_, _, Genlmsghdr<CtrlCmd, CtrlAttr>>(Nlmsghdr::request(GenlId::Ctrl, genl))
+            .unwrap();
+        assert_eq!(GenlFamily::parse(&reply).unwrap().name, "nlctrl");
+    }
+}
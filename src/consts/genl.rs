@@ -18,3 +18,16 @@ impl_var_trait!(
     DelmcastGrp => libc::CTRL_CMD_DELMCAST_GRP as u8,
     GetmcastGrp => libc::CTRL_CMD_GETMCAST_GRP as u8
 );
+
+// Defined in include/uapi/linux/genetlink.h but only exported by libc for glibc targets
+const GENL_UNS_ADMIN_PERM: u32 = 0x10;
+
+impl_var!(
+    /// Values for the flags of an operation of a generic netlink family
+    GenlOpFlag, u32,
+    AdminPerm => libc::GENL_ADMIN_PERM as u32,
+    CmdCapDo => libc::GENL_CMD_CAP_DO as u32,
+    CmdCapDump => libc::GENL_CMD_CAP_DUMP as u32,
+    CmdCapHaspol => libc::GENL_CMD_CAP_HASPOL as u32,
+    UnsAdminPerm => GENL_UNS_ADMIN_PERM
+);
@@ -4,6 +4,34 @@
 #[doc(hidden)]
 macro_rules! impl_var_base {
     ($name:ident, $ty:ty, $( $( #[cfg($meta:meta)] )* $var:ident => $val:expr ),* ) => {
+        impl $name {
+            /// Convert from the integer value returning an error instead of
+            /// `UnrecognizedVariant` if the value does not correspond to a known variant
+            pub fn try_from_value(v: $ty) -> Result<Self, $crate::err::DeError> {
+                match $name::from(v) {
+                    $name::UnrecognizedVariant(_) => Err($crate::err::DeError::new(&format!(
+                        "Unrecognized value {} for {}",
+                        v,
+                        stringify!($name)
+                    ))),
+                    var => Ok(var),
+                }
+            }
+
+            /// Get the integer value of the variant
+            pub fn value(&self) -> $ty {
+                <$ty>::from(self)
+            }
+
+            /// Get the integer value if the variant is `UnrecognizedVariant`
+            pub fn unrecognized_value(&self) -> Option<$ty> {
+                match *self {
+                    $name::UnrecognizedVariant(i) => Some(i),
+                    _ => None,
+                }
+            }
+        }
+
         impl From<$ty> for $name {
             fn from(v: $ty) -> Self {
                 match v {
@@ -49,22 +77,120 @@ macro_rules! impl_var_base {
         impl $crate::Nl for $name {
             fn serialize(&self, mem: &mut $crate::StreamWriteBuffer) -> Result<(), $crate::err::SerError> {
                 let v: $ty = self.clone().into();
-                v.serialize(mem)
+                $crate::Nl::serialize(&v, mem)
             }
 
             fn deserialize<T>(mem: &mut $crate::StreamReadBuffer<T>) -> Result<Self, $crate::err::DeError>
                     where T: AsRef<[u8]> {
-                let v = <$ty>::deserialize(mem)?;
+                let v = <$ty as $crate::Nl>::deserialize(mem)?;
                 Ok(v.into())
             }
 
             fn size(&self) -> usize {
                 std::mem::size_of::<$ty>()
             }
+
+            fn type_size() -> Option<usize> {
+                Some(std::mem::size_of::<$ty>())
+            }
+
+            fn check_attr_types(&self) -> Result<(), $crate::err::DeError> {
+                $name::try_from_value(self.value()).map(|_| ())
+            }
+        }
+
+        $crate::__impl_var_serde!($name, $ty, $( $( #[cfg($meta)] )* $var ),* );
+    };
+}
+
+// Known variants are serialized by name and unrecognized variants by their integer value.
+// Defined twice so that the `serde` feature of `neli` and not of the calling crate controls
+// whether the implementation is generated.
+#[cfg(feature = "serde")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __impl_var_serde {
+    ($name:ident, $ty:ty, $( $( #[cfg($meta:meta)] )* $var:ident ),* ) => {
+        impl $crate::__serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: $crate::__serde::Serializer,
+            {
+                match *self {
+                    $(
+                        $(
+                            #[cfg($meta)]
+                        )*
+                        $name::$var => serializer.serialize_str(stringify!($var)),
+                    )*
+                    $name::UnrecognizedVariant(i) => {
+                        $crate::__serde::Serialize::serialize(&i, serializer)
+                    }
+                }
+            }
+        }
+
+        impl<'de> $crate::__serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: $crate::__serde::Deserializer<'de>,
+            {
+                struct VarVisitor;
+
+                impl<'de> $crate::__serde::de::Visitor<'de> for VarVisitor {
+                    type Value = $name;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        write!(f, "a variant name or integer value for {}", stringify!($name))
+                    }
+
+                    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+                    where
+                        E: $crate::__serde::de::Error,
+                    {
+                        match v {
+                            $(
+                                $(
+                                    #[cfg($meta)]
+                                )*
+                                s if s == stringify!($var) => Ok($name::$var),
+                            )*
+                            _ => Err(E::invalid_value($crate::__serde::de::Unexpected::Str(v), &self)),
+                        }
+                    }
+
+                    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+                    where
+                        E: $crate::__serde::de::Error,
+                    {
+                        <$ty as std::convert::TryFrom<u64>>::try_from(v)
+                            .map($name::from)
+                            .map_err(|_| E::invalid_value($crate::__serde::de::Unexpected::Unsigned(v), &self))
+                    }
+
+                    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+                    where
+                        E: $crate::__serde::de::Error,
+                    {
+                        <$ty as std::convert::TryFrom<i64>>::try_from(v)
+                            .map($name::from)
+                            .map_err(|_| E::invalid_value($crate::__serde::de::Unexpected::Signed(v), &self))
+                    }
+                }
+
+                deserializer.deserialize_any(VarVisitor)
+            }
         }
     };
 }
 
+#[cfg(not(feature = "serde"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __impl_var_serde {
+    ($name:ident, $ty:ty, $( $( #[cfg($meta:meta)] )* $var:ident ),* ) => {};
+}
+
 #[macro_export]
 /// For naming a new enum, passing in what type it serializes to and deserializes
 /// from, and providing a mapping from variants to expressions (such as libc consts) that
@@ -112,7 +238,7 @@ macro_rules! impl_var {
             UnrecognizedVariant($ty),
         }
 
-        impl_var_base!($name, $ty, $( $( #[cfg($meta)] )* $var => $val),* );
+        $crate::impl_var_base!($name, $ty, $( $( #[cfg($meta)] )* $var => $val),* );
     );
     (
         $name:ident, $ty:ty,
@@ -133,7 +259,7 @@ macro_rules! impl_var {
             UnrecognizedVariant($ty),
         }
 
-        impl_var_base!($name, $ty, $( $( #[cfg($meta:meta)] )* $var => $val),* );
+        $crate::impl_var_base!($name, $ty, $( $( #[cfg($meta)] )* $var => $val),* );
     );
 }
 
@@ -168,7 +294,7 @@ macro_rules! impl_trait {
 macro_rules! impl_var_trait {
     ( $( #[$outer:meta] )* $name:ident, $ty:ty, $impl_name:ident,
       $( $( #[cfg($meta:meta)] )* $var:ident => $val:expr ),* ) => ( // with comments
-        impl_var!( $(#[$outer])*
+        $crate::impl_var!( $(#[$outer])*
             $name, $ty, $( $( #[cfg($meta)] )* $var => $val ),*
         );
 
@@ -176,8 +302,42 @@ macro_rules! impl_var_trait {
     );
     ( $name:ident, $ty:ty, $impl_name:ident,
       $( $( #[cfg($meta:meta)] )* $var:ident => $val:expr ),* ) => ( // without comments
-        impl_var!($name, $ty, $( $( #[cfg($meta)] )* $var => $val ),* );
+        $crate::impl_var!($name, $ty, $( $( #[cfg($meta)] )* $var => $val ),* );
 
         impl $impl_name for $name {}
     );
 }
+
+#[macro_export]
+/// For associating attribute type constants with the Rust type of their payload.
+/// Each entry creates a unit struct implementing `neli::nlattr::TypedAttr` that can be passed
+/// to the typed getters such as `AttrHandle::get`.
+///
+/// # Usage
+/// ```ignore
+/// impl_typed_attr!(MyNetlinkProtoAttrs,
+///     /// The ID of the object
+///     MyId => Id: u32,
+///     MyName => Name: String
+/// );
+///
+/// let id: Option<u32> = handle.get::<MyId>()?;
+/// ```
+macro_rules! impl_typed_attr {
+    ( $attr_ty:ident, $( $( #[$outer:meta] )* $name:ident => $var:ident: $payload:ty ),* ) => {
+        $(
+            $(#[$outer])*
+            #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+            pub struct $name;
+
+            impl $crate::nlattr::TypedAttr for $name {
+                type Type = $attr_ty;
+                type Payload = $payload;
+
+                fn attr_type() -> $attr_ty {
+                    $attr_ty::$var
+                }
+            }
+        )*
+    };
+}
@@ -8,6 +8,10 @@
 //! in `/usr/include/linux` on many distros. You can also see `man 3 netlink`, `man 7 netlink`,
 //! and `man 7 rtnetlink` for more information.
 //!
+//! The values themselves are taken from the `libc` crate or defined in Rust in this module
+//! rather than extracted from C headers at build time, so no C toolchain or kernel headers are
+//! needed to build or cross compile `neli`.
+//!
 //! # Design decisions
 //!
 //! * Macros are exported so that these conventions are extensible and usable for data types
@@ -52,10 +56,22 @@ pub mod socket;
 pub use crate::consts::socket::*;
 
 /// Reimplementation of alignto macro in C
-pub fn alignto(len: usize) -> usize {
+///
+/// This is a `const fn` so it can be used to compute the length of a `Pad` type.
+pub const fn alignto(len: usize) -> usize {
     (len + libc::NLA_ALIGNTO as usize - 1) & !(libc::NLA_ALIGNTO as usize - 1)
 }
 
+/// Check whether an attribute starting `offset` bytes into a netlink message must be preceded by
+/// a pad attribute so that its payload is 8 byte aligned
+///
+/// This mirrors `nla_need_padding_for_64bit` in the kernel, which inserts attributes like
+/// `IFLA_PAD` or `TCA_PAD` before 64-bit counters.
+pub const fn needs_64bit_pad(offset: usize) -> bool {
+    // The payload follows the 4 byte attribute header
+    (offset + 4) & 7 != 0
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -68,4 +84,30 @@ mod test {
         let unspec_variant = CtrlCmd::from(libc::CTRL_CMD_UNSPEC as u8);
         assert_eq!(unspec_variant, CtrlCmd::Unspec);
     }
+
+    #[test]
+    fn test_checked_conversions() {
+        assert_eq!(CtrlCmd::try_from_value(3).unwrap(), CtrlCmd::Getfamily);
+        assert!(CtrlCmd::try_from_value(200).is_err());
+
+        assert_eq!(CtrlCmd::Getfamily.value(), 3);
+        assert_eq!(CtrlCmd::Getfamily.unrecognized_value(), None);
+        assert_eq!(CtrlCmd::from(200).value(), 200);
+        assert_eq!(CtrlCmd::from(200).unrecognized_value(), Some(200));
+    }
+
+    #[test]
+    fn test_needs_64bit_pad() {
+        assert!(!needs_64bit_pad(4));
+        assert!(needs_64bit_pad(8));
+        assert!(needs_64bit_pad(16));
+        assert!(!needs_64bit_pad(20));
+    }
+
+    #[test]
+    fn test_custom_family() {
+        assert_eq!(NlFamily::custom(libc::NETLINK_GENERIC), NlFamily::Generic);
+        assert_eq!(NlFamily::custom(30), NlFamily::UnrecognizedVariant(30));
+        assert_eq!(libc::c_int::from(NlFamily::custom(30)), 30);
+    }
 }
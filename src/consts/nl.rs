@@ -94,3 +94,10 @@ impl_var!(
     Create => libc::NLM_F_CREATE as u16,
     Append => libc::NLM_F_APPEND as u16
 );
+
+/// Flag in `nl_flags` of an `Nlmsg::Error` message indicating that the payload of the request was
+/// not echoed - deserialized as `NlmF::Root` as the value is shared with that flag
+pub const NLM_F_CAPPED: u16 = 0x100;
+/// Flag in `nl_flags` of an `Nlmsg::Error` message indicating that extended ACK attributes follow
+/// the echoed request - deserialized as `NlmF::Match` as the value is shared with that flag
+pub const NLM_F_ACK_TLVS: u16 = 0x200;
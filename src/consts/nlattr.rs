@@ -24,3 +24,54 @@ impl_var_trait!(
     Name => libc::CTRL_ATTR_MCAST_GRP_NAME as u16,
     Id => libc::CTRL_ATTR_MCAST_GRP_ID as u16
 );
+
+impl_var_trait!(
+    /// Values for `nla_type` in the nested attributes describing an operation in `CtrlAttr::Ops`
+    CtrlAttrOp, u16, NlAttrType,
+    Unspec => libc::CTRL_ATTR_OP_UNSPEC as u16,
+    Id => libc::CTRL_ATTR_OP_ID as u16,
+    Flags => libc::CTRL_ATTR_OP_FLAGS as u16
+);
+
+impl_var_trait!(
+    /// Extended ACK attributes appended to `Nlmsgerr` when `NETLINK_EXT_ACK` is enabled
+    NlmsgerrAttr, u16, NlAttrType,
+    Unused => 0,
+    Msg => 1,
+    Offs => 2,
+    Cookie => 3
+);
+
+/// Flag in `nla_type` marking an attribute that contains nested attributes
+pub const NLA_F_NESTED: u16 = 1 << 15;
+/// Flag in `nla_type` marking an attribute with a payload in network byte order
+pub const NLA_F_NET_BYTEORDER: u16 = 1 << 14;
+/// Mask for the attribute type without the `NLA_F_NESTED` and `NLA_F_NET_BYTEORDER` flags
+pub const NLA_TYPE_MASK: u16 = !(NLA_F_NESTED | NLA_F_NET_BYTEORDER);
+
+impl_typed_attr!(CtrlAttr,
+    /// `CtrlAttr::FamilyId` with a `u16` payload
+    CtrlAttrFamilyId => FamilyId: u16,
+    /// `CtrlAttr::FamilyName` with a string payload
+    CtrlAttrFamilyName => FamilyName: String,
+    /// `CtrlAttr::Version` with a `u32` payload
+    CtrlAttrVersion => Version: u32,
+    /// `CtrlAttr::Hdrsize` with a `u32` payload
+    CtrlAttrHdrsize => Hdrsize: u32,
+    /// `CtrlAttr::Maxattr` with a `u32` payload
+    CtrlAttrMaxattr => Maxattr: u32
+);
+
+impl_typed_attr!(CtrlAttrMcastGrp,
+    /// `CtrlAttrMcastGrp::Name` with a string payload
+    CtrlAttrMcastGrpName => Name: String,
+    /// `CtrlAttrMcastGrp::Id` with a `u32` payload
+    CtrlAttrMcastGrpId => Id: u32
+);
+
+impl_typed_attr!(CtrlAttrOp,
+    /// `CtrlAttrOp::Id` with a `u32` payload
+    CtrlAttrOpId => Id: u32,
+    /// `CtrlAttrOp::Flags` with a `u32` payload
+    CtrlAttrOpFlags => Flags: u32
+);
@@ -70,7 +70,25 @@ impl_var!(
     Redirect => libc::RTPROT_REDIRECT,
     Kernel => libc::RTPROT_KERNEL,
     Boot => libc::RTPROT_BOOT,
-    Static => libc::RTPROT_STATIC
+    Static => libc::RTPROT_STATIC,
+    Gated => 8,
+    Ra => 9,
+    Mrt => 10,
+    Zebra => 11,
+    Bird => 12,
+    Dnrouted => 13,
+    Xorp => 14,
+    Ntk => 15,
+    Dhcp => 16,
+    Mrouted => 17,
+    Keepalived => 18,
+    Babel => 42,
+    Openr => 99,
+    Bgp => 186,
+    Isis => 187,
+    Ospf => 188,
+    Rip => 189,
+    Eigrp => 192
 );
 
 impl_var!(
@@ -95,6 +113,13 @@ impl_var!(
     Local => libc::RT_TABLE_LOCAL
 );
 
+/// Table IDs above 255 are only carried by `Rta::Table` so they are compared as `u32`s
+impl From<RtTable> for u32 {
+    fn from(table: RtTable) -> Self {
+        u32::from(u8::from(table))
+    }
+}
+
 impl_var!(
     /// `rtm_flags`
     /// Flags for rtnetlink messages
@@ -208,6 +233,56 @@ impl_var_trait!(
     SlaveData => libc::IFLA_INFO_SLAVE_DATA
 );
 
+impl_typed_attr!(Ifla,
+    /// `Ifla::Ifname` with a string payload
+    IflaIfname => Ifname: String,
+    /// `Ifla::Mtu` with a `u32` payload
+    IflaMtu => Mtu: u32,
+    /// `Ifla::Link` with a `u32` payload
+    IflaLink => Link: u32,
+    /// `Ifla::Master` with a `u32` payload
+    IflaMaster => Master: u32,
+    /// `Ifla::Txqlen` with a `u32` payload
+    IflaTxqlen => Txqlen: u32,
+    /// `Ifla::Operstate` with an `IfOper` payload
+    IflaOperstate => Operstate: IfOper,
+    /// `Ifla::Address` with a `MacAddress` payload - only valid for links with 6 byte hardware
+    /// addresses such as Ethernet
+    IflaAddress => Address: crate::types::MacAddress,
+    /// `Ifla::Broadcast` with a `MacAddress` payload - only valid for links with 6 byte hardware
+    /// addresses such as Ethernet
+    IflaBroadcast => Broadcast: crate::types::MacAddress,
+    /// `Ifla::Stats64` with a `LinkStats64` payload
+    IflaStats64 => Stats64: crate::rtnl::LinkStats64,
+    /// `Ifla::Linkmode` with a `u8` payload
+    IflaLinkmode => Linkmode: u8,
+    /// `Ifla::Group` with a `u32` payload
+    IflaGroup => Group: u32,
+    /// `Ifla::Promiscuity` with a `u32` payload
+    IflaPromiscuity => Promiscuity: u32,
+    /// `Ifla::NumTxQueues` with a `u32` payload
+    IflaNumTxQueues => NumTxQueues: u32,
+    /// `Ifla::NumRxQueues` with a `u32` payload
+    IflaNumRxQueues => NumRxQueues: u32,
+    /// `Ifla::Carrier` with a `u8` payload
+    IflaCarrier => Carrier: u8
+);
+
+/// `IflaInfo` attributes are nested in `Ifla::Linkinfo` and parsed as `Rtattr`s
+impl RtaType for IflaInfo {}
+
+impl_var_trait!(
+    /// Enum for use with `Rtattr.rta_type`.
+    /// Values are VLAN attributes nested in `IflaInfo::Data` when the link kind is `vlan`.
+    IflaVlan, libc::c_ushort, RtaType,
+    Unspec => 0,
+    Id => 1,
+    Flags => 2,
+    EgressQos => 3,
+    IngressQos => 4,
+    Protocol => 5
+);
+
 impl_var_trait!(
     /// Enum for use with `Rtattr.rta_type`.
     /// Values are interface address message attributes. Used with `Ifaddrmsg`.
@@ -266,6 +341,19 @@ impl_var_trait!(
     TtlPropagate => libc::RTA_TTL_PROPAGATE
 );
 
+impl_typed_attr!(Rta,
+    /// `Rta::Table` with a `u32` payload
+    RtaTable => Table: u32,
+    /// `Rta::Iif` with a `u32` payload
+    RtaIif => Iif: u32,
+    /// `Rta::Oif` with a `u32` payload
+    RtaOif => Oif: u32,
+    /// `Rta::Priority` with a `u32` payload
+    RtaPriority => Priority: u32,
+    /// `Rta::Mark` with a `u32` payload
+    RtaMark => Mark: u32
+);
+
 impl_var_trait!(
     /// Enum for use with `Rtattr.rta_type` -
     /// Values specify queuing discipline attributes. Used with `Tcmsg`.
@@ -278,7 +366,14 @@ impl_var_trait!(
     Rate => libc::TCA_RATE,
     Fcnt => libc::TCA_FCNT,
     Stats2 => libc::TCA_STATS2,
-    Stab => libc::TCA_STAB
+    Stab => libc::TCA_STAB,
+    #[cfg(target_env="gnu")]
+    Pad => libc::TCA_PAD
+);
+
+impl_typed_attr!(Tca,
+    /// `Tca::Kind` with a string payload
+    TcaKind => Kind: String
 );
 
 impl_var_trait!(
@@ -327,6 +422,18 @@ impl_var!(
     None => libc::ARPHRD_NONE
 );
 
+impl_var!(
+    /// RFC 2863 operational states of interfaces in `Ifla::Operstate`
+    IfOper, u8,
+    Unknown => libc::IF_OPER_UNKNOWN as u8,
+    Notpresent => libc::IF_OPER_NOTPRESENT as u8,
+    Down => libc::IF_OPER_DOWN as u8,
+    Lowerlayerdown => libc::IF_OPER_LOWERLAYERDOWN as u8,
+    Testing => libc::IF_OPER_TESTING as u8,
+    Dormant => libc::IF_OPER_DORMANT as u8,
+    Up => libc::IF_OPER_UP as u8
+);
+
 impl_var!(
     /// Values for `ifi_flags` in `Ifinfomsg`
     Iff, libc::c_uint,
@@ -352,3 +459,33 @@ impl_var!(
 
     // Possibly more types here - need to look into private flags for interfaces
 );
+
+impl_var!(
+    /// rtnetlink multicast groups - join them with `NlSocket::add_mcast_membership` to receive
+    /// notifications of changes such as `Rtm::Newaddr` and `Rtm::Deladdr` for IPv4 addresses
+    /// with `Rtnlgrp::Ipv4Ifaddr`
+    Rtnlgrp, u32,
+    None => libc::RTNLGRP_NONE,
+    Link => libc::RTNLGRP_LINK,
+    Notify => libc::RTNLGRP_NOTIFY,
+    Neigh => libc::RTNLGRP_NEIGH,
+    Tc => libc::RTNLGRP_TC,
+    Ipv4Ifaddr => libc::RTNLGRP_IPV4_IFADDR,
+    Ipv4Mroute => libc::RTNLGRP_IPV4_MROUTE,
+    Ipv4Route => libc::RTNLGRP_IPV4_ROUTE,
+    Ipv4Rule => libc::RTNLGRP_IPV4_RULE,
+    Ipv6Ifaddr => libc::RTNLGRP_IPV6_IFADDR,
+    Ipv6Mroute => libc::RTNLGRP_IPV6_MROUTE,
+    Ipv6Route => libc::RTNLGRP_IPV6_ROUTE,
+    Ipv6Ifinfo => libc::RTNLGRP_IPV6_IFINFO,
+    Ipv6Prefix => libc::RTNLGRP_IPV6_PREFIX,
+    Ipv6Rule => libc::RTNLGRP_IPV6_RULE,
+    NdUseropt => libc::RTNLGRP_ND_USEROPT,
+    Ipv4Netconf => libc::RTNLGRP_IPV4_NETCONF,
+    Ipv6Netconf => libc::RTNLGRP_IPV6_NETCONF,
+    Mdb => libc::RTNLGRP_MDB,
+    MplsRoute => libc::RTNLGRP_MPLS_ROUTE,
+    Nsid => libc::RTNLGRP_NSID,
+    Nexthop => libc::RTNLGRP_NEXTHOP,
+    Brvlan => libc::RTNLGRP_BRVLAN
+);
@@ -39,3 +39,28 @@ impl_var!(
     Rdma => libc::NETLINK_RDMA,
     Crypto => libc::NETLINK_CRYPTO
 );
+
+impl NlFamily {
+    /// Protocol number that may not have a named variant, such as one registered by a vendor or
+    /// out of tree kernel module - numbers without a named variant are stored in
+    /// `UnrecognizedVariant` and can be passed to `NlSocket` like any other family
+    pub fn custom(proto: libc::c_int) -> Self {
+        NlFamily::from(proto)
+    }
+}
+
+impl_var!(
+    /// Netlink socket options at the `SOL_NETLINK` level - values are given as numbers as `libc`
+    /// does not define all of them for every target
+    NlSockOpt, libc::c_int,
+    AddMembership => 1,
+    DropMembership => 2,
+    PktInfo => 3,
+    BroadcastError => 4,
+    NoEnobufs => 5,
+    ListenAllNsid => 8,
+    ListMemberships => 9,
+    CapAck => 10,
+    ExtAck => 11,
+    GetStrictChk => 12
+);
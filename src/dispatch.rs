@@ -0,0 +1,293 @@
+//! Callback based handling of received messages
+//!
+//! `NlDispatcher` receives datagrams from an `NlSocket` and calls the handler registered for the
+//! type of each message, similar to the `nl_cb` callbacks of libnl. `NLMSG_DONE` messages, ACKs,
+//! and errors, either reported by the kernel or returned while parsing a message, have handlers
+//! of their own. Each handler returns an `NlCbAction` telling the dispatcher whether to continue
+//! receiving.
+//!
+//! By default, the dispatcher stops at the end of a dump and at an ACK, returns errors to the
+//! caller, and ignores messages without a handler. This makes `NlDispatcher::run` suitable both
+//! for handling the response to a request and, with handlers that never stop, as an event loop
+//! for multicast notifications.
+
+use std::collections::HashMap;
+
+use buffering::StreamReadBuffer;
+
+use crate::{
+    consts::{alignto, NlType, Nlmsg},
+    err::{NlError, Nlmsgerr},
+    nl::{DeMode, Nlmsghdr, RawHeader, NLMSG_HDRLEN},
+    socket::{done_error, nlmsgerr_error, NlSocket},
+    DeError, Nl,
+};
+
+/// What the dispatcher does after a handler returns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NlCbAction {
+    /// Handle the next message
+    Continue,
+    /// Stop and return to the caller - the remaining messages of the datagram are discarded
+    Stop,
+}
+
+// Parses the message and calls the handler registered for it
+type MsgHandler<'a> =
+    Box<dyn FnMut(&[u8], DeMode) -> Result<Result<NlCbAction, NlError>, DeError> + 'a>;
+type EventHandler<'a> = Box<dyn FnMut() -> Result<NlCbAction, NlError> + 'a>;
+type ErrorHandler<'a> = Box<dyn FnMut(NlError) -> Result<NlCbAction, NlError> + 'a>;
+
+/// Routes received messages to handlers registered by message type
+///
+/// Handlers may borrow from the enclosing scope for the lifetime `'a` of the dispatcher, for
+/// example to collect the messages of a dump into a local `Vec`.
+#[derive(Default)]
+pub struct NlDispatcher<'a> {
+    handlers: HashMap<u16, MsgHandler<'a>>,
+    unhandled: Option<MsgHandler<'a>>,
+    done: Option<EventHandler<'a>>,
+    ack: Option<EventHandler<'a>>,
+    error: Option<ErrorHandler<'a>>,
+}
+
+impl<'a> NlDispatcher<'a> {
+    /// Create a dispatcher without handlers
+    pub fn new() -> Self {
+        NlDispatcher::default()
+    }
+
+    /// Call `handler` with the messages of type `nl_type` parsed with payloads of type `P`,
+    /// replacing the handler previously registered for the type
+    ///
+    /// Messages that fail to parse are passed to the error handler instead.
+    pub fn on<T, P, F>(&mut self, nl_type: T, mut handler: F) -> &mut Self
+    where
+        T: NlType,
+        P: Nl,
+        F: FnMut(Nlmsghdr<T, P>) -> Result<NlCbAction, NlError> + 'a,
+    {
+        self.handlers.insert(
+            nl_type.into(),
+            Box::new(move |msg, mode| {
+                Nlmsghdr::deserialize_with_mode(&mut StreamReadBuffer::new(msg), mode)
+                    .map(&mut handler)
+            }),
+        );
+        self
+    }
+
+    /// Call `handler` with the messages of types without a handler - these are ignored by default
+    pub fn on_unhandled<F>(&mut self, mut handler: F) -> &mut Self
+    where
+        F: FnMut(Nlmsghdr<u16, Vec<u8>>) -> Result<NlCbAction, NlError> + 'a,
+    {
+        self.unhandled = Some(Box::new(move |msg, mode| {
+            Nlmsghdr::deserialize_with_mode(&mut StreamReadBuffer::new(msg), mode).map(&mut handler)
+        }));
+        self
+    }
+
+    /// Call `handler` at the `NLMSG_DONE` message ending a dump - the default stops
+    pub fn on_done<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: FnMut() -> Result<NlCbAction, NlError> + 'a,
+    {
+        self.done = Some(Box::new(handler));
+        self
+    }
+
+    /// Call `handler` at an ACK - the default stops
+    pub fn on_ack<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: FnMut() -> Result<NlCbAction, NlError> + 'a,
+    {
+        self.ack = Some(Box::new(handler));
+        self
+    }
+
+    /// Call `handler` with errors reported by the kernel and errors parsing a message - the
+    /// default returns the error from `dispatch` and `run`
+    pub fn on_error<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: FnMut(NlError) -> Result<NlCbAction, NlError> + 'a,
+    {
+        self.error = Some(Box::new(handler));
+        self
+    }
+
+    /// Receive one datagram from `socket` and dispatch its messages
+    ///
+    /// Datagrams are received directly from the socket so messages left in the buffer of the
+    /// socket by a previous `NlSocket::recv_nl` are not seen and sequence number and PID checks
+    /// set on the socket are not applied.
+    pub fn dispatch(&mut self, socket: &NlSocket) -> Result<NlCbAction, NlError> {
+        let mut datagram = vec![0; socket.peek_len()?];
+        let len = socket.recv(&mut datagram, 0)?;
+        if len == 0 {
+            return Err(NlError::new("No data could be read from the socket"));
+        }
+        datagram.truncate(len);
+
+        let mode = socket.de_mode();
+        let mut pos = 0;
+        while pos + NLMSG_HDRLEN <= datagram.len() {
+            let header = match RawHeader::peek(&datagram[pos..]) {
+                Ok(header) => header,
+                Err(e) => return self.handle_error(e),
+            };
+            let msg = &datagram[pos..pos + header.nl_len];
+            pos += alignto(header.nl_len);
+
+            if self.handle(msg, mode)? == NlCbAction::Stop {
+                return Ok(NlCbAction::Stop);
+            }
+        }
+        Ok(NlCbAction::Continue)
+    }
+
+    /// Receive and dispatch datagrams from `socket` until a handler stops
+    pub fn run(&mut self, socket: &NlSocket) -> Result<(), NlError> {
+        while self.dispatch(socket)? == NlCbAction::Continue {}
+        Ok(())
+    }
+
+    fn handle(&mut self, msg: &[u8], mode: DeMode) -> Result<NlCbAction, NlError> {
+        let nl_type = RawHeader::read(msg).nl_type;
+        if nl_type == u16::from(Nlmsg::Done) {
+            if let Some(e) = done_error(&msg[NLMSG_HDRLEN..]) {
+                return self.handle_error(e);
+            }
+            return match self.done {
+                Some(ref mut handler) => handler(),
+                None => Ok(NlCbAction::Stop),
+            };
+        }
+        if nl_type == u16::from(Nlmsg::Error) {
+            let err = match Nlmsghdr::<Nlmsg, Nlmsgerr<Nlmsg>>::deserialize(
+                &mut StreamReadBuffer::new(msg),
+            ) {
+                Ok(err) => err,
+                Err(e) => return self.handle_error(NlError::from(e)),
+            };
+            if err.nl_payload.error != 0 {
+                return self.handle_error(nlmsgerr_error(&err));
+            }
+            return match self.ack {
+                Some(ref mut handler) => handler(),
+                None => Ok(NlCbAction::Stop),
+            };
+        }
+        if nl_type == u16::from(Nlmsg::Noop) {
+            return Ok(NlCbAction::Continue);
+        }
+
+        let result = match self.handlers.get_mut(&nl_type) {
+            Some(handler) => handler(msg, mode),
+            None => match self.unhandled {
+                Some(ref mut handler) => handler(msg, mode),
+                None => Ok(Ok(NlCbAction::Continue)),
+            },
+        };
+        match result {
+            Ok(action) => action,
+            Err(e) => self.handle_error(NlError::from(e)),
+        }
+    }
+
+    fn handle_error(&mut self, e: NlError) -> Result<NlCbAction, NlError> {
+        match self.error {
+            Some(ref mut handler) => handler(e),
+            None => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        consts::{NlFamily, RtAddrFamily, Rtm},
+        nl::NlEmpty,
+        rtnl::Ifinfomsg,
+    };
+
+    fn getlink(index: i32) -> Nlmsghdr<Rtm, Ifinfomsg> {
+        let mut ifi = Ifinfomsg::dump(RtAddrFamily::Unspecified);
+        ifi.ifi_index = index;
+        Nlmsghdr::ack_request(Rtm::Getlink, ifi)
+    }
+
+    #[test]
+    fn test_dispatcher_dump() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        s.send_nl(Nlmsghdr::dump_request(
+            Rtm::Getlink,
+            Ifinfomsg::dump(RtAddrFamily::Unspecified),
+        ))
+        .unwrap();
+
+        let mut links = Vec::new();
+        let mut done = false;
+        {
+            let mut dispatcher = NlDispatcher::new();
+            dispatcher
+                .on(Rtm::Newlink, |msg: Nlmsghdr<Rtm, Ifinfomsg>| {
+                    links.push(msg.nl_payload.ifi_index);
+                    Ok(NlCbAction::Continue)
+                })
+                .on_done(|| {
+                    done = true;
+                    Ok(NlCbAction::Stop)
+                });
+            dispatcher.run(&s).unwrap();
+        }
+        assert!(done);
+        assert!(links.contains(&1));
+    }
+
+    #[test]
+    fn test_dispatcher_ack_and_error() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+
+        // The reply is not handled and the ACK stops by default
+        s.send_nl(getlink(1)).unwrap();
+        let mut unhandled = 0;
+        {
+            let mut dispatcher = NlDispatcher::new();
+            dispatcher.on_unhandled(|msg| {
+                assert_eq!(msg.nl_type, u16::from(Rtm::Newlink));
+                unhandled += 1;
+                Ok(NlCbAction::Continue)
+            });
+            dispatcher.run(&s).unwrap();
+        }
+        assert_eq!(unhandled, 1);
+
+        s.send_nl(getlink(i32::MAX)).unwrap();
+        let err = NlDispatcher::new().run(&s).unwrap_err();
+        assert_eq!(err.errno(), Some(libc::ENODEV));
+
+        s.send_nl(getlink(i32::MAX)).unwrap();
+        s.send_nl(Nlmsghdr::ack_request(Nlmsg::Noop, NlEmpty))
+            .unwrap();
+        let mut errors = Vec::new();
+        let mut acks = 0;
+        {
+            let mut dispatcher = NlDispatcher::new();
+            dispatcher
+                .on_error(|e| {
+                    errors.push(e.errno());
+                    Ok(NlCbAction::Continue)
+                })
+                .on_ack(|| {
+                    acks += 1;
+                    Ok(NlCbAction::Stop)
+                });
+            dispatcher.run(&s).unwrap();
+        }
+        assert_eq!(errors, vec![Some(libc::ENODEV)]);
+        assert_eq!(acks, 1);
+    }
+}
@@ -17,14 +17,16 @@
 use std::{
     error::Error,
     fmt::{self, Display},
-    io, str, string,
+    io::{self, Read},
+    str, string,
 };
 
 use buffering::{StreamReadBuffer, StreamWriteBuffer};
 
 use crate::{
-    consts::NlType,
-    nl::{NlEmpty, Nlmsghdr},
+    consts::{alignto, NlType, NlmF, NlmsgerrAttr, NLM_F_ACK_TLVS, NLM_F_CAPPED},
+    nl::{deserialize_header, NlEmpty, Nlmsghdr, NLMSG_HDRLEN},
+    nlattr::AttrIter,
     Nl,
 };
 
@@ -42,11 +44,67 @@ macro_rules! try_err_compat {
 
 /// Struct representing netlink packets containing errors
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Nlmsgerr<T> {
     /// Error code
     pub error: libc::c_int,
     /// Packet header for request that failed
     pub nlmsg: Nlmsghdr<T, NlEmpty>,
+    /// Bytes following the echoed header - the payload of a failed request unless
+    /// `NLM_F_CAPPED` is set, followed by extended ACK attributes if `NLM_F_ACK_TLVS` is set
+    pub trailing: Vec<u8>,
+}
+
+impl<T> Nlmsgerr<T> {
+    /// Payload of the failed request echoed by the kernel, using the flags of the `Nlmsghdr`
+    /// containing this error
+    ///
+    /// The payload is empty for ACKs and for errors with `NLM_F_CAPPED` set, which the kernel
+    /// sends when `NlSocket::set_cap_ack` is enabled.
+    pub fn request_payload(&self, flags: &[NlmF]) -> Result<&[u8], DeError> {
+        if self.error == 0 || flags.iter().any(|f| u16::from(f) == NLM_F_CAPPED) {
+            return Ok(&[]);
+        }
+        let len = (self.nlmsg.nl_len as usize).saturating_sub(NLMSG_HDRLEN);
+        self.trailing
+            .get(..len)
+            .ok_or_else(|| DeError::new("Echoed request payload is truncated"))
+    }
+
+    /// Parse the extended ACK attributes using the flags of the `Nlmsghdr` containing this error
+    ///
+    /// Extended ACK attributes are only sent if `NlSocket::set_ext_ack` was enabled and the
+    /// kernel supports them, otherwise an empty `ExtAck` is returned.
+    pub fn ext_ack(&self, flags: &[NlmF]) -> Result<ExtAck, DeError> {
+        let mut ext_ack = ExtAck::default();
+        if !flags.iter().any(|f| u16::from(f) == NLM_F_ACK_TLVS) {
+            return Ok(ext_ack);
+        }
+        let echoed_len = alignto(self.request_payload(flags)?.len());
+        let tlvs = self
+            .trailing
+            .get(echoed_len..)
+            .ok_or_else(|| DeError::new("Extended ACK attributes are truncated"))?;
+        for attr in AttrIter::<NlmsgerrAttr>::new(tlvs) {
+            let attr = attr?;
+            match attr.nla_type {
+                NlmsgerrAttr::Msg => ext_ack.msg = Some(attr.get_payload_as::<String>()?),
+                NlmsgerrAttr::Offs => ext_ack.offset = Some(attr.get_payload_as::<u32>()?),
+                _ => (),
+            }
+        }
+        Ok(ext_ack)
+    }
+}
+
+/// Information the kernel appends to errors and ACKs when `NETLINK_EXT_ACK` is enabled
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExtAck {
+    /// Human readable description of the error
+    pub msg: Option<String>,
+    /// Offset of the attribute that caused the error in the request, starting from the netlink
+    /// header
+    pub offset: Option<u32>,
 }
 
 impl<T> Nl for Nlmsgerr<T>
@@ -55,7 +113,8 @@ where
 {
     fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
         self.error.serialize(mem)?;
-        self.nlmsg.serialize(mem)?;
+        self.nlmsg.serialize_with_len(self.nlmsg.nl_len, mem)?;
+        self.trailing.serialize(mem)?;
         self.pad(mem)?;
         Ok(())
     }
@@ -64,16 +123,38 @@ where
     where
         B: AsRef<[u8]>,
     {
-        let nlmsg = Nlmsgerr {
-            error: libc::c_int::deserialize(mem)?,
-            nlmsg: Nlmsghdr::<T, NlEmpty>::deserialize(mem)?,
+        let size_hint = mem.take_size_hint();
+        let error = libc::c_int::deserialize(mem)?;
+        // The echoed header keeps the length of the original request but the request payload is
+        // only included for errors, so the payload is kept as bytes rather than parsed.
+        let (nl_len, nl_type, nl_flags, nl_seq, nl_pid) = deserialize_header(mem)?;
+        let mut nlmsg = Nlmsgerr {
+            error,
+            nlmsg: Nlmsghdr {
+                nl_len,
+                nl_type,
+                nl_flags,
+                nl_seq,
+                nl_pid,
+                nl_payload: NlEmpty,
+            },
+            trailing: Vec::new(),
         };
-        nlmsg.strip(mem)?;
+        match size_hint {
+            Some(sh) => {
+                let trailing_len = sh.checked_sub(nlmsg.size()).ok_or_else(|| {
+                    DeError::new("Size hint is smaller than the netlink error header")
+                })?;
+                nlmsg.trailing = vec![0; trailing_len];
+                mem.read_exact(&mut nlmsg.trailing)?;
+            }
+            None => nlmsg.strip(mem)?,
+        }
         Ok(nlmsg)
     }
 
     fn size(&self) -> usize {
-        self.error.size() + self.nlmsg.size()
+        self.error.size() + self.nlmsg.size() + self.trailing.size()
     }
 }
 
@@ -88,15 +169,67 @@ pub enum NlError {
     BadSeq,
     /// Incorrect PID socket identifier in received message
     BadPid,
+    /// The socket is non-blocking and no message is available yet
+    WouldBlock,
+    /// The receive or send timeout set on the socket expired
+    Timeout,
+    /// A signal interrupted a blocking call before it completed - see
+    /// `NlSocket::set_interrupt_policy`
+    Interrupted,
+    /// The kernel flagged a dump response with `NLM_F_DUMP_INTR` because the dumped data
+    /// changed while it was being dumped, so the response may be inconsistent
+    DumpInterrupted,
+    /// The receive buffer of the socket overflowed and messages were dropped - see
+    /// `NlSocket::set_overrun_handler`
+    Overrun,
+    /// The datagram received was longer than the receive buffer so its end was discarded - see
+    /// `NlSocket::set_grow_recv_buffer`
+    Truncated {
+        /// Length of the whole datagram
+        len: usize,
+    },
+    /// Error from the operating system or the kernel identified by its `errno` value
+    Errno(libc::c_int),
+    /// Error returned by the kernel with extended ACK information - see `NlSocket::set_ext_ack`
+    ExtAck {
+        /// Error code
+        error: libc::c_int,
+        /// Extended ACK attributes
+        ext_ack: ExtAck,
+    },
 }
 
-try_err_compat!(NlError, io::Error, SerError, DeError);
+try_err_compat!(NlError, SerError, DeError);
+
+impl From<io::Error> for NlError {
+    fn from(v: io::Error) -> Self {
+        match v.kind() {
+            io::ErrorKind::WouldBlock => NlError::WouldBlock,
+            io::ErrorKind::Interrupted => NlError::Interrupted,
+            _ => match v.raw_os_error() {
+                Some(errno) => NlError::Errno(errno),
+                None => NlError::new(&v.to_string()),
+            },
+        }
+    }
+}
 
 impl NlError {
     /// Create new error from `&str`
     pub fn new(s: &str) -> Self {
         NlError::Msg(s.to_string())
     }
+
+    /// The `errno` value of an error from the operating system or the kernel, such as
+    /// `libc::ENOBUFS` when messages were dropped or a delivery error reported with
+    /// `NETLINK_BROADCAST_ERROR`
+    pub fn errno(&self) -> Option<libc::c_int> {
+        match *self {
+            NlError::Errno(errno) => Some(errno),
+            NlError::ExtAck { error, .. } => Some(-error),
+            _ => None,
+        }
+    }
 }
 
 /// Netlink protocol error
@@ -107,6 +240,29 @@ impl Display for NlError {
             NlError::NoAck => "No ack received",
             NlError::BadSeq => "Sequence number does not match the request",
             NlError::BadPid => "PID does not match the socket",
+            NlError::WouldBlock => "Operation would block",
+            NlError::Timeout => "Operation timed out",
+            NlError::Interrupted => "Operation interrupted by a signal",
+            NlError::DumpInterrupted => "Dump was interrupted by a change of the dumped data",
+            NlError::Overrun => "Socket receive buffer overrun, messages were dropped",
+            NlError::Truncated { len } => {
+                return write!(
+                    f,
+                    "Datagram of {} bytes was truncated to fit the receive buffer",
+                    len
+                )
+            }
+            NlError::Errno(errno) => return write!(f, "{}", io::Error::from_raw_os_error(errno)),
+            NlError::ExtAck { error, ref ext_ack } => {
+                write!(f, "{}", io::Error::from_raw_os_error(-error))?;
+                if let Some(ref msg) = ext_ack.msg {
+                    write!(f, ": {}", msg)?;
+                }
+                if let Some(offset) = ext_ack.offset {
+                    write!(f, " (attribute at offset {})", offset)?;
+                }
+                return Ok(());
+            }
         };
         write!(f, "{}", msg)
     }
@@ -119,6 +275,14 @@ impl Error for NlError {
             NlError::NoAck => "No ack received",
             NlError::BadSeq => "Sequence number does not match the request",
             NlError::BadPid => "PID does not match the socket",
+            NlError::WouldBlock => "Operation would block",
+            NlError::Timeout => "Operation timed out",
+            NlError::Interrupted => "Operation interrupted by a signal",
+            NlError::DumpInterrupted => "Dump was interrupted by a change of the dumped data",
+            NlError::Overrun => "Socket receive buffer overrun, messages were dropped",
+            NlError::Truncated { .. } => "Datagram was truncated to fit the receive buffer",
+            NlError::Errno(_) => "Operating system error",
+            NlError::ExtAck { .. } => "Netlink error with extended ACK information",
         }
     }
 }
@@ -178,3 +342,97 @@ impl Error for DeError {
         self.0.as_str()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use byteorder::{NativeEndian, WriteBytesExt};
+
+    use crate::consts::Nlmsg;
+
+    fn error_msg(flags: u16, echoed_payload: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::new();
+        payload.write_i32::<NativeEndian>(-libc::EINVAL).unwrap();
+        payload.write_u32::<NativeEndian>(20).unwrap();
+        payload.write_u16::<NativeEndian>(16).unwrap();
+        payload.write_u16::<NativeEndian>(5).unwrap();
+        payload.write_u32::<NativeEndian>(1).unwrap();
+        payload.write_u32::<NativeEndian>(0).unwrap();
+        payload.extend_from_slice(echoed_payload);
+        payload.write_u16::<NativeEndian>(13).unwrap();
+        payload
+            .write_u16::<NativeEndian>(NlmsgerrAttr::Msg.into())
+            .unwrap();
+        payload.extend_from_slice(b"bad attr\0\0\0\0");
+        payload.write_u16::<NativeEndian>(8).unwrap();
+        payload
+            .write_u16::<NativeEndian>(NlmsgerrAttr::Offs.into())
+            .unwrap();
+        payload.write_u32::<NativeEndian>(20).unwrap();
+
+        let mut msg = Vec::new();
+        msg.write_u32::<NativeEndian>(16 + payload.len() as u32)
+            .unwrap();
+        msg.write_u16::<NativeEndian>(Nlmsg::Error.into()).unwrap();
+        msg.write_u16::<NativeEndian>(flags).unwrap();
+        msg.write_u32::<NativeEndian>(1).unwrap();
+        msg.write_u32::<NativeEndian>(0).unwrap();
+        msg.extend_from_slice(&payload);
+        msg
+    }
+
+    #[test]
+    fn test_ext_ack() {
+        let expected = ExtAck {
+            msg: Some("bad attr".to_string()),
+            offset: Some(20),
+        };
+
+        let bytes = error_msg(NLM_F_ACK_TLVS, &[1, 2, 3, 4]);
+        let msg =
+            Nlmsghdr::<Nlmsg, Nlmsgerr<Nlmsg>>::deserialize(&mut StreamReadBuffer::new(&bytes))
+                .unwrap();
+        assert_eq!(msg.nl_payload.error, -libc::EINVAL);
+        assert_eq!(
+            msg.nl_payload.request_payload(&msg.nl_flags).unwrap(),
+            &[1, 2, 3, 4]
+        );
+        assert_eq!(msg.nl_payload.ext_ack(&msg.nl_flags).unwrap(), expected);
+        assert_eq!(msg.nl_payload.ext_ack(&[]).unwrap(), ExtAck::default());
+
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        msg.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), bytes.as_slice());
+
+        let bytes = error_msg(NLM_F_ACK_TLVS | NLM_F_CAPPED, &[]);
+        let msg =
+            Nlmsghdr::<Nlmsg, Nlmsgerr<Nlmsg>>::deserialize(&mut StreamReadBuffer::new(&bytes))
+                .unwrap();
+        assert_eq!(msg.nl_payload.nlmsg.nl_len, 20);
+        assert!(msg
+            .nl_payload
+            .request_payload(&msg.nl_flags)
+            .unwrap()
+            .is_empty());
+        assert_eq!(msg.nl_payload.ext_ack(&msg.nl_flags).unwrap(), expected);
+
+        let err = NlError::ExtAck {
+            error: msg.nl_payload.error,
+            ext_ack: expected,
+        };
+        assert!(err
+            .to_string()
+            .ends_with(": bad attr (attribute at offset 20)"));
+        assert_eq!(err.errno(), Some(libc::EINVAL));
+    }
+
+    #[test]
+    fn test_errno() {
+        let io_err = io::Error::from_raw_os_error(libc::ENOBUFS);
+        let err = NlError::from(io::Error::from_raw_os_error(libc::ENOBUFS));
+        assert_eq!(err.errno(), Some(libc::ENOBUFS));
+        assert_eq!(err.to_string(), io_err.to_string());
+        assert_eq!(NlError::new("message").errno(), None);
+    }
+}
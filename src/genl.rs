@@ -12,16 +12,26 @@
 //! parsing at the top level when one `Nlattr` structure is not nested within another, a use case
 //! that is instead handled in `nlattr.rs`.
 
+use std::mem;
+
 use buffering::{StreamReadBuffer, StreamWriteBuffer};
 
 use crate::{
-    consts::{Cmd, NlAttrType},
-    nlattr::{AttrHandle, Nlattr},
+    consts::{
+        needs_64bit_pad, Cmd, CtrlAttr, CtrlAttrHdrsize, CtrlAttrMaxattr, CtrlAttrMcastGrp,
+        CtrlAttrOp, CtrlAttrOpFlags, CtrlAttrVersion, CtrlCmd, GenlOpFlag, NlAttrType,
+    },
+    err::NlError,
+    nl::{NlEmpty, NLMSG_HDRLEN},
+    nlattr::{AttrHandle, Nlattr, NlattrBuilder},
     DeError, Nl, SerError,
 };
 
+const GENL_HDRLEN: usize = 4;
+
 /// Struct representing generic netlink header and payload
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Genlmsghdr<C, T> {
     /// Generic netlink message command
     pub cmd: C,
@@ -54,9 +64,109 @@ where
     T: NlAttrType,
 {
     /// Get handle for attribute parsing and traversal
-    pub fn get_attr_handle(&self) -> AttrHandle<T> {
+    pub fn get_attr_handle(&self) -> AttrHandle<'_, T> {
         AttrHandle::new_borrowed(&self.attrs)
     }
+
+    /// Get the attributes mutably to append or remove attributes - the lengths of the attributes
+    /// and the enclosing `Nlmsghdr` are recomputed when serializing
+    pub fn get_attrs_mut(&mut self) -> &mut Vec<Nlattr<T, Vec<u8>>> {
+        &mut self.attrs
+    }
+
+    /// Append an attribute created from its type and payload - useful for conditionally adding
+    /// attributes such as optional filters after the message has been constructed
+    pub fn push_attr<P>(&mut self, nla_type: T, payload: P) -> Result<(), SerError>
+    where
+        P: Nl,
+    {
+        self.attrs.push(Nlattr::new(None, nla_type, payload)?);
+        Ok(())
+    }
+}
+
+/// Builder for `Genlmsghdr` that creates attributes from their type and payload
+///
+/// Attribute lengths and padding are computed when each attribute is added. The first error
+/// encountered while adding attributes is returned from `build`.
+pub struct GenlmsghdrBuilder<C, T> {
+    cmd: C,
+    version: u8,
+    attrs: Vec<Nlattr<T, Vec<u8>>>,
+    err: Option<SerError>,
+}
+
+impl<C, T> GenlmsghdrBuilder<C, T>
+where
+    C: Cmd,
+    T: NlAttrType,
+{
+    /// Start building a message with the given command and family version
+    pub fn new(cmd: C, version: u8) -> Self {
+        GenlmsghdrBuilder {
+            cmd,
+            version,
+            attrs: Vec::new(),
+            err: None,
+        }
+    }
+
+    /// Append an attribute with a payload of any type that implements `Nl`
+    pub fn attr<P>(mut self, nla_type: T, payload: P) -> Self
+    where
+        P: Nl,
+    {
+        if self.err.is_none() {
+            match Nlattr::new(None, nla_type, payload) {
+                Ok(attr) => self.attrs.push(attr),
+                Err(e) => self.err = Some(e),
+            }
+        }
+        self
+    }
+
+    /// Append an attribute with a 64-bit payload, preceded by an empty attribute of type
+    /// `pad_type` if needed so that the payload is 8 byte aligned within the netlink message
+    pub fn attr_64bit<P>(self, pad_type: T, nla_type: T, payload: P) -> Self
+    where
+        P: Nl,
+    {
+        let offset = NLMSG_HDRLEN + GENL_HDRLEN + self.attrs.asize();
+        let builder = if needs_64bit_pad(offset) {
+            self.attr(pad_type, NlEmpty)
+        } else {
+            self
+        };
+        builder.attr(nla_type, payload)
+    }
+
+    /// Append an attribute containing nested attributes built with `Nlattr::nested`
+    pub fn nested(mut self, builder: NlattrBuilder<T>) -> Self {
+        if self.err.is_none() {
+            match builder.build() {
+                Ok(attr) => self.attrs.push(attr),
+                Err(e) => self.err = Some(e),
+            }
+        }
+        self
+    }
+
+    /// Append already constructed attributes such as attributes containing nested attributes
+    pub fn attrs<I>(mut self, attrs: I) -> Self
+    where
+        I: IntoIterator<Item = Nlattr<T, Vec<u8>>>,
+    {
+        self.attrs.extend(attrs);
+        self
+    }
+
+    /// Build the message or return the first error encountered while adding attributes
+    pub fn build(self) -> Result<Genlmsghdr<C, T>, SerError> {
+        if let Some(e) = self.err {
+            return Err(e);
+        }
+        Genlmsghdr::new(self.cmd, self.version, self.attrs)
+    }
 }
 
 impl<C, T> Nl for Genlmsghdr<C, T>
@@ -107,6 +217,119 @@ where
     fn size(&self) -> usize {
         self.cmd.size() + self.version.size() + self.reserved.size() + self.attrs.asize()
     }
+
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.attrs.check_attr_types()
+    }
+}
+
+/// Description of a generic netlink family as reported by the `nlctrl` family
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenlFamily {
+    /// Numeric ID used as `nl_type` for messages of the family
+    pub id: u16,
+    /// Name of the family
+    pub name: String,
+    /// Version of the family
+    pub version: u32,
+    /// Size of the family specific header following `Genlmsghdr`
+    pub hdr_size: u32,
+    /// Highest attribute type accepted by the family
+    pub max_attr: u32,
+    /// Commands supported by the family
+    pub ops: Vec<GenlOp>,
+    /// Multicast groups of the family
+    pub mcast_groups: Vec<GenlMcastGroup>,
+}
+
+/// Command supported by a generic netlink family
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenlOp {
+    /// Value of the command
+    pub id: u32,
+    /// Permissions and capabilities of the command
+    pub flags: Vec<GenlOpFlag>,
+}
+
+/// Multicast group of a generic netlink family
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenlMcastGroup {
+    /// Name of the group
+    pub name: String,
+    /// Numeric ID used to subscribe to the group
+    pub id: u32,
+}
+
+impl GenlFamily {
+    /// Parse a family from the payload of a `CtrlCmd::Newfamily` message sent in reply to
+    /// `CtrlCmd::Getfamily`
+    pub fn parse(genl: &Genlmsghdr<CtrlCmd, CtrlAttr>) -> Result<Self, NlError> {
+        let handle = genl.get_attr_handle();
+        let id = handle.get_attr_payload_as::<u16>(CtrlAttr::FamilyId)?;
+        let name = handle.get_attr_payload_as::<String>(CtrlAttr::FamilyName)?;
+        let version = handle.get::<CtrlAttrVersion>()?.unwrap_or(0);
+        let hdr_size = handle.get::<CtrlAttrHdrsize>()?.unwrap_or(0);
+        let max_attr = handle.get::<CtrlAttrMaxattr>()?.unwrap_or(0);
+
+        let mut ops = Vec::new();
+        if handle.has_attribute(CtrlAttr::Ops) {
+            for op in handle.get_nested_attributes::<u16>(CtrlAttr::Ops)?.iter() {
+                let attrs = op.get_nested_attributes::<CtrlAttrOp>()?;
+                let flags = attrs.get::<CtrlAttrOpFlags>()?.unwrap_or(0);
+                let mut op_flags = Vec::new();
+                for i in 0..mem::size_of::<u32>() * 8 {
+                    let bit = 1 << i;
+                    if bit & flags == bit {
+                        op_flags.push(bit.into());
+                    }
+                }
+                ops.push(GenlOp {
+                    id: attrs.get_attr_payload_as::<u32>(CtrlAttrOp::Id)?,
+                    flags: op_flags,
+                });
+            }
+        }
+
+        let mut mcast_groups = Vec::new();
+        if handle.has_attribute(CtrlAttr::McastGroups) {
+            for group in handle
+                .get_nested_attributes::<u16>(CtrlAttr::McastGroups)?
+                .iter()
+            {
+                let attrs = group.get_nested_attributes::<CtrlAttrMcastGrp>()?;
+                mcast_groups.push(GenlMcastGroup {
+                    name: attrs.get_attr_payload_as::<String>(CtrlAttrMcastGrp::Name)?,
+                    id: attrs.get_attr_payload_as::<u32>(CtrlAttrMcastGrp::Id)?,
+                });
+            }
+        }
+
+        Ok(GenlFamily {
+            id,
+            name,
+            version,
+            hdr_size,
+            max_attr,
+            ops,
+            mcast_groups,
+        })
+    }
+
+    /// Look up the ID of a multicast group by name
+    pub fn mcast_group(&self, name: &str) -> Option<u32> {
+        self.mcast_groups
+            .iter()
+            .find(|group| group.name == name)
+            .map(|group| group.id)
+    }
+
+    /// Look up a command supported by the family
+    pub fn op(&self, id: u32) -> Option<&GenlOp> {
+        self.ops.iter().find(|op| op.id == id)
+    }
 }
 
 #[cfg(test)]
@@ -118,10 +341,302 @@ mod test {
     use byteorder::{NativeEndian, WriteBytesExt};
 
     use crate::{
-        consts::{CtrlAttr, CtrlCmd, NlFamily},
+        consts::{GenlId, NlFamily, NlmF},
+        nl::Nlmsghdr,
         socket::NlSocket,
     };
 
+    #[test]
+    pub fn test_builder() {
+        let genl = GenlmsghdrBuilder::new(CtrlCmd::Getfamily, 2)
+            .attr(CtrlAttr::FamilyId, 5u16)
+            .attr(CtrlAttr::FamilyName, "nlctrl")
+            .attrs(vec![Nlattr::new(None, CtrlAttr::Version, 1u32).unwrap()])
+            .build()
+            .unwrap();
+        let expected = Genlmsghdr::new(
+            CtrlCmd::Getfamily,
+            2,
+            vec![
+                Nlattr::new(None, CtrlAttr::FamilyId, 5u16).unwrap(),
+                Nlattr::new(None, CtrlAttr::FamilyName, "nlctrl").unwrap(),
+                Nlattr::new(None, CtrlAttr::Version, 1u32).unwrap(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(genl, expected);
+        assert_eq!(genl.size(), 4 + 8 + 12 + 8);
+
+        let genl = GenlmsghdrBuilder::<_, CtrlAttr>::new(CtrlCmd::Getfamily, 2)
+            .attr(CtrlAttr::FamilyName, "nl\0ctrl".to_string())
+            .attr(CtrlAttr::FamilyId, 5u16)
+            .build();
+        assert!(genl.is_err());
+    }
+
+    #[test]
+    pub fn test_family_parse() {
+        let genl = GenlmsghdrBuilder::new(CtrlCmd::Newfamily, 2)
+            .attr(CtrlAttr::FamilyName, "test")
+            .attr(CtrlAttr::FamilyId, 30u16)
+            .attr(CtrlAttr::Version, 1u32)
+            .attr(CtrlAttr::Hdrsize, 0u32)
+            .attr(CtrlAttr::Maxattr, 4u32)
+            .nested(
+                Nlattr::nested(CtrlAttr::Ops)
+                    .nested(
+                        Nlattr::nested(1u16)
+                            .attr(CtrlAttrOp::Id, 3u32)
+                            .attr(CtrlAttrOp::Flags, 0x6u32),
+                    )
+                    .nested(
+                        Nlattr::nested(2u16)
+                            .attr(CtrlAttrOp::Id, 5u32)
+                            .attr(CtrlAttrOp::Flags, 0x3u32),
+                    ),
+            )
+            .nested(
+                Nlattr::nested(CtrlAttr::McastGroups).nested(
+                    Nlattr::nested(1u16)
+                        .attr(CtrlAttrMcastGrp::Id, 9u32)
+                        .attr(CtrlAttrMcastGrp::Name, "events"),
+                ),
+            )
+            .build()
+            .unwrap();
+        let family = GenlFamily::parse(&genl).unwrap();
+        assert_eq!(
+            family,
+            GenlFamily {
+                id: 30,
+                name: "test".to_string(),
+                version: 1,
+                hdr_size: 0,
+                max_attr: 4,
+                ops: vec![
+                    GenlOp {
+                        id: 3,
+                        flags: vec![GenlOpFlag::CmdCapDo, GenlOpFlag::CmdCapDump],
+                    },
+                    GenlOp {
+                        id: 5,
+                        flags: vec![GenlOpFlag::AdminPerm, GenlOpFlag::CmdCapDo],
+                    },
+                ],
+                mcast_groups: vec![GenlMcastGroup {
+                    name: "events".to_string(),
+                    id: 9,
+                }],
+            }
+        );
+        assert_eq!(family.mcast_group("events"), Some(9));
+        assert_eq!(family.mcast_group("other"), None);
+        assert_eq!(family.op(5).unwrap().flags.len(), 2);
+
+        let genl = GenlmsghdrBuilder::new(CtrlCmd::Newfamily, 2)
+            .attr(CtrlAttr::FamilyName, "test")
+            .build()
+            .unwrap();
+        assert!(GenlFamily::parse(&genl).is_err());
+    }
+
+    #[test]
+    pub fn test_builder_nested() {
+        let genl = GenlmsghdrBuilder::new(CtrlCmd::Getfamily, 2)
+            .nested(
+                Nlattr::nested(CtrlAttr::McastGroups)
+                    .nested(Nlattr::nested(1u16).attr(CtrlAttrMcastGrp::Id, 3u32)),
+            )
+            .build()
+            .unwrap();
+        let handle = genl.get_attr_handle();
+        assert!(
+            handle
+                .get_attribute(CtrlAttr::McastGroups)
+                .unwrap()
+                .nla_nested
+        );
+        let groups = handle
+            .get_nested_attributes::<u16>(CtrlAttr::McastGroups)
+            .unwrap();
+        let group = groups.get_nested_attributes::<CtrlAttrMcastGrp>(1).unwrap();
+        assert_eq!(
+            group
+                .get_attr_payload_as::<u32>(CtrlAttrMcastGrp::Id)
+                .unwrap(),
+            3
+        );
+    }
+
+    #[test]
+    pub fn test_read_modify_write() {
+        let nl = Nlmsghdr::new(
+            None,
+            GenlId::Ctrl,
+            Vec::new(),
+            None,
+            None,
+            GenlmsghdrBuilder::new(CtrlCmd::Newfamily, 2)
+                .attr(CtrlAttr::FamilyName, "nlctrl")
+                .attr(CtrlAttr::Version, 2u32)
+                .nested(
+                    Nlattr::nested(CtrlAttr::McastGroups)
+                        .nested(Nlattr::nested(1u16).attr(CtrlAttrMcastGrp::Id, 3u32)),
+                )
+                .build()
+                .unwrap(),
+        );
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        nl.serialize(&mut mem).unwrap();
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        let received =
+            Nlmsghdr::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>::deserialize(&mut mem).unwrap();
+
+        // Copy the borrowed attributes into an owned, editable form
+        let mut attrs = received.nl_payload.get_attr_handle().into_owned();
+        let mut groups = attrs
+            .get_nested_attributes::<u16>(CtrlAttr::McastGroups)
+            .unwrap();
+        let mut group = groups.get_nested_attributes::<CtrlAttrMcastGrp>(1).unwrap();
+        group
+            .to_mut()
+            .iter_mut()
+            .find(|a| a.nla_type == CtrlAttrMcastGrp::Id)
+            .unwrap()
+            .set_payload(7u32)
+            .unwrap();
+        groups
+            .get_attribute_mut(1)
+            .unwrap()
+            .set_nested_attributes(group.get_slice())
+            .unwrap();
+        attrs
+            .get_attribute_mut(CtrlAttr::McastGroups)
+            .unwrap()
+            .set_nested_attributes(groups.get_slice())
+            .unwrap();
+        attrs.to_mut().retain(|a| a.nla_type != CtrlAttr::Version);
+        attrs
+            .to_mut()
+            .push(Nlattr::new(None, CtrlAttr::FamilyId, 16u16).unwrap());
+
+        let mut rewritten = received.clone();
+        rewritten.nl_payload =
+            Genlmsghdr::new(received.nl_payload.cmd, 2, attrs.into_vec()).unwrap();
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        rewritten.serialize(&mut mem).unwrap();
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        let parsed =
+            Nlmsghdr::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>::deserialize(&mut mem).unwrap();
+        let handle = parsed.nl_payload.get_attr_handle();
+        assert!(!handle.has_attribute(CtrlAttr::Version));
+        assert_eq!(
+            handle
+                .get_attr_payload_as::<u16>(CtrlAttr::FamilyId)
+                .unwrap(),
+            16
+        );
+        let id = handle
+            .get_nested_attributes::<u16>(CtrlAttr::McastGroups)
+            .unwrap()
+            .get_nested_attributes::<CtrlAttrMcastGrp>(1)
+            .unwrap()
+            .get_attr_payload_as::<u32>(CtrlAttrMcastGrp::Id)
+            .unwrap();
+        assert_eq!(id, 7);
+    }
+
+    #[test]
+    pub fn test_builder_64bit() {
+        // The first payload starts at offset 24 in the message so no pad is needed
+        let genl = GenlmsghdrBuilder::new(CtrlCmd::Getfamily, 2)
+            .attr_64bit(CtrlAttr::Unspec, CtrlAttr::Version, 1u64)
+            .attr_64bit(CtrlAttr::Unspec, CtrlAttr::Hdrsize, 2u64)
+            .build()
+            .unwrap();
+        let handle = genl.get_attr_handle();
+        assert_eq!(
+            handle
+                .iter()
+                .map(|a| a.nla_type.clone())
+                .collect::<Vec<_>>(),
+            vec![CtrlAttr::Version, CtrlAttr::Unspec, CtrlAttr::Hdrsize]
+        );
+        assert_eq!(handle.iter_without_pad(CtrlAttr::Unspec).count(), 2);
+        assert_eq!(
+            handle
+                .get_attr_payload_as::<u64>(CtrlAttr::Hdrsize)
+                .unwrap(),
+            2
+        );
+        assert_eq!(genl.size(), 4 + 12 + 4 + 12);
+    }
+
+    #[test]
+    pub fn test_push_attr() {
+        let filter: Option<&str> = Some("nlctrl");
+        let mut nl = Nlmsghdr::new(
+            None,
+            GenlId::Ctrl,
+            vec![NlmF::Request, NlmF::Dump],
+            None,
+            None,
+            Genlmsghdr::<CtrlCmd, CtrlAttr>::new(CtrlCmd::Getfamily, 2, Vec::new()).unwrap(),
+        );
+        if let Some(name) = filter {
+            nl.nl_payload.push_attr(CtrlAttr::FamilyName, name).unwrap();
+        }
+        nl.nl_payload.push_attr(CtrlAttr::Version, 1u32).unwrap();
+        assert!(nl
+            .nl_payload
+            .push_attr(CtrlAttr::FamilyName, "a\0b".to_string())
+            .is_err());
+
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        nl.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref().len(), 16 + 4 + 12 + 8);
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        let parsed =
+            Nlmsghdr::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>::deserialize(&mut mem).unwrap();
+        assert_eq!(parsed.nl_len, 40);
+        assert_eq!(parsed.nl_payload.get_attr_handle().iter().count(), 2);
+    }
+
+    #[test]
+    pub fn test_mutated_lengths() {
+        let mut nl = Nlmsghdr::new(
+            None,
+            GenlId::Ctrl,
+            Vec::new(),
+            None,
+            None,
+            GenlmsghdrBuilder::new(CtrlCmd::Getfamily, 2)
+                .attr(CtrlAttr::FamilyId, 5u16)
+                .build()
+                .unwrap(),
+        );
+        nl.nl_payload
+            .get_attrs_mut()
+            .push(Nlattr::new(None, CtrlAttr::FamilyName, "nlctrl").unwrap());
+        nl.nl_payload.get_attrs_mut()[0].payload = vec![1, 0, 0, 0];
+
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        nl.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref().len(), 16 + 4 + 8 + 12);
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        let parsed =
+            Nlmsghdr::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>::deserialize(&mut mem).unwrap();
+        assert_eq!(parsed.nl_len, 40);
+        let handle = parsed.nl_payload.get_attr_handle();
+        assert_eq!(handle.get_attribute(CtrlAttr::FamilyId).unwrap().nla_len, 8);
+        assert_eq!(
+            handle
+                .get_attr_payload_as::<String>(CtrlAttr::FamilyName)
+                .unwrap(),
+            "nlctrl"
+        );
+    }
+
     #[test]
     pub fn test_serialize() {
         let attr =
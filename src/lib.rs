@@ -7,20 +7,27 @@
 //! constants in the context of netlink.
 //!
 //! ## The project is broken down into the following modules:
+//! * `client` - This sends a request and returns its reply in one call and manages one shared
+//!   socket per protocol.
 //! * `consts` - This is where all of the C-defined constants are wrapped into type safe enums for
-//! use in the library.
+//!   use in the library.
+//! * `dispatch` - This calls handlers registered by message type for received messages.
 //! * `err` - This module contains all of the protocol and library-level errors encountered in the
-//! code.
+//!   code.
 //! * `genl` - This code provides parsing for the generic netlink subsystem of the netlink
-//! protocol.
+//!   protocol.
 //! * `netfilter` - Netfilter related protocols (NFLOG, NFQUEUE, CONNTRACK).
 //! * `nlattr` - This code provides more granular parsing methods for the generic netlink
-//! attributes in the context of generic netlink requests and responses.
+//!   attributes in the context of generic netlink requests and responses.
 //! * `nl` - This is the top level netlink header code that handles the header that all netlink
-//! messages are encapsulated in.
+//!   messages are encapsulated in.
+//! * `router` - This multiplexes concurrent requests over one socket and routes the responses
+//!   back to the caller of each request.
 //! * `rtnl` - This module is for the routing netlink subsystem of the netlink protocol.
+//! * `server` - This accepts requests from userspace peers and sends replies to them.
 //! * `socket` - This provides a socket structure for use in sending and receiving messages and a
-//! number of convenience functions for commonly encountered use cases.
+//!   number of convenience functions for commonly encountered use cases.
+//! * `types` - Data types commonly found in netlink payloads such as MAC addresses.
 //!
 //! ## Traits
 //!
@@ -30,6 +37,23 @@
 //! where, to deserialize a type, a buffer needs to be provided by the caller function and passed
 //! to the callee.
 //!
+//! With the `derive` feature enabled, `#[derive(Nl)]` can be used to generate the `Nl`
+//! implementation for structs where every field already implements `Nl`. Fields are serialized
+//! in declaration order with no padding between them so declare any padding fields explicitly.
+//!
+//! With the `mio` feature enabled, `NlSocket` implements `mio::event::Source` so it can be
+//! registered with a `mio::Poll` event loop without going through tokio.
+//!
+//! With the `stream` feature for tokio or the `async-io` feature for async-std and smol enabled,
+//! `NlSocket` can be wrapped in an async socket that is a `Stream` of messages and a `Sink` for
+//! messages to send. See `socket::asynchronous` to use it with other runtimes.
+//!
+//! With the `serde` feature enabled, headers, attributes, constants, and the types in `types`
+//! implement `serde::Serialize` and `serde::Deserialize` so that messages can be dumped to and
+//! loaded from formats like JSON for logging and test fixtures. Constants are represented by
+//! their variant name or, for unrecognized variants, by their integer value so they require a
+//! self-describing format.
+//!
 //! ## Design decisions
 //!
 //! This is a fairly low level library that currently does not have a whole lot of higher level
@@ -55,8 +79,10 @@
 
 #![deny(missing_docs)]
 
+pub mod client;
 /// C constants defined as types
 pub mod consts;
+pub mod dispatch;
 /// Error module
 pub mod err;
 /// Genetlink (generic netlink) header and attribute helpers
@@ -66,15 +92,27 @@ pub mod netfilter;
 pub mod nl;
 /// Netlink attribute handler
 pub mod nlattr;
+pub mod router;
 /// Route netlink bindings
 pub mod rtnl;
+pub mod server;
 /// Wrapper for `libc` sockets
 pub mod socket;
+pub mod types;
+
+#[cfg(feature = "derive")]
+pub use neli_derive::{FromAttrs, Nl};
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde as __serde;
 
 use std::{
+    borrow::Cow,
     ffi::CString,
-    io::{Read, Write},
-    mem, str,
+    io::{self, Read, Write},
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str,
 };
 
 pub use buffering::{StreamReadBuffer, StreamWriteBuffer};
@@ -104,8 +142,6 @@ macro_rules! log {
     ($fmt:tt, $($args:expr),*) => {
         if *$crate::LOGGING_INITIALIZED && *$crate::SHOW_LOGS {
             log::debug!(concat!($fmt, "\n{}"), $($args),*, ["-"; 80].join(""));
-        } else {
-            ()
         }
     }
 }
@@ -114,6 +150,11 @@ macro_rules! log {
 /// Implementations for basic and `neli`'s types are provided (see below). Create new
 /// implementations if you have to work with a Netlink API that uses
 /// values of more unusual types.
+///
+/// `serialize` takes `&self` so messages can be serialized through shared references, and no
+/// `Default` bound is required - any information needed to serialize or deserialize a value,
+/// such as the length of a variable sized payload, is carried by the value itself or provided
+/// through the size hint on the buffer.
 pub trait Nl: Sized {
     /// Serialization method
     fn serialize(&self, m: &mut StreamWriteBuffer) -> Result<(), SerError>;
@@ -126,11 +167,50 @@ pub trait Nl: Sized {
     /// The size of the binary representation of a struct - not aligned to word size
     fn size(&self) -> usize;
 
+    /// The size of the binary representation of the type if it is known without an instance
+    /// of the type - `None` for types whose size depends on their contents
+    fn type_size() -> Option<usize> {
+        None
+    }
+
+    /// Return an error if an attribute type held by the value was not recognized when
+    /// deserializing
+    ///
+    /// `Nlmsghdr::deserialize_with_mode` calls this on the payload in `DeMode::Strict`. Constant
+    /// enums reject `UnrecognizedVariant`, types holding attributes check their attribute types
+    /// and all other types accept any value.
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        Ok(())
+    }
+
     /// The size of the binary representation of a struct - aligned to word size
+    ///
+    /// This is the exact buffer size required by `serialize_into`.
     fn asize(&self) -> usize {
         alignto(self.size())
     }
 
+    /// Serialize into a caller provided buffer such as a stack allocated array
+    ///
+    /// The buffer must be at least `asize()` bytes long. Any alignment padding is zeroed and the
+    /// number of bytes used, equal to `asize()`, is returned.
+    fn serialize_into(&self, buf: &mut [u8]) -> Result<usize, SerError> {
+        let len = self.asize();
+        if buf.len() < len {
+            return Err(SerError::new(format!(
+                "Buffer of length {} is too small to hold {} bytes",
+                buf.len(),
+                len
+            )));
+        }
+        let buf = &mut buf[..len];
+        for b in buf.iter_mut() {
+            *b = 0;
+        }
+        self.serialize(&mut StreamWriteBuffer::new_sized(buf))?;
+        Ok(len)
+    }
+
     /// Pad the data serialized data structure to alignment
     fn pad(&self, m: &mut StreamWriteBuffer) -> Result<(), SerError> {
         let padding_len = self.asize() - self.size();
@@ -151,6 +231,45 @@ pub trait Nl: Sized {
     }
 }
 
+/// Deserialize a value from the next `len` bytes of the buffer and return it with the number of
+/// those bytes that it did not read
+///
+/// The buffer is bounded with the size hint instead of copying the bytes. Types that take the size
+/// hint read all of the bytes it covers and types that leave it read `size()` bytes.
+pub(crate) fn deserialize_bounded<P, B>(
+    mem: &mut StreamReadBuffer<B>,
+    len: usize,
+) -> Result<(P, usize), DeError>
+where
+    P: Nl,
+    B: AsRef<[u8]>,
+{
+    mem.set_size_hint(len);
+    let value = P::deserialize(mem)?;
+    let unparsed_len = match mem.take_size_hint() {
+        Some(_) => len.checked_sub(value.size()).ok_or_else(|| {
+            DeError::new(&format!(
+                "Deserialized {} bytes from a buffer bounded to {} bytes",
+                value.size(),
+                len
+            ))
+        })?,
+        None => 0,
+    };
+    Ok((value, unparsed_len))
+}
+
+/// Discard up to `len` bytes from the buffer, stopping early at the end of the buffer
+pub(crate) fn skip_bytes<B>(mem: &mut StreamReadBuffer<B>, len: usize) -> Result<(), DeError>
+where
+    B: AsRef<[u8]>,
+{
+    if len > 0 {
+        io::copy(&mut mem.by_ref().take(len as u64), &mut io::sink())?;
+    }
+    Ok(())
+}
+
 /// Deserialize trait that allows a buffer to be passed in so that references with appropriate
 /// lifetimes can be returned
 pub trait NlBuf<'a>: Sized {
@@ -176,6 +295,32 @@ impl Nl for u8 {
     fn size(&self) -> usize {
         mem::size_of::<u8>()
     }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<u8>())
+    }
+}
+
+impl Nl for i8 {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        mem.write_i8(*self)?;
+        Ok(())
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        Ok(mem.read_i8()?)
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<i8>()
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<i8>())
+    }
 }
 
 impl Nl for u16 {
@@ -194,6 +339,32 @@ impl Nl for u16 {
     fn size(&self) -> usize {
         mem::size_of::<u16>()
     }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<u16>())
+    }
+}
+
+impl Nl for i16 {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        mem.write_i16::<NativeEndian>(*self)?;
+        Ok(())
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        Ok(mem.read_i16::<NativeEndian>()?)
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<i16>()
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<i16>())
+    }
 }
 
 impl Nl for u32 {
@@ -212,6 +383,10 @@ impl Nl for u32 {
     fn size(&self) -> usize {
         mem::size_of::<u32>()
     }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<u32>())
+    }
 }
 
 impl Nl for i32 {
@@ -230,6 +405,10 @@ impl Nl for i32 {
     fn size(&self) -> usize {
         mem::size_of::<i32>()
     }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<i32>())
+    }
 }
 
 impl Nl for u64 {
@@ -248,9 +427,57 @@ impl Nl for u64 {
     fn size(&self) -> usize {
         mem::size_of::<u64>()
     }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<u64>())
+    }
 }
 
-impl<'a> Nl for &'a [u8] {
+impl Nl for i64 {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        mem.write_i64::<NativeEndian>(*self)?;
+        Ok(())
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        Ok(mem.read_i64::<NativeEndian>()?)
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<i64>()
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<i64>())
+    }
+}
+
+impl Nl for u128 {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        mem.write_u128::<NativeEndian>(*self)?;
+        Ok(())
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        Ok(mem.read_u128::<NativeEndian>()?)
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<u128>()
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<u128>())
+    }
+}
+
+impl Nl for &[u8] {
     fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
         let _ = mem.write(self)?;
         Ok(())
@@ -281,6 +508,107 @@ impl<'a> NlBuf<'a> for &'a [u8] {
     }
 }
 
+impl<const N: usize> Nl for [u8; N] {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        mem.write_all(self)?;
+        Ok(())
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        let mut array = [0; N];
+        mem.read_exact(&mut array)?;
+        Ok(array)
+    }
+
+    fn size(&self) -> usize {
+        N
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(N)
+    }
+}
+
+impl Nl for Ipv4Addr {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        self.octets().serialize(mem)
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        Ok(Ipv4Addr::from(<[u8; 4]>::deserialize(mem)?))
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<[u8; 4]>()
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<[u8; 4]>())
+    }
+}
+
+impl Nl for Ipv6Addr {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        self.octets().serialize(mem)
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        Ok(Ipv6Addr::from(<[u8; 16]>::deserialize(mem)?))
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<[u8; 16]>()
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<[u8; 16]>())
+    }
+}
+
+/// The size hint is used to determine the address family when deserializing - a size hint of 4
+/// will produce an `IpAddr::V4` and a size hint of 16 will produce an `IpAddr::V6`
+impl Nl for IpAddr {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        match *self {
+            IpAddr::V4(ref addr) => addr.serialize(mem),
+            IpAddr::V6(ref addr) => addr.serialize(mem),
+        }
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        match mem.take_size_hint() {
+            Some(4) => Ok(IpAddr::V4(Ipv4Addr::deserialize(mem)?)),
+            Some(16) => Ok(IpAddr::V6(Ipv6Addr::deserialize(mem)?)),
+            Some(sh) => Err(DeError::new(&format!(
+                "Size hint of {} does not correspond to an IPv4 or IPv6 address",
+                sh
+            ))),
+            None => Err(DeError::new(
+                "Size hint required to deserialize IP addresses",
+            )),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match *self {
+            IpAddr::V4(ref addr) => addr.size(),
+            IpAddr::V6(ref addr) => addr.size(),
+        }
+    }
+}
+
 impl Nl for Vec<u8> {
     fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
         let size_hint = mem.take_size_hint();
@@ -317,7 +645,33 @@ impl Nl for Vec<u8> {
     }
 }
 
-impl<'a> Nl for &'a str {
+/// Byte payloads that are either borrowed, to avoid copying static data or data from a receive
+/// buffer, or owned. Deserializing always produces an owned value.
+impl<'a> Nl for Cow<'a, [u8]> {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        let size_hint = mem.take_size_hint();
+        let slice: &[u8] = self.as_ref();
+        let slice_hinted = match size_hint {
+            Some(sh) => &slice[0..sh],
+            None => slice,
+        };
+        let _ = mem.write(slice_hinted)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    where
+        B: AsRef<[u8]>,
+    {
+        Ok(Cow::Owned(Vec::<u8>::deserialize(mem)?))
+    }
+
+    fn size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl Nl for &str {
     fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
         let str_bytes = self.as_bytes();
         let nul = &[0u8];
@@ -352,7 +706,7 @@ impl<'a> NlBuf<'a> for &'a str {
         mem.read_exact(input)?;
         let idx = input.iter().position(|elem| *elem == 0);
         let slice_ref = if let Some(i) = idx {
-            &input[..(i as usize)]
+            &input[..i]
         } else {
             input
         };
@@ -395,6 +749,80 @@ impl Nl for String {
     }
 }
 
+impl<T> Nl for Option<T>
+where
+    T: Nl,
+{
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        match *self {
+            Some(ref v) => v.serialize(mem),
+            None => {
+                mem.take_size_hint();
+                Ok(())
+            }
+        }
+    }
+
+    fn deserialize<B>(mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    where
+        B: AsRef<[u8]>,
+    {
+        if mem.peek_size_hint() == Some(0) || mem.at_end() {
+            mem.take_size_hint();
+            return Ok(None);
+        }
+        Ok(Some(T::deserialize(mem)?))
+    }
+
+    fn size(&self) -> usize {
+        self.as_ref().map_or(0, Nl::size)
+    }
+}
+
+macro_rules! impl_nl_tuple {
+    ( $( $ty:ident => $idx:tt ),* ; $last_ty:ident => $last_idx:tt ) => {
+        impl<$( $ty, )* $last_ty> Nl for ( $( $ty, )* $last_ty )
+        where
+            $( $ty: Nl, )*
+            $last_ty: Nl,
+        {
+            fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+                $( self.$idx.serialize(mem)?; )*
+                self.$last_idx.serialize(mem)
+            }
+
+            fn deserialize<Buf>(mem: &mut StreamReadBuffer<Buf>) -> Result<Self, DeError>
+            where
+                Buf: AsRef<[u8]>,
+            {
+                let size_hint = mem.take_size_hint();
+                let prefix = ( $( $ty::deserialize(mem)?, )* );
+                if let Some(sh) = size_hint {
+                    let consumed = 0 $( + prefix.$idx.size() )*;
+                    mem.set_size_hint(sh.checked_sub(consumed).ok_or_else(|| {
+                        DeError::new("Size hint is smaller than the fixed size tuple elements")
+                    })?);
+                }
+                let last = $last_ty::deserialize(mem)?;
+                Ok(( $( prefix.$idx, )* last ))
+            }
+
+            fn size(&self) -> usize {
+                $( self.$idx.size() + )* self.$last_idx.size()
+            }
+
+            fn type_size() -> Option<usize> {
+                Some(0) $( .and_then(|acc| $ty::type_size().map(|s| acc + s)) )*
+                    .and_then(|acc| $last_ty::type_size().map(|s| acc + s))
+            }
+        }
+    };
+}
+
+impl_nl_tuple!(A => 0; B => 1);
+impl_nl_tuple!(A => 0, B => 1; C => 2);
+impl_nl_tuple!(A => 0, B => 1, C => 2; D => 3);
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -497,6 +925,210 @@ mod test {
         assert_eq!(test_int, deserialed_int);
     }
 
+    #[test]
+    fn test_nl_i8() {
+        let v: i8 = -5;
+        let s: &mut [u8; 1] = &mut [0];
+        {
+            let mut mem = StreamWriteBuffer::new_sized(s);
+            v.serialize(&mut mem).unwrap();
+        }
+        assert_eq!(s[0], v as u8);
+
+        let mut mem = StreamReadBuffer::new(&[251]);
+        let v = i8::deserialize(&mut mem).unwrap();
+        assert_eq!(v, -5)
+    }
+
+    #[test]
+    fn test_nl_i16() {
+        let v: i16 = -6000;
+        let s: &mut [u8] = &mut [0; 2];
+        {
+            let mut c = Cursor::new(&mut *s);
+            c.write_i16::<NativeEndian>(-6000).unwrap();
+        }
+        let s_test = &mut [0; 2];
+        {
+            let mut mem = StreamWriteBuffer::new_sized(s_test);
+            v.serialize(&mut mem).unwrap();
+        }
+        assert_eq!(s, s_test);
+
+        let v = {
+            let mut mem = StreamReadBuffer::new(&*s);
+            i16::deserialize(&mut mem).unwrap()
+        };
+        assert_eq!(v, -6000)
+    }
+
+    #[test]
+    fn test_nl_i64() {
+        let test_int: i64 = -12_345_678_901_234;
+        let expected_serial: &mut [u8] = &mut [0; 8];
+        {
+            let mut c = Cursor::new(&mut *expected_serial);
+            c.write_i64::<NativeEndian>(test_int).unwrap();
+        }
+        let test_serial = &mut [0; 8];
+        {
+            let mut mem = StreamWriteBuffer::new_sized(test_serial);
+            test_int.serialize(&mut mem).unwrap();
+        }
+        assert_eq!(expected_serial, test_serial);
+
+        let deserialized_int = {
+            let mut mem = StreamReadBuffer::new(&*expected_serial);
+            i64::deserialize(&mut mem).unwrap()
+        };
+        assert_eq!(test_int, deserialized_int);
+    }
+
+    #[test]
+    fn test_nl_u128() {
+        let test_int: u128 = 123_456_789_012_345_678_901_234_567_890;
+        let expected_serial: &mut [u8] = &mut [0; 16];
+        {
+            let mut c = Cursor::new(&mut *expected_serial);
+            c.write_u128::<NativeEndian>(test_int).unwrap();
+        }
+        let test_serial = &mut [0; 16];
+        {
+            let mut mem = StreamWriteBuffer::new_sized(test_serial);
+            test_int.serialize(&mut mem).unwrap();
+        }
+        assert_eq!(expected_serial, test_serial);
+        assert_eq!(u128::type_size(), Some(16));
+
+        let deserialized_int = {
+            let mut mem = StreamReadBuffer::new(&*expected_serial);
+            u128::deserialize(&mut mem).unwrap()
+        };
+        assert_eq!(test_int, deserialized_int);
+    }
+
+    #[test]
+    fn test_nl_array() {
+        let hwaddr: [u8; 6] = [0, 1, 2, 3, 4, 5];
+        let s = &mut [0; 6];
+        {
+            let mut mem = StreamWriteBuffer::new_sized(s);
+            hwaddr.serialize(&mut mem).unwrap();
+        }
+        assert_eq!(&hwaddr, s);
+        assert_eq!(<[u8; 16]>::type_size(), Some(16));
+
+        let mut mem = StreamReadBuffer::new(&[0, 1, 2, 3, 4, 5, 6, 7]);
+        let array = <[u8; 6]>::deserialize(&mut mem).unwrap();
+        assert_eq!(array, hwaddr);
+        assert_eq!(
+            u16::deserialize(&mut mem).unwrap(),
+            u16::from_ne_bytes([6, 7])
+        );
+
+        let mut mem = StreamReadBuffer::new(&[0, 1, 2]);
+        assert!(<[u8; 4]>::deserialize(&mut mem).is_err());
+    }
+
+    #[test]
+    fn test_nl_ip_addr() {
+        let v4 = Ipv4Addr::new(192, 168, 1, 1);
+        let s = &mut [0; 4];
+        {
+            let mut mem = StreamWriteBuffer::new_sized(s);
+            v4.serialize(&mut mem).unwrap();
+        }
+        assert_eq!(&[192, 168, 1, 1], s);
+
+        let v6 = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        IpAddr::V6(v6).serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), &v6.octets());
+
+        let mut mem = StreamReadBuffer::new(&[10, 0, 0, 1]);
+        assert_eq!(
+            Ipv4Addr::deserialize(&mut mem).unwrap(),
+            Ipv4Addr::new(10, 0, 0, 1)
+        );
+
+        let mut mem = StreamReadBuffer::new(&[10, 0, 0, 1]);
+        mem.set_size_hint(4);
+        assert_eq!(
+            IpAddr::deserialize(&mut mem).unwrap(),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))
+        );
+
+        let octets = v6.octets();
+        let mut mem = StreamReadBuffer::new(&octets);
+        mem.set_size_hint(16);
+        assert_eq!(IpAddr::deserialize(&mut mem).unwrap(), IpAddr::V6(v6));
+
+        let mut mem = StreamReadBuffer::new(&[10, 0, 0, 1]);
+        assert!(IpAddr::deserialize(&mut mem).is_err());
+        mem.set_size_hint(3);
+        assert!(IpAddr::deserialize(&mut mem).is_err());
+    }
+
+    #[test]
+    fn test_serialize_into() {
+        let mut buf = [0xffu8; 8];
+        assert_eq!(5u16.serialize_into(&mut buf).unwrap(), 4);
+        let mut expected = [0u8, 0, 0, 0, 0xff, 0xff, 0xff, 0xff];
+        expected[..2].copy_from_slice(&5u16.to_ne_bytes());
+        assert_eq!(buf, expected);
+
+        let mut buf = [0u8; 3];
+        assert!(5u32.serialize_into(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_nl_tuple() {
+        let t = (1u16, 2u16);
+        assert_eq!(t.size(), 4);
+        assert_eq!(<(u16, u16)>::type_size(), Some(4));
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        t.serialize(&mut mem).unwrap();
+        let mut expected = 1u16.to_ne_bytes().to_vec();
+        expected.extend_from_slice(&2u16.to_ne_bytes());
+        assert_eq!(mem.as_ref(), expected.as_slice());
+        let mut mem = StreamReadBuffer::new(expected);
+        assert_eq!(<(u16, u16)>::deserialize(&mut mem).unwrap(), t);
+
+        let t = (1u8, 2u16, 3u32, vec![4u8, 5]);
+        assert_eq!(<(u8, u16, u32, Vec<u8>)>::type_size(), None);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        t.serialize(&mut mem).unwrap();
+        let mut bytes = mem.as_ref().to_vec();
+        bytes.push(6);
+        let mut mem = StreamReadBuffer::new(bytes);
+        mem.set_size_hint(t.size());
+        assert_eq!(<(u8, u16, u32, Vec<u8>)>::deserialize(&mut mem).unwrap(), t);
+        assert_eq!(u8::deserialize(&mut mem).unwrap(), 6);
+    }
+
+    #[test]
+    fn test_nl_option() {
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        Some(5u16).serialize(&mut mem).unwrap();
+        None::<u32>.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), &5u16.to_ne_bytes());
+        assert_eq!(Some(5u16).size(), 2);
+        assert_eq!(None::<u32>.size(), 0);
+
+        let mut mem = StreamReadBuffer::new(5u16.to_ne_bytes());
+        assert_eq!(Option::<u16>::deserialize(&mut mem).unwrap(), Some(5));
+        assert_eq!(Option::<u32>::deserialize(&mut mem).unwrap(), None);
+
+        let mut mem = StreamReadBuffer::new(&[1, 2, 3, 4]);
+        mem.set_size_hint(0);
+        assert_eq!(Option::<Vec<u8>>::deserialize(&mut mem).unwrap(), None);
+        mem.set_size_hint(2);
+        assert_eq!(
+            Option::<Vec<u8>>::deserialize(&mut mem).unwrap(),
+            Some(vec![1, 2])
+        );
+    }
+
     #[test]
     fn test_nl_vec() {
         let v = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
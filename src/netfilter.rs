@@ -13,6 +13,7 @@ use libc::c_int;
 use crate::consts::netfilter::{LogCopyMode, NfLogAttr, NfLogCfg};
 use crate::err::{DeError, SerError};
 use crate::nlattr::Nlattr;
+use crate::types::{BeU16, BeU32, BeU64};
 use crate::{Nl, StreamReadBuffer, StreamWriteBuffer};
 
 type Nlattrs = Vec<Nlattr<NfLogAttr, Vec<u8>>>;
@@ -25,13 +26,13 @@ struct Timestamp {
 
 impl Nl for Timestamp {
     fn serialize(&self, m: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        u64::to_be(self.secs).serialize(m)?;
-        u64::to_be(self.usecs).serialize(m)?;
+        BeU64::new(self.secs).serialize(m)?;
+        BeU64::new(self.usecs).serialize(m)?;
         Ok(())
     }
     fn deserialize<B: AsRef<[u8]>>(m: &mut StreamReadBuffer<B>) -> Result<Self, DeError> {
-        let secs = u64::from_be(u64::deserialize(m)?);
-        let usecs = u64::from_be(u64::deserialize(m)?);
+        let secs = BeU64::deserialize(m)?.get();
+        let usecs = BeU64::deserialize(m)?.get();
         Ok(Self { secs, usecs })
     }
     fn size(&self) -> usize {
@@ -39,9 +40,9 @@ impl Nl for Timestamp {
     }
 }
 
-impl Into<SystemTime> for Timestamp {
-    fn into(self) -> SystemTime {
-        let dur = Duration::new(self.secs, (self.usecs * 1000) as u32);
+impl From<Timestamp> for SystemTime {
+    fn from(t: Timestamp) -> Self {
+        let dur = Duration::new(t.secs, (t.usecs * 1000) as u32);
         UNIX_EPOCH + dur
     }
 }
@@ -127,7 +128,7 @@ impl Nl for LogPacket {
     }
     fn deserialize<B: AsRef<[u8]>>(m: &mut StreamReadBuffer<B>) -> Result<Self, DeError> {
         let hint = m.take_size_hint().map(|h| h.saturating_sub(4));
-        let hw_protocol = u16::from_be(Nl::deserialize(m)?);
+        let hw_protocol = BeU16::deserialize(m)?.get();
         let hook = Nl::deserialize(m)?;
         let _pad: u8 = Nl::deserialize(m)?;
         m.set_size_hint(hint.unwrap_or_default());
@@ -146,7 +147,7 @@ impl Nl for LogPacket {
                 }
                 NfLogAttr::Hwaddr => {
                     let mut buffer = StreamReadBuffer::new(&attr.payload);
-                    let len = u16::from_be(u16::deserialize(&mut buffer)?);
+                    let len = BeU16::deserialize(&mut buffer)?.get();
                     let mut hwaddr = attr.payload;
                     // Drop the len and padding
                     hwaddr.drain(..4);
@@ -165,19 +166,19 @@ impl Nl for LogPacket {
                     result.prefix = CString::new(bytes).expect("Leftover null byte");
                 }
                 NfLogAttr::IfindexIndev => {
-                    result.ifindex_in = Some(u32::from_be(attr.get_payload_as()?))
+                    result.ifindex_in = Some(attr.get_payload_as::<BeU32>()?.get())
                 }
                 NfLogAttr::IfindexOutdev => {
-                    result.ifindex_out = Some(u32::from_be(attr.get_payload_as()?))
+                    result.ifindex_out = Some(attr.get_payload_as::<BeU32>()?.get())
                 }
                 NfLogAttr::IfindexPhyindev => {
-                    result.ifindex_physin = Some(u32::from_be(attr.get_payload_as()?))
+                    result.ifindex_physin = Some(attr.get_payload_as::<BeU32>()?.get())
                 }
                 NfLogAttr::IfindexPhyoutdev => {
-                    result.ifindex_physout = Some(u32::from_be(attr.get_payload_as()?))
+                    result.ifindex_physout = Some(attr.get_payload_as::<BeU32>()?.get())
                 }
-                NfLogAttr::Uid => result.uid = Some(u32::from_be(attr.get_payload_as()?)),
-                NfLogAttr::Gid => result.gid = Some(u32::from_be(attr.get_payload_as()?)),
+                NfLogAttr::Uid => result.uid = Some(attr.get_payload_as::<BeU32>()?.get()),
+                NfLogAttr::Gid => result.gid = Some(attr.get_payload_as::<BeU32>()?.get()),
                 _ => (),
             }
         }
@@ -236,7 +237,7 @@ impl Nl for LogConfigReq {
         self.family.serialize(m)?;
         // protocol version
         0u8.serialize(m)?;
-        u16::to_be(self.group).serialize(m)?;
+        BeU16::new(self.group).serialize(m)?;
         self.attrs.serialize(m)?;
         self.pad(m)?;
         Ok(())
@@ -263,14 +264,14 @@ pub struct LogConfigMode {
 
 impl Nl for LogConfigMode {
     fn serialize(&self, m: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        u32::to_be(self.copy_range).serialize(m)?;
+        BeU32::new(self.copy_range).serialize(m)?;
         self.copy_mode.serialize(m)?;
         // A padding
         0u8.serialize(m)?;
         Ok(())
     }
     fn deserialize<B: AsRef<[u8]>>(m: &mut StreamReadBuffer<B>) -> Result<Self, DeError> {
-        let copy_range = u32::from_be(u32::deserialize(m)?);
+        let copy_range = BeU32::deserialize(m)?.get();
         let copy_mode = LogCopyMode::deserialize(m)?;
         // A padding
         u8::deserialize(m)?;
@@ -7,21 +7,105 @@
 //! # Design decisions
 //!
 //! Payloads for `Nlmsghdr` can be any type that implements the `Nl` trait.
+//!
+//! `NlStreamDeserializer` exists for the case where netlink messages do not arrive one datagram
+//! at a time, for example when reading from a replay file or a netlink connection proxied over
+//! TCP. It buffers partial data and reports how many more bytes are required before the next
+//! message can be parsed instead of returning a deserialization error.
 
-use std::mem;
+use std::{convert::TryFrom, mem};
 
 use buffering::{StreamReadBuffer, StreamWriteBuffer};
 
 use crate::{
-    consts::{NlType, NlmF},
-    err::{DeError, SerError},
-    Nl,
+    consts::{alignto, NlType, NlmF},
+    deserialize_bounded,
+    err::{DeError, NlError, SerError},
+    skip_bytes, Nl,
 };
 
+pub(crate) const NLMSG_HDRLEN: usize = 16;
+
+/// Handling of payload bytes that are left over and attribute types that are not recognized
+/// after deserializing the payload of an `Nlmsghdr`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DeMode {
+    /// Return an error if any bytes are left over or an attribute type is
+    /// `UnrecognizedVariant` - useful in tests to detect incomplete parsing
+    Strict,
+    /// Skip any bytes that are left over and keep unrecognized attribute types - useful when
+    /// newer kernels append fields to a payload or add attributes
+    #[default]
+    Lenient,
+}
+
+type Header<T> = (u32, T, Vec<NlmF>, u32, u32);
+
+// Deserialize the header fields of a netlink message without the payload.
+pub(crate) fn deserialize_header<T, B>(mem: &mut StreamReadBuffer<B>) -> Result<Header<T>, DeError>
+where
+    T: NlType,
+    B: AsRef<[u8]>,
+{
+    let nl_len = u32::deserialize(mem)?;
+    let nl_type = T::deserialize(mem)?;
+    let nl_flags = {
+        let flags = u16::deserialize(mem)?;
+        let mut nl_flags = Vec::new();
+        for i in 0..mem::size_of::<u16>() * 8 {
+            let bit = 1 << i;
+            if bit & flags == bit {
+                nl_flags.push(bit.into());
+            }
+        }
+        nl_flags
+    };
+    let nl_seq = u32::deserialize(mem)?;
+    let nl_pid = u32::deserialize(mem)?;
+    Ok((nl_len, nl_type, nl_flags, nl_seq, nl_pid))
+}
+
+// Header fields of a netlink message read from its raw bytes without deserializing it, used to
+// decide how to handle a message before parsing it.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RawHeader {
+    pub(crate) nl_len: usize,
+    pub(crate) nl_type: u16,
+    pub(crate) nl_flags: u16,
+    pub(crate) nl_seq: u32,
+}
+
+impl RawHeader {
+    // Read the header at the start of `header`, which holds at least `NLMSG_HDRLEN` bytes.
+    pub(crate) fn read(header: &[u8]) -> Self {
+        RawHeader {
+            nl_len: u32::from_ne_bytes([header[0], header[1], header[2], header[3]]) as usize,
+            nl_type: u16::from_ne_bytes([header[4], header[5]]),
+            nl_flags: u16::from_ne_bytes([header[6], header[7]]),
+            nl_seq: u32::from_ne_bytes([header[8], header[9], header[10], header[11]]),
+        }
+    }
+
+    // Read the header of the message at the start of `datagram`, failing if its length is
+    // shorter than the header or longer than the rest of the datagram.
+    pub(crate) fn peek(datagram: &[u8]) -> Result<Self, NlError> {
+        let header = datagram.get(..NLMSG_HDRLEN).map(RawHeader::read);
+        match header {
+            Some(header) if header.nl_len >= NLMSG_HDRLEN && header.nl_len <= datagram.len() => {
+                Ok(header)
+            }
+            _ => Err(NlError::new(
+                "Message length does not match the length of the datagram",
+            )),
+        }
+    }
+}
+
 /// Top level netlink header and payload
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Nlmsghdr<T, P> {
-    /// Length of the netlink message
+    /// Length of the netlink message - recomputed from the payload when serializing
     pub nl_len: u32,
     /// Type of the netlink message
     pub nl_type: T,
@@ -60,15 +144,108 @@ where
         nl.nl_len = nl_len.unwrap_or(nl.size() as u32);
         nl
     }
-}
 
-impl<T, P> Nl for Nlmsghdr<T, P>
-where
-    T: NlType,
-    P: Nl,
-{
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        self.nl_len.serialize(mem)?;
+    /// Create a request with `NLM_F_REQUEST` set
+    pub fn request(nl_type: T, nl_payload: P) -> Self {
+        Nlmsghdr::new(None, nl_type, vec![NlmF::Request], None, None, nl_payload)
+    }
+
+    /// Create a request with `NLM_F_REQUEST | NLM_F_ACK` set, for example to delete an object
+    /// and wait for the kernel to confirm it
+    pub fn ack_request(nl_type: T, nl_payload: P) -> Self {
+        Nlmsghdr::new(
+            None,
+            nl_type,
+            vec![NlmF::Request, NlmF::Ack],
+            None,
+            None,
+            nl_payload,
+        )
+    }
+
+    /// Create a GET request with `NLM_F_REQUEST | NLM_F_DUMP` set to list all objects
+    pub fn dump_request(nl_type: T, nl_payload: P) -> Self {
+        Nlmsghdr::new(
+            None,
+            nl_type,
+            vec![NlmF::Request, NlmF::Dump],
+            None,
+            None,
+            nl_payload,
+        )
+    }
+
+    /// Create a NEW request with `NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_EXCL` set that
+    /// fails with `EEXIST` if the object already exists
+    pub fn create_request(nl_type: T, nl_payload: P) -> Self {
+        Nlmsghdr::new(
+            None,
+            nl_type,
+            vec![NlmF::Request, NlmF::Ack, NlmF::Create, NlmF::Excl],
+            None,
+            None,
+            nl_payload,
+        )
+    }
+
+    /// Create a NEW request with `NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_REPLACE` set
+    /// that creates the object or replaces it if it already exists
+    pub fn replace_request(nl_type: T, nl_payload: P) -> Self {
+        Nlmsghdr::new(
+            None,
+            nl_type,
+            vec![NlmF::Request, NlmF::Ack, NlmF::Create, NlmF::Replace],
+            None,
+            None,
+            nl_payload,
+        )
+    }
+
+    /// Deserialize a message handling bytes in the payload that are not consumed when
+    /// deserializing `P` and attribute types that are not recognized according to `mode` -
+    /// `Nl::deserialize` uses `DeMode::Lenient`
+    pub fn deserialize_with_mode<B>(
+        mem: &mut StreamReadBuffer<B>,
+        mode: DeMode,
+    ) -> Result<Self, DeError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let (nl_len, nl_type, nl_flags, nl_seq, nl_pid) = deserialize_header::<T, B>(mem)?;
+        let payload_len = (nl_len as usize).checked_sub(NLMSG_HDRLEN).ok_or_else(|| DeError::new("Packet reported shorter length than netlink header - make sure you are receiving the correct type from the socket"))?;
+        let (nl_payload, unparsed_len) = deserialize_bounded::<P, B>(mem, payload_len)?;
+        if mode == DeMode::Strict {
+            if unparsed_len > 0 {
+                return Err(DeError::new(
+                    "Payload of netlink message contains bytes that were not parsed",
+                ));
+            }
+            nl_payload.check_attr_types()?;
+        }
+        // The final message in a buffer may not be followed by its padding
+        skip_bytes(
+            mem,
+            unparsed_len + alignto(nl_len as usize) - nl_len as usize,
+        )?;
+
+        Ok(Nlmsghdr {
+            nl_len,
+            nl_type,
+            nl_flags,
+            nl_seq,
+            nl_pid,
+            nl_payload,
+        })
+    }
+
+    // Serialize with the provided length in place of the length of the message - used for the
+    // header of the request echoed back in `Nlmsgerr`, which keeps the original length.
+    pub(crate) fn serialize_with_len(
+        &self,
+        nl_len: u32,
+        mem: &mut StreamWriteBuffer,
+    ) -> Result<(), SerError> {
+        nl_len.serialize(mem)?;
         self.nl_type.serialize(mem)?;
         let val = self.nl_flags.iter().fold(0, |acc: u16, val| {
             let v: u16 = val.into();
@@ -82,45 +259,28 @@ where
 
         Ok(())
     }
+}
+
+impl<T, P> Nl for Nlmsghdr<T, P>
+where
+    T: NlType,
+    P: Nl,
+{
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        let nl_len = u32::try_from(self.size())
+            .map_err(|_| SerError::new("Message is too long to fit in a netlink header"))?;
+        self.serialize_with_len(nl_len, mem)
+    }
 
     fn deserialize<B>(mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
     where
         B: AsRef<[u8]>,
     {
-        let nl_len = u32::deserialize(mem)?;
-        let nl_type = T::deserialize(mem)?;
-        let nl_flags = {
-            let flags = u16::deserialize(mem)?;
-            let mut nl_flags = Vec::new();
-            for i in 0..mem::size_of::<u16>() * 8 {
-                let bit = 1 << i;
-                if bit & flags == bit {
-                    nl_flags.push(bit.into());
-                }
-            }
-            nl_flags
-        };
-        let nl_seq = u32::deserialize(mem)?;
-        let nl_pid = u32::deserialize(mem)?;
-        let nl_payload = {
-            let payload_len = (nl_len as usize).checked_sub(
-                nl_len.size() + nl_type.size() + 0u16.size() + nl_seq.size() + nl_pid.size(),
-            ).ok_or_else(|| DeError::new("Packet reported shorter length than netlink header - make sure you are receiving the correct type from the socket"))?;
-            mem.set_size_hint(payload_len);
-            P::deserialize(mem)?
-        };
-
-        let nl = Nlmsghdr::<T, P> {
-            nl_len,
-            nl_type,
-            nl_flags,
-            nl_seq,
-            nl_pid,
-            nl_payload,
-        };
-        nl.strip(mem)?;
+        Self::deserialize_with_mode(mem, DeMode::Lenient)
+    }
 
-        Ok(nl)
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.nl_payload.check_attr_types()
     }
 
     fn size(&self) -> usize {
@@ -133,8 +293,123 @@ where
     }
 }
 
+// Add the flags of `flags` that `msg` does not have yet.
+pub(crate) fn add_flags<T, P>(msg: &mut Nlmsghdr<T, P>, flags: &[NlmF]) {
+    for flag in flags {
+        if !msg.nl_flags.contains(flag) {
+            msg.nl_flags.push(flag.clone());
+        }
+    }
+}
+
+/// Builder for `Nlmsghdr` that computes the message length from the payload
+///
+/// The sequence number and PID default to `0`, which lets the kernel and `NlSocket` fill them in.
+/// `build` rejects flag combinations that cannot be sent to the kernel.
+pub struct NlmsghdrBuilder<T, P> {
+    nl_type: T,
+    nl_flags: Vec<NlmF>,
+    nl_seq: u32,
+    nl_pid: u32,
+    nl_payload: P,
+}
+
+impl<T, P> NlmsghdrBuilder<T, P>
+where
+    T: NlType,
+    P: Nl,
+{
+    /// Start building a message with the given type and payload
+    pub fn new(nl_type: T, nl_payload: P) -> Self {
+        NlmsghdrBuilder {
+            nl_type,
+            nl_flags: Vec::new(),
+            nl_seq: 0,
+            nl_pid: 0,
+            nl_payload,
+        }
+    }
+
+    /// Add a flag to the message
+    pub fn flag(mut self, flag: NlmF) -> Self {
+        if !self.nl_flags.contains(&flag) {
+            self.nl_flags.push(flag);
+        }
+        self
+    }
+
+    /// Add a set of flags to the message
+    pub fn flags<I>(self, flags: I) -> Self
+    where
+        I: IntoIterator<Item = NlmF>,
+    {
+        flags.into_iter().fold(self, NlmsghdrBuilder::flag)
+    }
+
+    /// Set the sequence number
+    pub fn seq(mut self, nl_seq: u32) -> Self {
+        self.nl_seq = nl_seq;
+        self
+    }
+
+    /// Set the PID
+    pub fn pid(mut self, nl_pid: u32) -> Self {
+        self.nl_pid = nl_pid;
+        self
+    }
+
+    /// Validate the flags and build the message with `nl_len` set to the length of the message
+    pub fn build(self) -> Result<Nlmsghdr<T, P>, SerError> {
+        validate_flags(&self.nl_flags)?;
+        let mut nl = Nlmsghdr {
+            nl_len: 0,
+            nl_type: self.nl_type,
+            nl_flags: self.nl_flags,
+            nl_seq: self.nl_seq,
+            nl_pid: self.nl_pid,
+            nl_payload: self.nl_payload,
+        };
+        nl.nl_len = u32::try_from(nl.size())
+            .map_err(|_| SerError::new("Message is too long to fit in a netlink header"))?;
+        Ok(nl)
+    }
+}
+
+// Modifiers for GET requests and NEW requests share bit values so they can only be
+// distinguished by the message type and must never be mixed.
+fn validate_flags(flags: &[NlmF]) -> Result<(), SerError> {
+    let has = |f: NlmF| flags.contains(&f);
+    let get_mods = [NlmF::Root, NlmF::Match, NlmF::Atomic, NlmF::Dump];
+    let new_mods = [NlmF::Replace, NlmF::Excl, NlmF::Create, NlmF::Append];
+    let reply_flags = [NlmF::Multi, NlmF::DumpIntr, NlmF::DumpFiltered];
+    let any = |set: &[NlmF]| set.iter().any(|f| flags.contains(f));
+
+    if any(&get_mods) && any(&new_mods) {
+        return Err(SerError::new(
+            "GET request modifiers cannot be combined with NEW request modifiers",
+        ));
+    }
+    if (any(&get_mods) || any(&new_mods)) && !has(NlmF::Request) {
+        return Err(SerError::new(
+            "Request modifiers require the request flag to be set",
+        ));
+    }
+    if has(NlmF::Request) && any(&reply_flags) {
+        return Err(SerError::new(
+            "Flags only valid in replies cannot be set on a request",
+        ));
+    }
+    if has(NlmF::Replace) && has(NlmF::Excl) {
+        return Err(SerError::new(
+            "Replace and exclusive flags are mutually exclusive",
+        ));
+    }
+    Ok(())
+}
+
 /// Struct indicating an empty payload
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NlEmpty;
 
 impl Nl for NlEmpty {
@@ -155,6 +430,98 @@ impl Nl for NlEmpty {
     fn size(&self) -> usize {
         0
     }
+
+    #[inline]
+    fn type_size() -> Option<usize> {
+        Some(0)
+    }
+}
+
+/// Result of attempting to parse a message from a partially received buffer
+#[derive(Debug, PartialEq)]
+pub enum DeStatus<T> {
+    /// A full message was parsed
+    Complete(T),
+    /// The buffer does not yet hold a full message - at least this many more bytes are required
+    Incomplete(usize),
+}
+
+/// Resumable deserializer for netlink messages received as a byte stream
+#[derive(Debug, Default)]
+pub struct NlStreamDeserializer {
+    buffer: Vec<u8>,
+    pending_padding: usize,
+}
+
+impl NlStreamDeserializer {
+    /// Create an empty deserializer
+    pub fn new() -> Self {
+        NlStreamDeserializer::default()
+    }
+
+    /// Append newly received data to the internal buffer
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Number of bytes buffered that have not yet been parsed
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns `true` if there is no unparsed data in the buffer
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Attempt to parse the next message from the buffered data
+    ///
+    /// If the buffer does not contain a full message, no data is consumed and the number of
+    /// additional bytes needed is returned so that the caller can read more data and try again.
+    /// A message is complete once `nl_len` bytes are buffered - padding after it is consumed if it
+    /// has been received and otherwise skipped when it arrives.
+    pub fn next_msg<T, P>(&mut self) -> Result<DeStatus<Nlmsghdr<T, P>>, DeError>
+    where
+        T: NlType,
+        P: Nl,
+    {
+        self.skip_padding();
+        let hdr_len = 0u32.size() + T::type_size().unwrap_or(2) + 0u16.size() + 2 * 0u32.size();
+        if self.buffer.len() < hdr_len {
+            return Ok(DeStatus::Incomplete(hdr_len - self.buffer.len()));
+        }
+        let nl_len = u32::deserialize(&mut StreamReadBuffer::new(&self.buffer[..4]))? as usize;
+        if nl_len < hdr_len {
+            return Err(DeError::new(
+                "Packet reported shorter length than netlink header",
+            ));
+        }
+        if self.buffer.len() < nl_len {
+            return Ok(DeStatus::Incomplete(nl_len - self.buffer.len()));
+        }
+        let msg_len = alignto(nl_len).min(self.buffer.len());
+        let msg = Nlmsghdr::deserialize(&mut StreamReadBuffer::new(&self.buffer[..msg_len]))?;
+        self.buffer.drain(..msg_len);
+        self.pending_padding = alignto(nl_len) - msg_len;
+        Ok(DeStatus::Complete(msg))
+    }
+
+    // Drop zeroed padding left over from the previous message - a non-zero byte means the sender
+    // did not pad the message and the next message has already started
+    fn skip_padding(&mut self) {
+        let skip = self
+            .buffer
+            .iter()
+            .take(self.pending_padding)
+            .take_while(|b| **b == 0)
+            .count();
+        self.buffer.drain(..skip);
+        self.pending_padding = if self.buffer.is_empty() {
+            self.pending_padding - skip
+        } else {
+            0
+        };
+    }
 }
 
 #[cfg(test)]
@@ -165,7 +532,11 @@ mod test {
 
     use byteorder::{NativeEndian, WriteBytesExt};
 
-    use crate::consts::Nlmsg;
+    use crate::{
+        consts::{CtrlAttr, CtrlCmd, Nlmsg},
+        genl::Genlmsghdr,
+        nlattr::Nlattr,
+    };
 
     #[test]
     fn test_nlhdr_serialize() {
@@ -205,4 +576,246 @@ mod test {
             nl
         );
     }
+
+    #[test]
+    fn test_stream_deserializer() {
+        let nl = Nlmsghdr::<Nlmsg, Vec<u8>>::new(
+            None,
+            Nlmsg::Noop,
+            Vec::new(),
+            Some(1),
+            None,
+            vec![1, 2, 3, 4],
+        );
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        nl.serialize(&mut mem).unwrap();
+        nl.serialize(&mut mem).unwrap();
+        let bytes = mem.as_ref().to_vec();
+
+        let mut de = NlStreamDeserializer::new();
+        assert_eq!(
+            de.next_msg::<Nlmsg, Vec<u8>>().unwrap(),
+            DeStatus::Incomplete(16)
+        );
+        de.extend(&bytes[..10]);
+        assert_eq!(
+            de.next_msg::<Nlmsg, Vec<u8>>().unwrap(),
+            DeStatus::Incomplete(6)
+        );
+        de.extend(&bytes[10..18]);
+        assert_eq!(
+            de.next_msg::<Nlmsg, Vec<u8>>().unwrap(),
+            DeStatus::Incomplete(2)
+        );
+        de.extend(&bytes[18..30]);
+        assert_eq!(
+            de.next_msg::<Nlmsg, Vec<u8>>().unwrap(),
+            DeStatus::Complete(nl)
+        );
+        assert_eq!(de.len(), 10);
+        assert_eq!(
+            de.next_msg::<Nlmsg, Vec<u8>>().unwrap(),
+            DeStatus::Incomplete(6)
+        );
+        de.extend(&bytes[30..]);
+        assert!(matches!(
+            de.next_msg::<Nlmsg, Vec<u8>>().unwrap(),
+            DeStatus::Complete(_)
+        ));
+        assert!(de.is_empty());
+    }
+
+    #[test]
+    fn test_stream_deserializer_padding() {
+        let nl = Nlmsghdr::<Nlmsg, Vec<u8>>::new(
+            None,
+            Nlmsg::Noop,
+            Vec::new(),
+            Some(1),
+            None,
+            vec![1, 2, 3],
+        );
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        nl.serialize(&mut mem).unwrap();
+        let bytes = mem.as_ref().to_vec();
+        assert_eq!(bytes.len(), 20);
+
+        // Unpadded final message
+        let mut de = NlStreamDeserializer::new();
+        de.extend(&bytes[..19]);
+        assert_eq!(
+            de.next_msg::<Nlmsg, Vec<u8>>().unwrap(),
+            DeStatus::Complete(nl.clone())
+        );
+        assert!(de.is_empty());
+
+        // Padding received after the message
+        de.extend(&bytes[19..]);
+        de.extend(&bytes);
+        assert_eq!(
+            de.next_msg::<Nlmsg, Vec<u8>>().unwrap(),
+            DeStatus::Complete(nl)
+        );
+        assert!(de.is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_nlhdr_serde() {
+        let nl = Nlmsghdr::<Nlmsg, Vec<u8>>::new(
+            None,
+            Nlmsg::Noop,
+            vec![NlmF::Request, NlmF::Ack],
+            Some(1),
+            None,
+            vec![1, 2],
+        );
+        let json = serde_json::to_string(&nl).unwrap();
+        assert_eq!(
+            json,
+            r#"{"nl_len":18,"nl_type":"Noop","nl_flags":["Request","Ack"],"nl_seq":1,"nl_pid":0,"nl_payload":[1,2]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Nlmsghdr<Nlmsg, Vec<u8>>>(&json).unwrap(),
+            nl
+        );
+
+        let unrecognized = serde_json::to_string(&Nlmsg::UnrecognizedVariant(100)).unwrap();
+        assert_eq!(unrecognized, "100");
+        assert_eq!(
+            serde_json::from_str::<Nlmsg>(&unrecognized).unwrap(),
+            Nlmsg::UnrecognizedVariant(100)
+        );
+        assert!(serde_json::from_str::<Nlmsg>(r#""NotAVariant""#).is_err());
+    }
+
+    #[test]
+    fn test_nlhdr_builder() {
+        let nl = NlmsghdrBuilder::new(Nlmsg::Noop, vec![1u8, 2, 3])
+            .flags(vec![NlmF::Request, NlmF::Ack])
+            .flag(NlmF::Request)
+            .seq(5)
+            .build()
+            .unwrap();
+        assert_eq!(nl.nl_len, 19);
+        assert_eq!(nl.nl_flags, vec![NlmF::Request, NlmF::Ack]);
+        assert_eq!(nl.nl_seq, 5);
+        assert_eq!(nl.nl_pid, 0);
+
+        let build = |flags: Vec<NlmF>| {
+            NlmsghdrBuilder::new(Nlmsg::Noop, NlEmpty)
+                .flags(flags)
+                .build()
+        };
+        assert!(build(vec![NlmF::Request, NlmF::Dump]).is_ok());
+        assert!(build(vec![NlmF::Request, NlmF::Create, NlmF::Excl]).is_ok());
+        assert!(build(vec![NlmF::Request, NlmF::Dump, NlmF::Create]).is_err());
+        assert!(build(vec![NlmF::Dump]).is_err());
+        assert!(build(vec![NlmF::Request, NlmF::Multi]).is_err());
+        assert!(build(vec![NlmF::Request, NlmF::Replace, NlmF::Excl]).is_err());
+    }
+
+    #[test]
+    fn test_nlhdr_request_presets() {
+        let nl = Nlmsghdr::request(Nlmsg::Noop, vec![1u8, 2, 3]);
+        assert_eq!(nl.nl_len, 19);
+        assert_eq!(nl.nl_flags, vec![NlmF::Request]);
+        let presets = [
+            Nlmsghdr::ack_request(Nlmsg::Noop, NlEmpty),
+            Nlmsghdr::dump_request(Nlmsg::Noop, NlEmpty),
+            Nlmsghdr::create_request(Nlmsg::Noop, NlEmpty),
+            Nlmsghdr::replace_request(Nlmsg::Noop, NlEmpty),
+        ];
+        for nl in presets.iter() {
+            assert!(validate_flags(&nl.nl_flags).is_ok());
+            assert!(nl.nl_flags.contains(&NlmF::Request));
+        }
+        assert!(presets[1].nl_flags.contains(&NlmF::Dump));
+        assert!(presets[2].nl_flags.contains(&NlmF::Excl));
+        assert!(presets[3].nl_flags.contains(&NlmF::Replace));
+    }
+
+    #[test]
+    fn test_nlhdr_add_flags() {
+        let mut nl = Nlmsghdr::request(Nlmsg::Noop, NlEmpty);
+        add_flags(&mut nl, &[NlmF::Request, NlmF::Ack]);
+        assert_eq!(nl.nl_flags, vec![NlmF::Request, NlmF::Ack]);
+    }
+
+    #[test]
+    fn test_nlhdr_de_mode() {
+        let nl = Nlmsghdr::<Nlmsg, Vec<u8>>::new(
+            None,
+            Nlmsg::Noop,
+            Vec::new(),
+            None,
+            None,
+            vec![1, 0, 2, 0, 3, 0, 0, 0],
+        );
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        nl.serialize(&mut mem).unwrap();
+        nl.serialize(&mut mem).unwrap();
+        let bytes = mem.as_ref().to_vec();
+
+        let mut mem = StreamReadBuffer::new(&bytes);
+        let parsed = Nlmsghdr::<Nlmsg, (u16, u16)>::deserialize(&mut mem).unwrap();
+        assert_eq!(parsed.nl_payload, (1, 2));
+        let parsed = Nlmsghdr::<Nlmsg, (u16, u16)>::deserialize(&mut mem).unwrap();
+        assert_eq!(parsed.nl_payload, (1, 2));
+        assert!(mem.at_end());
+
+        let mut mem = StreamReadBuffer::new(&bytes);
+        assert!(
+            Nlmsghdr::<Nlmsg, (u16, u16)>::deserialize_with_mode(&mut mem, DeMode::Strict).is_err()
+        );
+        let mut mem = StreamReadBuffer::new(&bytes);
+        assert!(
+            Nlmsghdr::<Nlmsg, Vec<u8>>::deserialize_with_mode(&mut mem, DeMode::Strict).is_ok()
+        );
+        assert!(
+            Nlmsghdr::<Nlmsg, Vec<u8>>::deserialize_with_mode(&mut mem, DeMode::Strict).is_ok()
+        );
+        assert!(mem.at_end());
+    }
+
+    #[test]
+    fn test_nlhdr_de_mode_attr_types() {
+        let attrs = vec![
+            Nlattr::new(None, 1u16, 0x10u16).unwrap(),
+            Nlattr::new(None, 100u16, 0u32).unwrap(),
+        ];
+        let genl = Genlmsghdr::<CtrlCmd, u16>::new(CtrlCmd::Getfamily, 2, attrs).unwrap();
+        let nl = Nlmsghdr::new(None, Nlmsg::Noop, Vec::new(), None, None, genl);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        nl.serialize(&mut mem).unwrap();
+        let bytes = mem.as_ref().to_vec();
+
+        let mut mem = StreamReadBuffer::new(&bytes);
+        let parsed =
+            Nlmsghdr::<Nlmsg, Genlmsghdr<CtrlCmd, CtrlAttr>>::deserialize(&mut mem).unwrap();
+        assert_eq!(
+            parsed
+                .nl_payload
+                .get_attr_handle()
+                .get_attribute(CtrlAttr::UnrecognizedVariant(100))
+                .map(|attr| attr.nla_len),
+            Some(8)
+        );
+        let mut mem = StreamReadBuffer::new(&bytes);
+        assert!(
+            Nlmsghdr::<Nlmsg, Genlmsghdr<CtrlCmd, CtrlAttr>>::deserialize_with_mode(
+                &mut mem,
+                DeMode::Strict
+            )
+            .is_err()
+        );
+        let mut mem = StreamReadBuffer::new(&bytes);
+        assert!(
+            Nlmsghdr::<Nlmsg, Genlmsghdr<CtrlCmd, u16>>::deserialize_with_mode(
+                &mut mem,
+                DeMode::Strict
+            )
+            .is_ok()
+        );
+    }
 }
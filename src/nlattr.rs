@@ -12,7 +12,7 @@
 //! // Get parsing handler for the attributes in this message where the next call
 //! // to either get_nested_attributes() or get_payload_with() will expect a u16 type
 //! // to be provided
-//! let mut handle = nlmsg.nl_payload.get_attr_handle();
+//! let handle = nlmsg.nl_payload.get_attr_handle();
 //!
 //! // Get the nested attribute where the Nlattr field of nla_type is equal to 1 and return
 //! // a handler containing only this nested attribute internally
@@ -52,15 +52,28 @@
 //!     attribute payloads contained in the `Genlmsghdr` payload
 //!     * Only `Nlattr` knows what is padding and what is not in its own payload - to every other
 //!     serialization and deserialization method, it may or may not be padding
+//! * When deserializing, the amount of padding to skip is computed from the length in the
+//! attribute header rather than the size of the parsed payload so payloads with odd lengths or
+//! trailing bytes (strings, MAC addresses, ports) never shift the following attributes
+//!
+//! The `NLA_F_NESTED` and `NLA_F_NET_BYTEORDER` flags are stored separately from `nla_type` so
+//! that attribute type enums still match when the kernel sets them. `Nlattr::nested` returns a
+//! builder that sets `NLA_F_NESTED` and tracks the length while nested attributes are added.
+//!
+//! Some families require 64-bit payloads to be 8 byte aligned and insert an empty pad attribute
+//! such as `IFLA_PAD` before them when needed. `GenlmsghdrBuilder::attr_64bit` and
+//! `Rtattrs::push_64bit` emit these pad attributes and `AttrHandle::iter_without_pad` and
+//! `Rtattrs::iter_without_pad` skip them when traversing parsed attributes.
 
-use std::slice;
+use std::{borrow::Cow, collections::HashMap, convert::TryFrom, marker::PhantomData, slice};
 
 use buffering::{StreamReadBuffer, StreamWriteBuffer};
 
 use crate::{
-    consts::NlAttrType,
+    consts::{alignto, NlAttrType, NLA_F_NESTED, NLA_F_NET_BYTEORDER, NLA_TYPE_MASK},
+    deserialize_bounded,
     err::{DeError, NlError, SerError},
-    Nl,
+    skip_bytes, Nl,
 };
 
 impl<T, P> Nl for Vec<Nlattr<T, P>>
@@ -81,13 +94,17 @@ where
     {
         let mut vec = Vec::new();
         let mut size_hint = mem.take_size_hint();
-        while size_hint > Some(0) || (size_hint == None && !mem.at_end()) {
+        while size_hint > Some(0) || (size_hint.is_none() && !mem.at_end()) {
             let next = Nlattr::<T, P>::deserialize(mem)?;
             if let Some(val) = size_hint {
                 if val > 0 {
-                    let result = val.checked_sub(next.asize()).ok_or_else(|| {
-                        DeError::new("Deserialization read passed the end of the specified buffer")
-                    })?;
+                    let result =
+                        val.checked_sub(alignto(next.nla_len as usize))
+                            .ok_or_else(|| {
+                                DeError::new(
+                                    "Deserialization read passed the end of the specified buffer",
+                                )
+                            })?;
                     size_hint = Some(result);
                 }
             }
@@ -112,9 +129,13 @@ where
         }
         size
     }
+
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.iter().try_for_each(|attr| attr.check_attr_types())
+    }
 }
 
-impl<'a, T> Nl for &'a [Nlattr<T, Vec<u8>>]
+impl<T> Nl for &[Nlattr<T, Vec<u8>>]
 where
     T: NlAttrType,
 {
@@ -145,11 +166,108 @@ where
     }
 }
 
+const NLA_HDRLEN: usize = 4;
+
+/// Get the integer value of an attribute type without consuming it
+fn nla_type_value<T>(nla_type: &T) -> Result<u16, SerError>
+where
+    T: NlAttrType,
+{
+    let mut value = [0u8; 2];
+    nla_type.serialize(&mut StreamWriteBuffer::new_sized(&mut value))?;
+    Ok(u16::from_ne_bytes(value))
+}
+
+/// Deserialize the payload of an attribute with a length of `attr_len` including the header and
+/// skip the padding that follows it
+///
+/// The length in the header is used instead of the size of the parsed payload so that payloads
+/// with trailing bytes the payload type does not read, such as extra null bytes after a string,
+/// do not cause the following attributes to be misparsed. Missing padding after the last
+/// attribute in a buffer is tolerated.
+///
+/// `Nlattr` and `Rtattr` use this after reading their header - it can be used in the same way
+/// when implementing `Nl` for other attribute formats. `Nl::pad` writes the matching padding
+/// when serializing.
+pub fn deserialize_attr_payload<P, B>(
+    mem: &mut StreamReadBuffer<B>,
+    attr_len: usize,
+) -> Result<P, DeError>
+where
+    P: Nl,
+    B: AsRef<[u8]>,
+{
+    let payload_len = attr_len.checked_sub(NLA_HDRLEN).ok_or_else(|| {
+        DeError::new(&format!(
+            "Attribute length {} is shorter than the attribute header",
+            attr_len
+        ))
+    })?;
+    let (payload, unparsed_len) = deserialize_bounded(mem, payload_len)?;
+    skip_bytes(mem, unparsed_len + alignto(attr_len) - attr_len)?;
+    Ok(payload)
+}
+
+/// Deserialize an attribute payload, checking that its length matches the size of `R` if the
+/// size is known statically
+pub(crate) fn deserialize_payload<R>(payload: &[u8]) -> Result<R, DeError>
+where
+    R: Nl,
+{
+    if let Some(size) = R::type_size() {
+        if size != payload.len() {
+            return Err(DeError::new(&format!(
+                "Attribute payload of {} bytes does not match the requested type of {} bytes",
+                payload.len(),
+                size
+            )));
+        }
+    }
+    let mut buf = StreamReadBuffer::new(payload);
+    buf.set_size_hint(payload.len());
+    R::deserialize(&mut buf)
+}
+
+/// Association of an attribute type constant with the Rust type of its payload
+///
+/// Implementations are usually created with the `impl_typed_attr!` macro and used with typed
+/// getters like `AttrHandle::get` so the payload type is checked at compile time.
+pub trait TypedAttr {
+    /// Enum of attribute types for the family
+    type Type;
+    /// Type of the attribute payload
+    type Payload: Nl;
+
+    /// Attribute type constant
+    fn attr_type() -> Self::Type;
+}
+
+/// Extraction of a struct from a set of attributes
+///
+/// Implementations are usually created with `#[derive(FromAttrs)]` behind the `derive` feature.
+pub trait FromAttrs: Sized {
+    /// Enum of attribute types for the family
+    type Type: NlAttrType;
+
+    /// Extract the struct from the attributes in `handle`
+    fn from_attrs(handle: &AttrHandle<'_, Self::Type>) -> Result<Self, DeError>;
+}
+
 /// Struct representing netlink attributes and payloads
-#[derive(Debug, PartialEq)]
+///
+/// Parsed attributes and attributes created with `new` store the payload as `Vec<u8>`. A payload
+/// that borrows instead, such as `Cow<[u8]>`, can be used with `with_payload` and
+/// `NlattrRef::into_nlattr`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Nlattr<T, P> {
-    /// Length of the attribute header and payload together
+    /// Length of the attribute header and payload together - recomputed from the payload when
+    /// serializing
     pub nla_len: u16,
+    /// Whether the `NLA_F_NESTED` flag is set in the attribute type
+    pub nla_nested: bool,
+    /// Whether the `NLA_F_NET_BYTEORDER` flag is set in the attribute type
+    pub nla_network_order: bool,
     /// Enum representing the type of the attribute payload
     pub nla_type: T,
     /// Payload of the attribute - either parsed or a binary buffer
@@ -161,6 +279,20 @@ where
     T: NlAttrType,
     P: Nl,
 {
+    /// Create an attribute that stores the payload as is instead of serializing it into a
+    /// `Vec<u8>` - for example a `Cow<[u8]>` borrowing static data
+    pub fn with_payload(nla_type: T, payload: P) -> Self {
+        let mut attr = Nlattr {
+            nla_len: 0,
+            nla_nested: false,
+            nla_network_order: false,
+            nla_type,
+            payload,
+        };
+        attr.nla_len = attr.size() as u16;
+        attr
+    }
+
     /// Get the size of the payload only
     pub fn payload_size(&self) -> usize {
         self.payload.size()
@@ -178,6 +310,8 @@ where
     {
         let mut attr = Nlattr {
             nla_len: nla_len.unwrap_or(0),
+            nla_nested: false,
+            nla_network_order: false,
             nla_type,
             payload: Vec::new(),
         };
@@ -200,6 +334,35 @@ where
         Ok(())
     }
 
+    /// Create an attribute with the type and payload type associated by `TypedAttr`
+    pub fn new_typed<A>(payload: A::Payload) -> Result<Self, SerError>
+    where
+        A: TypedAttr<Type = T>,
+    {
+        Nlattr::new(None, A::attr_type(), payload)
+    }
+
+    /// Start building an attribute with the `NLA_F_NESTED` flag set that contains nested
+    /// attributes
+    pub fn nested(nla_type: T) -> NlattrBuilder<T> {
+        NlattrBuilder::new(nla_type)
+    }
+
+    /// Replace the payload with the provided nested attributes - useful for writing back nested
+    /// attributes edited through an owned `AttrHandle`
+    pub fn set_nested_attributes<TT, P>(&mut self, attrs: &[Nlattr<TT, P>]) -> Result<(), SerError>
+    where
+        TT: NlAttrType,
+        P: Nl,
+    {
+        self.payload.clear();
+        self.nla_len = (self.nla_len.size() + self.nla_type.size()) as u16;
+        for attr in attrs {
+            self.add_nested_attribute(attr)?;
+        }
+        Ok(())
+    }
+
     /// Add a nested attribute to the end of the payload
     pub fn add_nested_attribute<TT, P>(&mut self, attr: &Nlattr<TT, P>) -> Result<(), SerError>
     where
@@ -216,17 +379,20 @@ where
     }
 
     /// Get an `Nlattr` payload as a provided type
+    ///
+    /// If `R::type_size()` is known, the payload length must match it exactly. Types with a
+    /// variable size such as `NlString` validate their own contents.
     pub fn get_payload_as<R>(&self) -> Result<R, DeError>
     where
         R: Nl,
     {
-        let mut buf = StreamReadBuffer::new(&self.payload);
-        buf.set_size_hint(self.payload_size());
-        R::deserialize(&mut buf)
+        deserialize_payload(&self.payload)
     }
 
     /// Return an `AttrHandle` for attributes nested in the given attribute payload
-    pub fn get_nested_attributes<R>(&self) -> Result<AttrHandle<R>, DeError>
+    ///
+    /// The handle owns the parsed attributes so it can be used to descend further.
+    pub fn get_nested_attributes<R>(&self) -> Result<AttrHandle<'static, R>, DeError>
     where
         R: NlAttrType,
     {
@@ -234,6 +400,14 @@ where
             &mut StreamReadBuffer::new(&self.payload),
         )?))
     }
+
+    /// Iterate over the attributes nested in the given attribute payload without allocating
+    pub fn iter_nested_attributes<R>(&self) -> AttrIter<'_, R>
+    where
+        R: NlAttrType,
+    {
+        AttrIter::new(&self.payload)
+    }
 }
 
 impl<T, P> Nl for Nlattr<T, P>
@@ -242,8 +416,17 @@ where
     P: Nl,
 {
     fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        self.nla_len.serialize(mem)?;
-        self.nla_type.serialize(mem)?;
+        let nla_len = u16::try_from(self.size())
+            .map_err(|_| SerError::new("Attribute is too long to fit in an attribute header"))?;
+        nla_len.serialize(mem)?;
+        let mut nla_type = nla_type_value(&self.nla_type)?;
+        if self.nla_nested {
+            nla_type |= NLA_F_NESTED;
+        }
+        if self.nla_network_order {
+            nla_type |= NLA_F_NET_BYTEORDER;
+        }
+        nla_type.serialize(mem)?;
         self.payload.serialize(mem)?;
         self.pad(mem)?;
         Ok(())
@@ -254,21 +437,366 @@ where
         B: AsRef<[u8]>,
     {
         let nla_len = u16::deserialize(mem)?;
-        let nla_type = T::deserialize(mem)?;
-        mem.set_size_hint(nla_len as usize - (nla_len.size() + nla_type.size()));
-        let payload = P::deserialize(mem)?;
-        let nla = Nlattr {
+        let nla_type = u16::deserialize(mem)?;
+        let nla_nested = nla_type & NLA_F_NESTED == NLA_F_NESTED;
+        let nla_network_order = nla_type & NLA_F_NET_BYTEORDER == NLA_F_NET_BYTEORDER;
+        let nla_type = T::from(nla_type & NLA_TYPE_MASK);
+        let payload = deserialize_attr_payload(mem, nla_len as usize)?;
+        Ok(Nlattr {
             nla_len,
+            nla_nested,
+            nla_network_order,
             nla_type,
             payload,
-        };
-        nla.strip(mem)?;
-        Ok(nla)
+        })
     }
 
     fn size(&self) -> usize {
         self.nla_len.size() + self.nla_type.size() + self.payload.size()
     }
+
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.nla_type.check_attr_types()
+    }
+}
+
+/// Builder for attributes containing nested attributes, created with `Nlattr::nested`
+///
+/// The length of the attribute is updated as each nested attribute is added. The first error
+/// encountered while adding attributes is returned from `build`.
+pub struct NlattrBuilder<T> {
+    attr: Nlattr<T, Vec<u8>>,
+    err: Option<SerError>,
+}
+
+impl<T> NlattrBuilder<T>
+where
+    T: NlAttrType,
+{
+    fn new(nla_type: T) -> Self {
+        let mut attr = Nlattr {
+            nla_len: 0,
+            nla_nested: true,
+            nla_network_order: false,
+            nla_type,
+            payload: Vec::new(),
+        };
+        attr.nla_len = attr.size() as u16;
+        NlattrBuilder { attr, err: None }
+    }
+
+    /// Add an already constructed nested attribute
+    pub fn push_attr<TT, P>(mut self, attr: Nlattr<TT, P>) -> Self
+    where
+        TT: NlAttrType,
+        P: Nl,
+    {
+        if self.err.is_none() {
+            if let Err(e) = self.attr.add_nested_attribute(&attr) {
+                self.err = Some(e);
+            }
+        }
+        self
+    }
+
+    /// Add a nested attribute with a payload of any type that implements `Nl`
+    pub fn attr<TT, P>(self, nla_type: TT, payload: P) -> Self
+    where
+        TT: NlAttrType,
+        P: Nl,
+    {
+        match Nlattr::new(None, nla_type, payload) {
+            Ok(attr) => self.push_attr(attr),
+            Err(e) => self.fail(e),
+        }
+    }
+
+    /// Add a nested attribute that itself contains nested attributes
+    pub fn nested<TT>(self, builder: NlattrBuilder<TT>) -> Self
+    where
+        TT: NlAttrType,
+    {
+        match builder.build() {
+            Ok(attr) => self.push_attr(attr),
+            Err(e) => self.fail(e),
+        }
+    }
+
+    fn fail(mut self, e: SerError) -> Self {
+        if self.err.is_none() {
+            self.err = Some(e);
+        }
+        self
+    }
+
+    /// Build the attribute or return the first error encountered while adding attributes
+    pub fn build(self) -> Result<Nlattr<T, Vec<u8>>, SerError> {
+        match self.err {
+            Some(e) => Err(e),
+            None => Ok(self.attr),
+        }
+    }
+}
+
+/// Array encoded as nested attributes whose types are the sequential indices of the elements
+///
+/// Families like nl80211 and devlink use this encoding for lists. Indices start at `1` when
+/// serializing and are ignored when deserializing so elements are returned in the order they
+/// appear in the buffer. Use this type as the payload of the attribute containing the array.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttrArray<T>(pub Vec<T>);
+
+impl<T> AttrArray<T> {
+    /// Create a new array from a `Vec`
+    pub fn new(vec: Vec<T>) -> Self {
+        AttrArray(vec)
+    }
+
+    /// Return the underlying `Vec`
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> From<Vec<T>> for AttrArray<T> {
+    fn from(vec: Vec<T>) -> Self {
+        AttrArray(vec)
+    }
+}
+
+impl<T> From<AttrArray<T>> for Vec<T> {
+    fn from(array: AttrArray<T>) -> Self {
+        array.0
+    }
+}
+
+impl<T> Nl for AttrArray<T>
+where
+    T: Nl,
+{
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        for (i, item) in self.0.iter().enumerate() {
+            let nla_len = u16::try_from(NLA_HDRLEN + item.size()).map_err(|_| {
+                SerError::new("Array element is too long to fit in an attribute header")
+            })?;
+            let nla_type = u16::try_from(i + 1)
+                .ok()
+                .filter(|t| t & NLA_TYPE_MASK == *t)
+                .ok_or_else(|| SerError::new("Too many array elements for attribute indices"))?;
+            nla_len.serialize(mem)?;
+            nla_type.serialize(mem)?;
+            item.serialize(mem)?;
+            item.pad(mem)?;
+        }
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    where
+        B: AsRef<[u8]>,
+    {
+        Vec::<Nlattr<u16, Vec<u8>>>::deserialize(mem)?
+            .iter()
+            .map(Nlattr::get_payload_as)
+            .collect::<Result<Vec<_>, _>>()
+            .map(AttrArray)
+    }
+
+    fn size(&self) -> usize {
+        self.0
+            .iter()
+            .map(|item| alignto(NLA_HDRLEN + item.size()))
+            .sum()
+    }
+}
+
+/// Borrowed view of an attribute and its payload, yielded by `AttrIter`
+#[derive(Debug, PartialEq)]
+pub struct NlattrRef<'a, T> {
+    /// Length of the attribute header and payload together
+    pub nla_len: u16,
+    /// Whether the `NLA_F_NESTED` flag is set in the attribute type
+    pub nla_nested: bool,
+    /// Whether the `NLA_F_NET_BYTEORDER` flag is set in the attribute type
+    pub nla_network_order: bool,
+    /// Enum representing the type of the attribute payload
+    pub nla_type: T,
+    /// Payload of the attribute borrowed from the buffer being iterated over
+    pub payload: &'a [u8],
+}
+
+impl<'a, T> NlattrRef<'a, T>
+where
+    T: NlAttrType,
+{
+    /// Get the payload as a provided type, checking the payload length the same way as
+    /// `Nlattr::get_payload_as`
+    pub fn get_payload_as<R>(&self) -> Result<R, DeError>
+    where
+        R: Nl,
+    {
+        deserialize_payload(self.payload)
+    }
+
+    /// Iterate over the attributes nested in the payload without allocating
+    pub fn iter_nested_attributes<R>(&self) -> AttrIter<'a, R>
+    where
+        R: NlAttrType,
+    {
+        AttrIter::new(self.payload)
+    }
+
+    /// Convert into an `Nlattr` whose payload borrows from the same buffer without copying
+    pub fn into_nlattr(self) -> Nlattr<T, Cow<'a, [u8]>> {
+        Nlattr {
+            nla_len: self.nla_len,
+            nla_nested: self.nla_nested,
+            nla_network_order: self.nla_network_order,
+            nla_type: self.nla_type,
+            payload: Cow::Borrowed(self.payload),
+        }
+    }
+}
+
+/// Iterator over attributes in a binary buffer that yields borrowed views of each attribute
+///
+/// No attribute is copied out of the buffer. If an attribute header is malformed, an error is
+/// returned and iteration stops.
+pub struct AttrIter<'a, T> {
+    buf: &'a [u8],
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T> AttrIter<'a, T> {
+    /// Create an iterator over the attributes in `buf`
+    pub fn new(buf: &'a [u8]) -> Self {
+        AttrIter {
+            buf,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for AttrIter<'a, T>
+where
+    T: NlAttrType,
+{
+    type Item = Result<NlattrRef<'a, T>, DeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let buf = self.buf;
+        if buf.len() < 4 {
+            self.buf = &[];
+            return Some(Err(DeError::new(
+                "Buffer too short for an attribute header",
+            )));
+        }
+        let nla_len = u16::from_ne_bytes([buf[0], buf[1]]);
+        let nla_type = u16::from_ne_bytes([buf[2], buf[3]]);
+        if (nla_len as usize) < 4 || nla_len as usize > buf.len() {
+            self.buf = &[];
+            return Some(Err(DeError::new(&format!(
+                "Invalid attribute length {} for a buffer of {} bytes",
+                nla_len,
+                buf.len()
+            ))));
+        }
+        self.buf = &buf[alignto(nla_len as usize).min(buf.len())..];
+        Some(Ok(NlattrRef {
+            nla_len,
+            nla_nested: nla_type & NLA_F_NESTED == NLA_F_NESTED,
+            nla_network_order: nla_type & NLA_F_NET_BYTEORDER == NLA_F_NET_BYTEORDER,
+            nla_type: T::from(nla_type & NLA_TYPE_MASK),
+            payload: &buf[4..nla_len as usize],
+        }))
+    }
+}
+
+/// Limits on the attribute structure of a message from an untrusted source
+///
+/// Only attributes with `NLA_F_NESTED` set are descended into because nesting cannot otherwise
+/// be detected without knowing the attribute family. Top level attributes are at depth 1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AttrLimits {
+    /// Maximum nesting depth
+    pub max_depth: usize,
+    /// Maximum number of attributes at all nesting levels combined
+    pub max_attrs: usize,
+}
+
+impl AttrLimits {
+    /// Default maximum nesting depth
+    pub const DEFAULT_MAX_DEPTH: usize = 16;
+    /// Default maximum number of attributes
+    pub const DEFAULT_MAX_ATTRS: usize = 4096;
+
+    /// Create new limits
+    pub fn new(max_depth: usize, max_attrs: usize) -> Self {
+        AttrLimits {
+            max_depth,
+            max_attrs,
+        }
+    }
+
+    /// Check the attributes in `buf`, returning an error if they are malformed or exceed the
+    /// limits
+    pub fn check(&self, buf: &[u8]) -> Result<(), DeError> {
+        let mut count = 0;
+        self.check_nested(buf, 0, &mut count)
+    }
+
+    // Walks the attribute tree with an explicit stack so the depth of the call stack does not
+    // depend on the input.
+    fn check_nested(&self, buf: &[u8], depth: usize, count: &mut usize) -> Result<(), DeError> {
+        if depth >= self.max_depth {
+            return Err(self.depth_error());
+        }
+        let mut stack = vec![AttrIter::<u16>::new(buf)];
+        while let Some(iter) = stack.last_mut() {
+            let attr = match iter.next() {
+                Some(attr) => attr?,
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            };
+            *count += 1;
+            if *count > self.max_attrs {
+                return Err(self.count_error());
+            }
+            if attr.nla_nested {
+                if depth + stack.len() >= self.max_depth {
+                    return Err(self.depth_error());
+                }
+                stack.push(AttrIter::new(attr.payload));
+            }
+        }
+        Ok(())
+    }
+
+    fn depth_error(&self) -> DeError {
+        DeError::new(&format!(
+            "Attributes are nested more than {} levels deep",
+            self.max_depth
+        ))
+    }
+
+    fn count_error(&self) -> DeError {
+        DeError::new(&format!(
+            "Message contains more than {} attributes",
+            self.max_attrs
+        ))
+    }
+}
+
+impl Default for AttrLimits {
+    fn default() -> Self {
+        AttrLimits::new(Self::DEFAULT_MAX_DEPTH, Self::DEFAULT_MAX_ATTRS)
+    }
 }
 
 /// Handle returned by `Genlmsghdr` for traversing nested attribute structures
@@ -309,50 +837,97 @@ where
         }
     }
 
+    /// Get the underlying `Vec` as a mutable reference, copying the attributes first if they are
+    /// borrowed so they can be changed, added, or removed
+    pub fn to_mut(&mut self) -> &mut Vec<Nlattr<T, Vec<u8>>>
+    where
+        T: Clone,
+    {
+        if let AttrHandle::Borrowed(v) = *self {
+            *self = AttrHandle::Owned(v.to_vec());
+        }
+        match self {
+            AttrHandle::Owned(ref mut v) => v,
+            AttrHandle::Borrowed(_) => unreachable!(),
+        }
+    }
+
+    /// Convert into a handle that owns its attributes and no longer borrows from the message it
+    /// was created from
+    pub fn into_owned(self) -> AttrHandle<'static, T>
+    where
+        T: Clone,
+    {
+        AttrHandle::Owned(self.into_vec())
+    }
+
+    /// Return the attributes as an owned `Vec`, copying them if they are borrowed
+    pub fn into_vec(self) -> Vec<Nlattr<T, Vec<u8>>>
+    where
+        T: Clone,
+    {
+        match self {
+            AttrHandle::Owned(v) => v,
+            AttrHandle::Borrowed(v) => v.to_vec(),
+        }
+    }
+
     /// Get size of buffer required to hold attributes
     pub fn size(&self) -> usize {
         self.get_slice().asize()
     }
 
     /// If attributes are parsed, pass back iterator over attributes
-    pub fn iter(&self) -> slice::Iter<Nlattr<T, Vec<u8>>> {
+    pub fn iter(&self) -> slice::Iter<'_, Nlattr<T, Vec<u8>>> {
         self.get_slice().iter()
     }
 
+    /// Iterate over attributes, skipping pad attributes of type `pad_type` that the kernel inserts
+    /// to align 64-bit payloads
+    pub fn iter_without_pad(&self, pad_type: T) -> impl Iterator<Item = &Nlattr<T, Vec<u8>>> {
+        self.iter().filter(move |attr| attr.nla_type != pad_type)
+    }
+
+    /// Iterate over the attributes nested in an attribute without allocating
+    pub fn iter_nested_attributes<S>(&self, subattr: T) -> Result<AttrIter<'_, S>, NlError>
+    where
+        S: NlAttrType,
+    {
+        Ok(self
+            .get_attribute(subattr)
+            .ok_or_else(|| NlError::new("Couldn't find specified attribute"))?
+            .iter_nested_attributes())
+    }
+
     /// Get the payload of an attribute as a handle for parsing nested attributes
-    pub fn get_nested_attributes<S>(&mut self, subattr: T) -> Result<AttrHandle<S>, NlError>
+    ///
+    /// The returned handle owns the parsed attributes and does not borrow from this handle so
+    /// calls can be chained to descend through multiple levels of nesting, each with its own
+    /// attribute type.
+    pub fn get_nested_attributes<S>(&self, subattr: T) -> Result<AttrHandle<'static, S>, NlError>
     where
         S: NlAttrType,
     {
-        Ok(AttrHandle::new(Vec::<Nlattr<S, Vec<u8>>>::deserialize(
-            &mut StreamReadBuffer::new(
-                &self
-                    .get_attribute(subattr)
-                    .ok_or_else(|| NlError::new("Couldn't find specified attribute"))?
-                    .payload,
-            ),
-        )?))
+        Ok(self
+            .get_attribute(subattr)
+            .ok_or_else(|| NlError::new("Couldn't find specified attribute"))?
+            .get_nested_attributes()?)
     }
 
     /// Get nested attributes from a parsed handle
-    pub fn get_attribute<'b>(&'b self, t: T) -> Option<&'b Nlattr<T, Vec<u8>>> {
-        for item in self.get_slice().iter() {
-            if item.nla_type == t {
-                return Some(&item);
-            }
-        }
-        None
+    pub fn get_attribute(&self, t: T) -> Option<&Nlattr<T, Vec<u8>>> {
+        self.get_slice().iter().find(|item| item.nla_type == t)
+    }
+
+    /// Check whether an attribute is present - useful for flag attributes with no payload
+    pub fn has_attribute(&self, t: T) -> bool {
+        self.get_attribute(t).is_some()
     }
 
     /// Mutably get nested attributes from a parsed handle
-    pub fn get_attribute_mut<'b>(&'b mut self, t: T) -> Option<&'b mut Nlattr<T, Vec<u8>>> {
+    pub fn get_attribute_mut(&mut self, t: T) -> Option<&mut Nlattr<T, Vec<u8>>> {
         let vec_mut = self.get_vec_mut()?;
-        for item in vec_mut.iter_mut() {
-            if item.nla_type == t {
-                return Some(item);
-            }
-        }
-        None
+        vec_mut.iter_mut().find(|item| item.nla_type == t)
     }
 
     /// Parse binary payload as a type that implements `Nl` using `deserialize` with an option size
@@ -366,17 +941,182 @@ where
             _ => Err(DeError::new("Failed to find specified attribute")),
         }
     }
+
+    /// Get the payload of an attribute as the type associated with it by `TypedAttr` or `None` if
+    /// the attribute is not present
+    pub fn get<A>(&self) -> Result<Option<A::Payload>, DeError>
+    where
+        A: TypedAttr<Type = T>,
+    {
+        self.get_attribute(A::attr_type())
+            .map(Nlattr::get_payload_as)
+            .transpose()
+    }
+
+    /// Build an index of the attributes by type for repeated lookups on large messages
+    ///
+    /// Each lookup through `get_attribute` scans the attributes while lookups through the index
+    /// take constant time after the index is built once.
+    pub fn index(&self) -> AttrIndex<'_, T> {
+        AttrIndex::new(self.get_slice(), DuplicatePolicy::FirstWins)
+            .expect("Only DuplicatePolicy::Error can fail")
+    }
+
+    /// Build an index of the attributes by type, resolving attribute types that appear more than
+    /// once according to `policy`
+    ///
+    /// Returns an error if `policy` is `DuplicatePolicy::Error` and a type appears more than once.
+    pub fn index_with_policy(&self, policy: DuplicatePolicy) -> Result<AttrIndex<'_, T>, DeError> {
+        AttrIndex::new(self.get_slice(), policy)
+    }
+
+    /// Iterate over all attributes of the given type in the order they appear
+    pub fn get_attributes(&self, t: T) -> impl Iterator<Item = &Nlattr<T, Vec<u8>>> {
+        self.iter().filter(move |item| item.nla_type == t)
+    }
+
+    /// Check these attributes and all attributes nested in them with `NLA_F_NESTED` against
+    /// `limits` before traversing a message from an untrusted source
+    pub fn check_limits(&self, limits: &AttrLimits) -> Result<(), DeError> {
+        let attrs = self.get_slice();
+        let mut count = attrs.len();
+        if count > limits.max_attrs {
+            return Err(limits.count_error());
+        }
+        if !attrs.is_empty() && limits.max_depth == 0 {
+            return Err(limits.depth_error());
+        }
+        for attr in attrs.iter().filter(|attr| attr.nla_nested) {
+            limits.check_nested(&attr.payload, 1, &mut count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Handling of attribute types that appear more than once in a set of attributes
+///
+/// The kernel semantics differ between families so the policy is chosen by the caller when
+/// building an `AttrIndex`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Keep the first attribute of each type - the behavior of `AttrHandle::get_attribute`
+    #[default]
+    FirstWins,
+    /// Keep the last attribute of each type
+    LastWins,
+    /// Keep every attribute of each type, available through `AttrIndex::get_attributes`
+    CollectAll,
+    /// Return an error if any attribute type appears more than once
+    Error,
+}
+
+/// Index of attributes by type created with `AttrHandle::index` or
+/// `AttrHandle::index_with_policy`
+pub struct AttrIndex<'a, T> {
+    attrs: &'a [Nlattr<T, Vec<u8>>],
+    index: HashMap<u16, Vec<usize>>,
+}
+
+impl<'a, T> AttrIndex<'a, T>
+where
+    T: NlAttrType,
+{
+    fn new(attrs: &'a [Nlattr<T, Vec<u8>>], policy: DuplicatePolicy) -> Result<Self, DeError> {
+        let mut index: HashMap<u16, Vec<usize>> = HashMap::with_capacity(attrs.len());
+        for (i, attr) in attrs.iter().enumerate() {
+            let nla_type = match nla_type_value(&attr.nla_type) {
+                Ok(t) => t,
+                Err(_) => continue,
+            };
+            let entry = index.entry(nla_type).or_default();
+            match policy {
+                DuplicatePolicy::FirstWins if !entry.is_empty() => (),
+                DuplicatePolicy::LastWins => {
+                    entry.clear();
+                    entry.push(i);
+                }
+                DuplicatePolicy::Error if !entry.is_empty() => {
+                    return Err(DeError::new(&format!(
+                        "Attribute type {} appears more than once",
+                        nla_type
+                    )));
+                }
+                _ => entry.push(i),
+            }
+        }
+        Ok(AttrIndex { attrs, index })
+    }
+
+    /// Get an attribute by type - with `DuplicatePolicy::CollectAll` this is the first attribute
+    /// of the type
+    pub fn get_attribute(&self, t: T) -> Option<&'a Nlattr<T, Vec<u8>>> {
+        self.get_attributes(t).next()
+    }
+
+    /// Get the payload of an attribute as the type associated with it by `TypedAttr` or `None` if
+    /// the attribute is not present
+    pub fn get<A>(&self) -> Result<Option<A::Payload>, DeError>
+    where
+        A: TypedAttr<Type = T>,
+    {
+        self.get_attribute(A::attr_type())
+            .map(Nlattr::get_payload_as)
+            .transpose()
+    }
+
+    /// Iterate over the attributes of the given type kept by the duplicate policy
+    pub fn get_attributes(&self, t: T) -> impl Iterator<Item = &'a Nlattr<T, Vec<u8>>> + '_ {
+        let attrs = self.attrs;
+        self.index
+            .get(&t.into())
+            .into_iter()
+            .flatten()
+            .map(move |i| &attrs[*i])
+    }
+
+    /// Check whether an attribute is present
+    pub fn has_attribute(&self, t: T) -> bool {
+        self.index.contains_key(&t.into())
+    }
+
+    /// Parse the payload of an attribute as a type that implements `Nl`
+    pub fn get_attr_payload_as<R>(&self, attr: T) -> Result<R, DeError>
+    where
+        R: Nl,
+    {
+        match self.get_attribute(attr) {
+            Some(a) => a.get_payload_as::<R>(),
+            _ => Err(DeError::new("Failed to find specified attribute")),
+        }
+    }
+
+    /// Number of distinct attribute types in the index
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Check whether the index is empty
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    use std::io::{Cursor, Write};
+    use std::{
+        io::{Cursor, Write},
+        net::{Ipv4Addr, Ipv6Addr},
+    };
 
     use byteorder::{NativeEndian, WriteBytesExt};
 
-    use crate::{consts::CtrlAttr, nl::NlEmpty};
+    use crate::{
+        consts::{CtrlAttr, CtrlAttrMcastGrp},
+        nl::NlEmpty,
+        types::{BeU16, BeU32, MacAddress, NlString},
+    };
 
     #[test]
     fn test_padding_size_calculation() {
@@ -410,6 +1150,8 @@ mod test {
 
         let nlattr_desired_deserialized = Nlattr {
             nla_len: 6,
+            nla_nested: false,
+            nla_network_order: false,
             nla_type: CtrlAttr::Unspec,
             payload: 4u16,
         };
@@ -460,6 +1202,391 @@ mod test {
         assert_eq!(nlattr.size(), 28);
     }
 
+    #[test]
+    fn test_nested_builder() {
+        let attr = Nlattr::nested(CtrlAttr::Ops)
+            .push_attr(Nlattr::new(None, 1u16, 5u32).unwrap())
+            .nested(Nlattr::nested(2u16).attr(1u16, 6u16))
+            .build()
+            .unwrap();
+        assert!(attr.nla_nested);
+        assert_eq!(attr.size(), 4 + 8 + 12);
+
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        attr.serialize(&mut mem).unwrap();
+        let mut desired = Cursor::new(Vec::new());
+        desired.write_u16::<NativeEndian>(24).unwrap();
+        desired
+            .write_u16::<NativeEndian>(u16::from(CtrlAttr::Ops) | NLA_F_NESTED)
+            .unwrap();
+        desired.write_u16::<NativeEndian>(8).unwrap();
+        desired.write_u16::<NativeEndian>(1).unwrap();
+        desired.write_u32::<NativeEndian>(5).unwrap();
+        desired.write_u16::<NativeEndian>(12).unwrap();
+        desired.write_u16::<NativeEndian>(2 | NLA_F_NESTED).unwrap();
+        desired.write_u16::<NativeEndian>(6).unwrap();
+        desired.write_u16::<NativeEndian>(1).unwrap();
+        desired.write_all(&[6, 0, 0, 0]).unwrap();
+        assert_eq!(mem.as_ref(), desired.get_ref().as_slice());
+
+        let mut reader = StreamReadBuffer::new(desired.into_inner());
+        let parsed = Nlattr::<CtrlAttr, Vec<u8>>::deserialize(&mut reader).unwrap();
+        assert_eq!(parsed, attr);
+        let handle = parsed.get_nested_attributes::<u16>().unwrap();
+        assert_eq!(handle.get_attr_payload_as::<u32>(1).unwrap(), 5);
+        let inner = handle.get_nested_attributes::<u16>(2).unwrap();
+        assert_eq!(inner.get_attr_payload_as::<u16>(1).unwrap(), 6);
+
+        let err = Nlattr::nested(CtrlAttr::Ops)
+            .attr(1u16, "a\0b".to_string())
+            .attr(2u16, 5u32)
+            .build();
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_flagged_attribute_lookup() {
+        let mut mem = Cursor::new(Vec::new());
+        mem.write_u16::<NativeEndian>(16).unwrap();
+        mem.write_u16::<NativeEndian>(u16::from(CtrlAttr::McastGroups) | NLA_F_NESTED)
+            .unwrap();
+        mem.write_u16::<NativeEndian>(12).unwrap();
+        mem.write_u16::<NativeEndian>(1 | NLA_F_NESTED).unwrap();
+        mem.write_u16::<NativeEndian>(8).unwrap();
+        mem.write_u16::<NativeEndian>(2 | NLA_F_NET_BYTEORDER)
+            .unwrap();
+        mem.write_all(&[0, 0, 0, 5]).unwrap();
+        let bytes = mem.into_inner();
+
+        let attrs =
+            Vec::<Nlattr<CtrlAttr, Vec<u8>>>::deserialize(&mut StreamReadBuffer::new(&bytes))
+                .unwrap();
+        let handle = AttrHandle::new(attrs);
+        let groups = handle.get_attribute(CtrlAttr::McastGroups).unwrap();
+        assert!(groups.nla_nested);
+        assert!(!groups.nla_network_order);
+
+        let group = handle
+            .get_nested_attributes::<u16>(CtrlAttr::McastGroups)
+            .unwrap()
+            .get_nested_attributes::<u16>(1)
+            .unwrap();
+        let id = group.get_attribute(2).unwrap();
+        assert!(id.nla_network_order);
+        assert!(!id.nla_nested);
+        assert_eq!(id.get_payload_as::<BeU32>().unwrap().get(), 5);
+
+        let id = AttrIter::<u16>::new(&bytes[4..])
+            .next()
+            .unwrap()
+            .unwrap()
+            .iter_nested_attributes::<u16>()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(id.nla_type, 2);
+        assert!(id.nla_network_order);
+
+        let mut out = StreamWriteBuffer::new_growable(None);
+        handle.get_slice().serialize(&mut out).unwrap();
+        assert_eq!(out.as_ref(), bytes.as_slice());
+    }
+
+    #[test]
+    fn test_attr_index() {
+        let handle = AttrHandle::new(vec![
+            Nlattr::new(None, CtrlAttr::FamilyId, 5u16).unwrap(),
+            Nlattr::new(None, CtrlAttr::FamilyName, "nlctrl").unwrap(),
+            Nlattr::new(None, CtrlAttr::FamilyId, 6u16).unwrap(),
+        ]);
+        let index = handle.index();
+        assert_eq!(index.len(), 2);
+        assert_eq!(
+            index
+                .get_attr_payload_as::<u16>(CtrlAttr::FamilyId)
+                .unwrap(),
+            5
+        );
+        assert_eq!(
+            index.get_attribute(CtrlAttr::FamilyName),
+            handle.get_attribute(CtrlAttr::FamilyName)
+        );
+        assert!(index.has_attribute(CtrlAttr::FamilyName));
+        assert!(!index.has_attribute(CtrlAttr::Version));
+        assert!(index.get_attr_payload_as::<u32>(CtrlAttr::Version).is_err());
+    }
+
+    #[test]
+    fn test_typed_attr() {
+        use crate::consts::{CtrlAttrFamilyId, CtrlAttrFamilyName, CtrlAttrVersion};
+
+        let handle = AttrHandle::new(vec![
+            Nlattr::new_typed::<CtrlAttrFamilyId>(16).unwrap(),
+            Nlattr::new_typed::<CtrlAttrFamilyName>("nlctrl".to_string()).unwrap(),
+        ]);
+        assert_eq!(handle.get::<CtrlAttrFamilyId>().unwrap(), Some(16));
+        assert_eq!(
+            handle.get::<CtrlAttrFamilyName>().unwrap(),
+            Some("nlctrl".to_string())
+        );
+        assert_eq!(handle.get::<CtrlAttrVersion>().unwrap(), None);
+        assert_eq!(handle.index().get::<CtrlAttrFamilyId>().unwrap(), Some(16));
+
+        // Payload size does not match the associated type
+        let handle = AttrHandle::new(vec![Nlattr::new(None, CtrlAttr::Version, 1u16).unwrap()]);
+        assert!(handle.get::<CtrlAttrVersion>().is_err());
+    }
+
+    #[test]
+    fn test_duplicate_policy() {
+        let handle = AttrHandle::new(vec![
+            Nlattr::new(None, CtrlAttr::FamilyId, 5u16).unwrap(),
+            Nlattr::new(None, CtrlAttr::FamilyName, "nlctrl").unwrap(),
+            Nlattr::new(None, CtrlAttr::FamilyId, 6u16).unwrap(),
+        ]);
+        let payloads = |index: &AttrIndex<CtrlAttr>| {
+            index
+                .get_attributes(CtrlAttr::FamilyId)
+                .map(|a| a.get_payload_as::<u16>().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let first = handle
+            .index_with_policy(DuplicatePolicy::FirstWins)
+            .unwrap();
+        assert_eq!(payloads(&first), vec![5]);
+        let last = handle.index_with_policy(DuplicatePolicy::LastWins).unwrap();
+        assert_eq!(payloads(&last), vec![6]);
+        assert_eq!(
+            last.get_attr_payload_as::<u16>(CtrlAttr::FamilyId).unwrap(),
+            6
+        );
+        let all = handle
+            .index_with_policy(DuplicatePolicy::CollectAll)
+            .unwrap();
+        assert_eq!(payloads(&all), vec![5, 6]);
+        assert_eq!(all.get_attributes(CtrlAttr::FamilyName).count(), 1);
+        assert!(handle.index_with_policy(DuplicatePolicy::Error).is_err());
+        assert_eq!(handle.get_attributes(CtrlAttr::FamilyId).count(), 2);
+
+        let unique = AttrHandle::new(vec![Nlattr::new(None, CtrlAttr::FamilyId, 5u16).unwrap()]);
+        assert!(unique.index_with_policy(DuplicatePolicy::Error).is_ok());
+    }
+
+    #[test]
+    fn test_odd_length_payloads() {
+        let mut mem = Cursor::new(Vec::new());
+        // String with an extra null byte that the parsed string does not account for
+        mem.write_u16::<NativeEndian>(9).unwrap();
+        mem.write_u16::<NativeEndian>(1).unwrap();
+        mem.write_all(&[b'a', b'b', b'c', 0, 0, 0, 0, 0]).unwrap();
+        mem.write_u16::<NativeEndian>(10).unwrap();
+        mem.write_u16::<NativeEndian>(2).unwrap();
+        mem.write_all(&[1, 2, 3, 4, 5, 6, 0, 0]).unwrap();
+        mem.write_u16::<NativeEndian>(6).unwrap();
+        mem.write_u16::<NativeEndian>(3).unwrap();
+        mem.write_all(&[0, 80, 0, 0]).unwrap();
+        mem.write_u16::<NativeEndian>(8).unwrap();
+        mem.write_u16::<NativeEndian>(4).unwrap();
+        mem.write_u32::<NativeEndian>(9).unwrap();
+        let bytes = mem.into_inner();
+
+        let mut reader = StreamReadBuffer::new(&bytes);
+        let name = Nlattr::<u16, String>::deserialize(&mut reader).unwrap();
+        assert_eq!(name.payload, "abc");
+        let mac = Nlattr::<u16, MacAddress>::deserialize(&mut reader).unwrap();
+        assert_eq!(mac.payload, MacAddress::new([1, 2, 3, 4, 5, 6]));
+        let port = Nlattr::<u16, BeU16>::deserialize(&mut reader).unwrap();
+        assert_eq!(port.payload.get(), 80);
+        let last = Nlattr::<u16, u32>::deserialize(&mut reader).unwrap();
+        assert_eq!(last.payload, 9);
+        assert!(reader.at_end());
+
+        let mut reader = StreamReadBuffer::new(&bytes[..12]);
+        reader.set_size_hint(12);
+        let strings = Vec::<Nlattr<u16, String>>::deserialize(&mut reader).unwrap();
+        assert_eq!(strings.len(), 1);
+
+        // Missing padding after the last attribute is tolerated
+        let mut reader = StreamReadBuffer::new(&bytes[12..22]);
+        assert!(Nlattr::<u16, MacAddress>::deserialize(&mut reader).is_ok());
+
+        let attrs = vec![
+            Nlattr::new(None, 1u16, "abc").unwrap(),
+            Nlattr::new(None, 2u16, MacAddress::new([1, 2, 3, 4, 5, 6])).unwrap(),
+            Nlattr::new(None, 3u16, BeU16::new(80)).unwrap(),
+            Nlattr::new(None, 4u16, 9u32).unwrap(),
+        ];
+        let mut out = StreamWriteBuffer::new_growable(None);
+        attrs.serialize(&mut out).unwrap();
+        assert_eq!(out.as_ref().len(), 8 + 12 + 8 + 8);
+        let parsed =
+            Vec::<Nlattr<u16, Vec<u8>>>::deserialize(&mut StreamReadBuffer::new(out.as_ref()))
+                .unwrap();
+        assert_eq!(parsed, attrs);
+    }
+
+    #[test]
+    fn test_cow_payload() {
+        static PAYLOAD: &[u8] = &[1, 2, 3, 4, 5];
+
+        let attr = Nlattr::with_payload(CtrlAttr::FamilyName, Cow::Borrowed(PAYLOAD));
+        assert_eq!(attr.nla_len, 9);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        attr.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), &[9, 0, 2, 0, 1, 2, 3, 4, 5, 0, 0, 0]);
+
+        let bytes = mem.as_ref().to_vec();
+        let borrowed = AttrIter::<CtrlAttr>::new(&bytes)
+            .next()
+            .unwrap()
+            .unwrap()
+            .into_nlattr();
+        assert!(matches!(borrowed.payload, Cow::Borrowed(_)));
+        assert_eq!(borrowed, attr);
+
+        let owned =
+            Nlattr::<CtrlAttr, Cow<[u8]>>::deserialize(&mut StreamReadBuffer::new(&bytes)).unwrap();
+        assert!(matches!(owned.payload, Cow::Owned(_)));
+        assert_eq!(owned, attr);
+    }
+
+    #[test]
+    fn test_attr_limits() {
+        let mut inner = Nlattr::nested(CtrlAttrMcastGrp::Unspec)
+            .attr(CtrlAttrMcastGrp::Id, 1u32)
+            .build()
+            .unwrap();
+        for _ in 0..3 {
+            inner = Nlattr::nested(CtrlAttrMcastGrp::Unspec)
+                .push_attr(inner)
+                .build()
+                .unwrap();
+        }
+        let outer = Nlattr::nested(CtrlAttr::McastGroups)
+            .push_attr(inner)
+            .build()
+            .unwrap();
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        outer.serialize(&mut mem).unwrap();
+
+        // Outer attribute, four nesting attributes and the ID make six levels and six attributes
+        assert!(AttrLimits::default().check(mem.as_ref()).is_ok());
+        assert!(AttrLimits::new(6, 6).check(mem.as_ref()).is_ok());
+        assert!(AttrLimits::new(5, 6).check(mem.as_ref()).is_err());
+        assert!(AttrLimits::new(6, 5).check(mem.as_ref()).is_err());
+
+        let handle = AttrHandle::new(vec![outer]);
+        assert!(handle.check_limits(&AttrLimits::new(6, 6)).is_ok());
+        assert!(handle.check_limits(&AttrLimits::new(5, 6)).is_err());
+        assert!(handle.check_limits(&AttrLimits::new(6, 5)).is_err());
+        assert!(handle.check_limits(&AttrLimits::new(0, 6)).is_err());
+    }
+
+    #[test]
+    fn test_attr_array() {
+        let array = AttrArray::new(vec![2412u32, 2437, 2462]);
+        let attr = Nlattr::new(None, CtrlAttr::Ops, array.clone()).unwrap();
+        assert_eq!(attr.size(), 4 + 3 * 8);
+
+        let elems = attr
+            .iter_nested_attributes::<u16>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            elems.iter().map(|e| e.nla_type).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(attr.get_payload_as::<AttrArray<u32>>().unwrap(), array);
+
+        let names = AttrArray::new(vec!["a".to_string(), "bcde".to_string()]);
+        let attr = Nlattr::new(None, CtrlAttr::Ops, names.clone()).unwrap();
+        assert_eq!(attr.size(), 4 + 8 + 12);
+        assert_eq!(attr.get_payload_as::<AttrArray<String>>().unwrap(), names);
+
+        assert!(attr.get_payload_as::<AttrArray<u32>>().is_err());
+    }
+
+    #[test]
+    fn test_attr_iter() {
+        let attr = Nlattr::nested(CtrlAttr::Ops)
+            .attr(1u16, 5u32)
+            .attr(2u16, 6u8)
+            .nested(Nlattr::nested(3u16).attr(1u16, "abc"))
+            .build()
+            .unwrap();
+        let handle = AttrHandle::new(vec![attr]);
+        let attrs = handle
+            .iter_nested_attributes::<u16>(CtrlAttr::Ops)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(attrs.len(), 3);
+        assert_eq!(attrs[0].get_payload_as::<u32>().unwrap(), 5);
+        assert_eq!(attrs[1].nla_len, 5);
+        assert_eq!(attrs[1].payload, &[6]);
+        assert!(attrs[2].nla_nested);
+        assert_eq!(attrs[2].nla_type, 3);
+        let inner = attrs[2]
+            .iter_nested_attributes::<u16>()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(inner.get_payload_as::<String>().unwrap(), "abc");
+
+        let mut iter = AttrIter::<u16>::new(&[8, 0, 1, 0, 0]);
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_payload_size_validation() {
+        let attr = Nlattr::new(None, CtrlAttr::FamilyId, 5u32).unwrap();
+        assert_eq!(attr.get_payload_as::<u32>().unwrap(), 5);
+        assert!(attr.get_payload_as::<u16>().is_err());
+        assert!(attr.get_payload_as::<u64>().is_err());
+        assert_eq!(attr.get_payload_as::<Vec<u8>>().unwrap(), vec![5, 0, 0, 0]);
+
+        let attr = Nlattr::new(None, CtrlAttr::FamilyName, "nlctrl").unwrap();
+        assert_eq!(
+            attr.get_payload_as::<NlString>().unwrap().as_str().unwrap(),
+            "nlctrl"
+        );
+        let attr = Nlattr::new(None, CtrlAttr::FamilyName, vec![b'a', b'b']).unwrap();
+        assert!(attr.get_payload_as::<NlString>().is_err());
+
+        let attr = Nlattr::new(None, CtrlAttr::FamilyId, [192u8, 168, 0, 1]).unwrap();
+        assert_eq!(
+            attr.get_payload_as::<Ipv4Addr>().unwrap(),
+            Ipv4Addr::new(192, 168, 0, 1)
+        );
+        assert!(attr.get_payload_as::<Ipv6Addr>().is_err());
+    }
+
+    #[test]
+    fn test_chained_nested_attributes() {
+        let attr = Nlattr::nested(CtrlAttr::Ops)
+            .nested(Nlattr::nested(1u16).nested(Nlattr::nested(2u16).attr(3u16, 7u32)))
+            .build()
+            .unwrap();
+        let handle = AttrHandle::new(vec![attr]);
+        let payload = handle
+            .get_nested_attributes::<u16>(CtrlAttr::Ops)
+            .unwrap()
+            .get_nested_attributes::<u16>(1)
+            .unwrap()
+            .get_nested_attributes::<u16>(2)
+            .unwrap()
+            .get_attr_payload_as::<u32>(3)
+            .unwrap();
+        assert_eq!(payload, 7);
+        assert!(handle
+            .get_nested_attributes::<u16>(CtrlAttr::Ops)
+            .unwrap()
+            .get_nested_attributes::<u16>(5)
+            .is_err());
+    }
+
     #[test]
     fn test_vec_nlattr_nl() {
         let mut vec_nlattr_desired = Cursor::new(vec![]);
@@ -0,0 +1,732 @@
+//! Multiplexing of concurrent requests over one socket
+//!
+//! `NlRouter` assigns sequence numbers to requests sent from any number of threads over a single
+//! `NlSocket` and routes the responses back to the `NlRouterReceiverHandle` returned for each
+//! request, so each outstanding request does not need a socket of its own. Messages that do not
+//! answer an outstanding request, such as multicast notifications, are discarded.
+//!
+//! With the `async` feature enabled, `AsyncNlRouter` does the same for sockets registered with an
+//! async runtime, returning a `Stream` for each request. `router::tokio::NlRouter` and
+//! `router::async_io::NlRouter` wrap an `NlSocket` for the respective runtime.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    io,
+    marker::PhantomData,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    task::Waker,
+};
+
+use buffering::{StreamReadBuffer, StreamWriteBuffer};
+
+#[cfg(feature = "logging")]
+use crate::log;
+use crate::{
+    consts::{alignto, NlFamily, NlType, NlmF, Nlmsg},
+    err::{NlError, Nlmsgerr},
+    nl::{DeMode, Nlmsghdr, RawHeader, NLMSG_HDRLEN},
+    socket::{done_error, nlmsgerr_error, NlSocket},
+    Nl,
+};
+
+#[derive(Default)]
+struct Queue {
+    msgs: VecDeque<Vec<u8>>,
+    waker: Option<Waker>,
+}
+
+// Messages received for outstanding requests, keyed by sequence number
+struct RouterState {
+    queues: HashMap<u32, Queue>,
+    next_seq: u32,
+    // A thread is blocked receiving from the socket - only used by `NlRouter`
+    receiving: bool,
+}
+
+impl RouterState {
+    fn new() -> Self {
+        RouterState {
+            queues: HashMap::new(),
+            next_seq: 1,
+            receiving: false,
+        }
+    }
+
+    // Assign a sequence number to a new request. Multicast notifications are sent with sequence
+    // number 0 so it is skipped.
+    fn register(&mut self) -> u32 {
+        let seq = self.next_seq;
+        self.next_seq = match seq.wrapping_add(1) {
+            0 => 1,
+            next => next,
+        };
+        self.queues.insert(seq, Queue::default());
+        seq
+    }
+
+    fn pop(&mut self, seq: u32) -> Option<Vec<u8>> {
+        self.queues.get_mut(&seq).and_then(|q| q.msgs.pop_front())
+    }
+
+    // Queue each message of the datagram for the request it answers and wake the task waiting
+    // for it. The messages after one with a malformed length are dropped.
+    fn route(&mut self, datagram: &[u8]) -> Result<(), NlError> {
+        let mut offset = 0;
+        while datagram.len() - offset >= NLMSG_HDRLEN {
+            let msg = &datagram[offset..];
+            let header = RawHeader::peek(msg)?;
+            if let Some(queue) = self.queues.get_mut(&header.nl_seq) {
+                queue.msgs.push_back(msg[..header.nl_len].to_vec());
+                if let Some(waker) = queue.waker.take() {
+                    waker.wake();
+                }
+            }
+            offset += alignto(header.nl_len).min(msg.len());
+        }
+        Ok(())
+    }
+
+    // Wake every task waiting for a response except the one for `seq` so that another one polls
+    // the socket when the task for `seq` stops polling it.
+    #[cfg(feature = "async")]
+    fn wake_others(&mut self, seq: u32) {
+        for (_, queue) in self.queues.iter_mut().filter(|(s, _)| **s != seq) {
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+// Serialize a request with the assigned sequence number and return whether it asks for an ACK.
+fn serialize_request<T, P>(mut msg: Nlmsghdr<T, P>, seq: u32) -> Result<(Vec<u8>, bool), NlError>
+where
+    T: Nl + NlType + Debug,
+    P: Nl + Debug,
+{
+    msg.nl_seq = seq;
+    #[cfg(feature = "logging")]
+    log!("Message sent:\n{:#?}", msg);
+    let mut mem = Vec::new();
+    msg.serialize(&mut StreamWriteBuffer::new_growable_ref(&mut mem))?;
+    Ok((mem, msg.nl_flags.contains(&NlmF::Ack)))
+}
+
+fn recv_datagram(socket: &NlSocket) -> io::Result<Vec<u8>> {
+    let mut mem = vec![0; socket.peek_len()?];
+    let read = socket.recv(&mut mem, 0)?;
+    mem.truncate(read);
+    Ok(mem)
+}
+
+// Parse a response to a request, ending the response at NLMSG_DONE, at NLMSG_ERROR, or after a
+// message without NLM_F_MULTI unless an ACK still follows.
+fn parse_response<T, P>(
+    msg: Vec<u8>,
+    ack: bool,
+    mode: DeMode,
+    finished: &mut bool,
+) -> Option<Result<Nlmsghdr<T, P>, NlError>>
+where
+    T: NlType,
+    P: Nl,
+{
+    // Messages are only queued once their length has been checked
+    let header = RawHeader::read(&msg);
+    if header.nl_type == u16::from(Nlmsg::Done) {
+        *finished = true;
+        return done_error(&msg[NLMSG_HDRLEN..]).map(Err);
+    }
+    let mut buf = StreamReadBuffer::new(msg);
+    if header.nl_type == u16::from(Nlmsg::Error) {
+        *finished = true;
+        return match Nlmsghdr::<Nlmsg, Nlmsgerr<Nlmsg>>::deserialize(&mut buf) {
+            Ok(ref err) if err.nl_payload.error == 0 => None,
+            Ok(ref err) => Some(Err(nlmsgerr_error(err))),
+            Err(e) => Some(Err(NlError::from(e))),
+        };
+    }
+    if header.nl_flags & u16::from(NlmF::Multi) == 0 && !ack {
+        *finished = true;
+    }
+    Some(Nlmsghdr::deserialize_with_mode(&mut buf, mode).map_err(NlError::from))
+}
+
+struct Shared {
+    socket: NlSocket,
+    state: Mutex<RouterState>,
+    received: Condvar,
+    de_mode: DeMode,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, RouterState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Block until a message for `seq` is received. One thread at a time receives from the
+    // socket and routes what it receives while the others wait for it.
+    fn next_msg(&self, seq: u32) -> Result<Vec<u8>, NlError> {
+        let mut state = self.state();
+        loop {
+            if let Some(msg) = state.pop(seq) {
+                return Ok(msg);
+            }
+            if state.receiving {
+                state = self.received.wait(state).unwrap_or_else(|e| e.into_inner());
+                continue;
+            }
+
+            state.receiving = true;
+            drop(state);
+            let datagram = recv_datagram(&self.socket);
+            state = self.state();
+            state.receiving = false;
+            self.received.notify_all();
+            match datagram {
+                Ok(ref d) if d.is_empty() => {
+                    return Err(NlError::new("No data could be read from the socket"))
+                }
+                Ok(d) => state.route(&d)?,
+                Err(e) => return Err(self.socket.recv_to_nl_error(e)),
+            }
+        }
+    }
+}
+
+/// Socket handle sending requests from several callers and routing responses back to them
+///
+/// Clones of a router share its socket so they can be moved to other threads.
+#[derive(Clone)]
+pub struct NlRouter {
+    shared: Arc<Shared>,
+}
+
+impl NlRouter {
+    /// Route requests and responses over `socket`
+    ///
+    /// The router assigns sequence numbers itself and receives datagrams directly from the
+    /// socket, so sequence number tracking and PID checks set on the socket are not used.
+    pub fn new(socket: NlSocket) -> Self {
+        NlRouter {
+            shared: Arc::new(Shared {
+                de_mode: socket.de_mode(),
+                socket,
+                state: Mutex::new(RouterState::new()),
+                received: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Create and bind a socket for `proto` and route requests over it - see
+    /// `NlSocket::connect`
+    pub fn connect(
+        proto: NlFamily,
+        pid: Option<u32>,
+        groups: Option<Vec<u32>>,
+    ) -> Result<Self, io::Error> {
+        Ok(NlRouter::new(NlSocket::connect(proto, pid, groups, false)?))
+    }
+
+    /// Send a request with a newly assigned sequence number and return a handle for its
+    /// response
+    ///
+    /// The response is parsed as messages of type `T` with payloads of type `P`. The kernel runs
+    /// one dump per socket at a time and fails other dump requests with `EBUSY` until it is
+    /// done.
+    pub fn send<ST, SP, T, P>(
+        &self,
+        msg: Nlmsghdr<ST, SP>,
+    ) -> Result<NlRouterReceiverHandle<T, P>, NlError>
+    where
+        ST: Nl + NlType + Debug,
+        SP: Nl + Debug,
+    {
+        let seq = self.shared.state().register();
+        // Dropping the handle if sending fails releases the sequence number
+        let mut handle = NlRouterReceiverHandle {
+            seq,
+            ack: false,
+            finished: false,
+            shared: Arc::clone(&self.shared),
+            type_data: PhantomData,
+            payload_data: PhantomData,
+        };
+        let (mem, ack) = serialize_request(msg, seq)?;
+        handle.ack = ack;
+        self.shared
+            .socket
+            .send(&mem, 0)
+            .map_err(|e| self.shared.socket.io_to_nl_error(e))?;
+        Ok(handle)
+    }
+
+    /// Reference to the underlying socket
+    pub fn get_ref(&self) -> &NlSocket {
+        &self.shared.socket
+    }
+
+    // Whether clones of the router or handles for outstanding requests still use the socket.
+    pub(crate) fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.shared) > 1
+    }
+}
+
+/// Iterator over the response to a request sent with `NlRouter::send`
+///
+/// The iterator ends after the response - at the `NLMSG_DONE` message ending a dump, at the ACK
+/// if one was requested, or after a message without `NLM_F_MULTI` otherwise. `NLMSG_DONE` and
+/// ACKs are not yielded and errors reported by the kernel are yielded as the last item.
+pub struct NlRouterReceiverHandle<T, P> {
+    seq: u32,
+    ack: bool,
+    finished: bool,
+    shared: Arc<Shared>,
+    type_data: PhantomData<T>,
+    payload_data: PhantomData<P>,
+}
+
+impl<T, P> NlRouterReceiverHandle<T, P> {
+    /// Sequence number assigned to the request
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+}
+
+impl<T, P> Iterator for NlRouterReceiverHandle<T, P>
+where
+    T: NlType,
+    P: Nl,
+{
+    type Item = Result<Nlmsghdr<T, P>, NlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let msg = match self.shared.next_msg(self.seq) {
+                Ok(msg) => msg,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Some(item) =
+                parse_response(msg, self.ack, self.shared.de_mode, &mut self.finished)
+            {
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+impl<T, P> Drop for NlRouterReceiverHandle<T, P> {
+    fn drop(&mut self) {
+        self.shared.state().queues.remove(&self.seq);
+    }
+}
+
+#[cfg(feature = "async")]
+pub use self::asynchronous::{AsyncNlRouter, AsyncNlRouterReceiverHandle};
+
+#[cfg(feature = "async")]
+mod asynchronous {
+    use super::*;
+
+    use std::{
+        future::poll_fn,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures_core::Stream;
+
+    use crate::socket::asynchronous::AsyncNlIo;
+
+    struct AsyncShared<S> {
+        io: S,
+        state: Mutex<RouterState>,
+        de_mode: DeMode,
+    }
+
+    impl<S> AsyncShared<S> {
+        fn state(&self) -> MutexGuard<'_, RouterState> {
+            self.state.lock().unwrap_or_else(|e| e.into_inner())
+        }
+    }
+
+    /// Async socket handle sending requests from several tasks and routing responses back to
+    /// them - see `NlRouter`
+    pub struct AsyncNlRouter<S> {
+        shared: Arc<AsyncShared<S>>,
+    }
+
+    impl<S> Clone for AsyncNlRouter<S> {
+        fn clone(&self) -> Self {
+            AsyncNlRouter {
+                shared: Arc::clone(&self.shared),
+            }
+        }
+    }
+
+    impl<S> AsyncNlRouter<S>
+    where
+        S: AsyncNlIo,
+    {
+        /// Route requests and responses over a socket that is registered with a runtime
+        pub fn from_io(io: S) -> Self {
+            AsyncNlRouter {
+                shared: Arc::new(AsyncShared {
+                    de_mode: io.socket().de_mode(),
+                    io,
+                    state: Mutex::new(RouterState::new()),
+                }),
+            }
+        }
+
+        /// Send a request with a newly assigned sequence number and return a `Stream` of its
+        /// response - see `NlRouter::send`
+        pub async fn send<ST, SP, T, P>(
+            &self,
+            msg: Nlmsghdr<ST, SP>,
+        ) -> Result<AsyncNlRouterReceiverHandle<S, T, P>, NlError>
+        where
+            ST: Nl + NlType + Debug,
+            SP: Nl + Debug,
+        {
+            let seq = self.shared.state().register();
+            let mut handle = AsyncNlRouterReceiverHandle {
+                seq,
+                ack: false,
+                finished: false,
+                shared: Arc::clone(&self.shared),
+                type_data: PhantomData,
+                payload_data: PhantomData,
+            };
+            let (mem, ack) = serialize_request(msg, seq)?;
+            handle.ack = ack;
+            poll_fn(|cx| self.shared.io.poll_write_with(cx, |s| s.send(&mem, 0))).await?;
+            Ok(handle)
+        }
+
+        /// Reference to the underlying socket
+        pub fn get_ref(&self) -> &NlSocket {
+            self.shared.io.socket()
+        }
+    }
+
+    /// `Stream` of the response to a request sent with `AsyncNlRouter::send` - see
+    /// `NlRouterReceiverHandle`
+    pub struct AsyncNlRouterReceiverHandle<S, T, P> {
+        seq: u32,
+        ack: bool,
+        finished: bool,
+        shared: Arc<AsyncShared<S>>,
+        type_data: PhantomData<T>,
+        payload_data: PhantomData<P>,
+    }
+
+    impl<S, T, P> AsyncNlRouterReceiverHandle<S, T, P> {
+        /// Sequence number assigned to the request
+        pub fn seq(&self) -> u32 {
+            self.seq
+        }
+    }
+
+    impl<S, T, P> Unpin for AsyncNlRouterReceiverHandle<S, T, P> {}
+
+    impl<S, T, P> Stream for AsyncNlRouterReceiverHandle<S, T, P>
+    where
+        S: AsyncNlIo,
+        T: NlType,
+        P: Nl,
+    {
+        type Item = Result<Nlmsghdr<T, P>, NlError>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+            let this = &mut *self;
+            while !this.finished {
+                let msg = {
+                    let mut state = this.shared.state();
+                    let msg = state.pop(this.seq);
+                    if msg.is_none() {
+                        if let Some(queue) = state.queues.get_mut(&this.seq) {
+                            queue.waker = Some(cx.waker().clone());
+                        }
+                    }
+                    msg
+                };
+                match msg {
+                    Some(msg) => {
+                        let item =
+                            parse_response(msg, this.ack, this.shared.de_mode, &mut this.finished);
+                        if item.is_some() {
+                            // The reactor only wakes the last task that polled the socket
+                            this.shared.state().wake_others(this.seq);
+                            return Poll::Ready(item);
+                        }
+                    }
+                    None => match this.shared.io.poll_read_with(cx, recv_datagram) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(ref d)) if d.is_empty() => {
+                            return Poll::Ready(Some(Err(NlError::new(
+                                "No data could be read from the socket",
+                            ))))
+                        }
+                        Poll::Ready(Ok(d)) => {
+                            if let Err(e) = this.shared.state().route(&d) {
+                                return Poll::Ready(Some(Err(e)));
+                            }
+                        }
+                        Poll::Ready(Err(e)) => {
+                            let err = this.shared.io.socket().recv_to_nl_error(e);
+                            return Poll::Ready(Some(Err(err)));
+                        }
+                    },
+                }
+            }
+            Poll::Ready(None)
+        }
+    }
+
+    impl<S, T, P> Drop for AsyncNlRouterReceiverHandle<S, T, P> {
+        fn drop(&mut self) {
+            let mut state = self.shared.state();
+            state.queues.remove(&self.seq);
+            state.wake_others(self.seq);
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+pub mod tokio {
+    //! Tokio-specific router
+
+    use super::*;
+
+    use ::tokio::io::unix::AsyncFd;
+
+    /// Tokio-enabled router
+    pub type NlRouter = AsyncNlRouter<AsyncFd<NlSocket>>;
+
+    impl NlRouter {
+        /// Set `socket` to nonblocking state, register it with the reactor of the current runtime
+        /// and route requests over it
+        pub fn new(socket: NlSocket) -> io::Result<Self> {
+            Ok(AsyncNlRouter::from_io(crate::socket::tokio::register(
+                socket,
+            )?))
+        }
+    }
+}
+
+#[cfg(feature = "async-io")]
+pub mod async_io {
+    //! async-io specific router, for use with async-std and smol
+
+    use super::*;
+
+    use ::async_io::Async;
+
+    /// async-io enabled router
+    pub type NlRouter = AsyncNlRouter<Async<NlSocket>>;
+
+    impl NlRouter {
+        /// Set `socket` to nonblocking state, register it with the reactor and route requests
+        /// over it
+        pub fn new(socket: NlSocket) -> io::Result<Self> {
+            Ok(AsyncNlRouter::from_io(Async::new(socket)?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{
+        mem::{size_of, zeroed},
+        os::unix::io::AsRawFd,
+        time::Duration,
+    };
+
+    use crate::{
+        consts::{RtAddrFamily, Rtm},
+        nl::NlEmpty,
+        rtnl::Ifinfomsg,
+    };
+
+    fn noop() -> Nlmsghdr<Nlmsg, NlEmpty> {
+        Nlmsghdr::new(
+            None,
+            Nlmsg::Noop,
+            vec![NlmF::Request, NlmF::Ack],
+            None,
+            None,
+            NlEmpty,
+        )
+    }
+
+    fn getlink() -> Nlmsghdr<Rtm, Ifinfomsg> {
+        Nlmsghdr::new(
+            None,
+            Rtm::Getlink,
+            vec![NlmF::Request, NlmF::Dump],
+            None,
+            None,
+            Ifinfomsg::dump(RtAddrFamily::Unspecified),
+        )
+    }
+
+    #[test]
+    fn test_router_interleaved() {
+        let router = NlRouter::connect(NlFamily::Route, None, None).unwrap();
+        let links = router.send::<_, _, Rtm, Ifinfomsg>(getlink()).unwrap();
+        let ack = router.send::<_, _, Nlmsg, NlEmpty>(noop()).unwrap();
+        assert_ne!(links.seq(), ack.seq());
+
+        // The ACK arrives after the dump and is found while the dump is queued for the other
+        // handle
+        assert_eq!(ack.count(), 0);
+        let links = links.collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(!links.is_empty());
+        assert!(links.iter().all(|l| l.nl_type == Rtm::Newlink));
+    }
+
+    #[test]
+    fn test_router_error() {
+        let router = NlRouter::connect(NlFamily::Route, None, None).unwrap();
+        let mut request = getlink();
+        request.nl_type = Rtm::from(0xffff);
+        request.nl_flags = vec![NlmF::Request, NlmF::Ack];
+        let mut handle = router.send::<_, _, Rtm, Ifinfomsg>(request).unwrap();
+        match handle.next() {
+            Some(Err(NlError::Errno(_))) => (),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+        assert!(handle.next().is_none());
+    }
+
+    #[test]
+    fn test_router_timeout() {
+        // Send requests to a peer that never responds
+        let (mut socket, _peer) = connected_to_peer();
+        socket
+            .set_recv_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let router = NlRouter::new(socket);
+        let mut handle = router.send::<_, _, Nlmsg, NlEmpty>(noop()).unwrap();
+        match handle.next() {
+            Some(Err(NlError::Timeout)) => (),
+            r => panic!("Expected a timeout, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_router_malformed_length() {
+        let (socket, peer) = connected_to_peer();
+        let port = socket.port_id().unwrap();
+        let router = NlRouter::new(socket);
+        let mut handle = router.send::<_, _, Nlmsg, NlEmpty>(noop()).unwrap();
+
+        // The header claims a payload that the datagram does not have
+        let mut mem = Vec::new();
+        Nlmsghdr::new(None, Nlmsg::Noop, vec![], Some(handle.seq()), None, NlEmpty)
+            .serialize(&mut StreamWriteBuffer::new_growable_ref(&mut mem))
+            .unwrap();
+        mem[..4].copy_from_slice(&32u32.to_ne_bytes());
+        peer.send_to(&mem, port, 0, 0).unwrap();
+        match handle.next() {
+            Some(Err(NlError::Msg(_))) => (),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+    }
+
+    // Socket whose requests are sent to the returned peer socket instead of the kernel
+    fn connected_to_peer() -> (NlSocket, NlSocket) {
+        let socket = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let peer = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let mut addr = unsafe { zeroed::<libc::sockaddr_nl>() };
+        addr.nl_family = libc::AF_NETLINK as u16;
+        addr.nl_pid = peer.port_id().unwrap();
+        assert_eq!(
+            unsafe {
+                libc::connect(
+                    socket.as_raw_fd(),
+                    &addr as *const _ as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+                )
+            },
+            0
+        );
+        (socket, peer)
+    }
+
+    #[test]
+    fn test_router_threads() {
+        let router = NlRouter::connect(NlFamily::Route, None, None).unwrap();
+        // The kernel runs one dump per socket at a time so only one thread dumps
+        let threads = (0..4)
+            .map(|i| {
+                let router = router.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        if i == 0 {
+                            let links = router
+                                .send::<_, _, Rtm, Ifinfomsg>(getlink())
+                                .unwrap()
+                                .collect::<Result<Vec<_>, _>>()
+                                .unwrap();
+                            assert!(!links.is_empty());
+                        }
+                        let acks = router.send::<_, _, Nlmsg, NlEmpty>(noop()).unwrap();
+                        assert_eq!(acks.count(), 0);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(router.shared.state().queues.is_empty());
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn test_router_tokio() {
+        use futures::TryStreamExt;
+
+        let rt = ::tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let socket = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+            let router = tokio::NlRouter::new(socket).unwrap();
+            let links = router
+                .send::<_, _, Rtm, Ifinfomsg>(getlink())
+                .await
+                .unwrap();
+            let acks = router.send::<_, _, Nlmsg, NlEmpty>(noop()).await.unwrap();
+            let (links, acks) =
+                futures::join!(links.try_collect::<Vec<_>>(), acks.try_collect::<Vec<_>>());
+            assert!(!links.unwrap().is_empty());
+            assert!(acks.unwrap().is_empty());
+        });
+    }
+
+    #[cfg(feature = "async-io")]
+    #[test]
+    fn test_router_async_io() {
+        use futures::TryStreamExt;
+
+        ::async_io::block_on(async {
+            let socket = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+            let router = async_io::NlRouter::new(socket).unwrap();
+            let acks = router.send::<_, _, Nlmsg, NlEmpty>(noop()).await.unwrap();
+            let links = router
+                .send::<_, _, Rtm, Ifinfomsg>(getlink())
+                .await
+                .unwrap();
+            assert!(acks.try_collect::<Vec<_>>().await.unwrap().is_empty());
+            assert!(!links.try_collect::<Vec<_>>().await.unwrap().is_empty());
+        });
+    }
+}
@@ -7,18 +7,24 @@
 //! series of structs organized in a style similar to the rest of the library with implementations
 //! of `Nl` for each.
 
-use std::mem;
+use std::{convert::TryFrom, fmt, mem};
 
 use buffering::{StreamReadBuffer, StreamWriteBuffer};
 
 use crate::{
-    consts::rtnl::*,
+    consts::{alignto, needs_64bit_pad, rtnl::*},
     err::{DeError, SerError},
+    nl::NlEmpty,
+    nlattr::{deserialize_attr_payload, deserialize_payload, TypedAttr},
+    types::MacAddress,
     Nl,
 };
 
+const RTA_HDRLEN: usize = 4;
+
 /// Set of `Rtattr` structs
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rtattrs<T, P>(Vec<Rtattr<T, P>>);
 
 impl<T, P> Rtattrs<T, P>
@@ -37,9 +43,60 @@ where
     }
 
     /// Return a reference iterator over underlying vector
-    pub fn iter(&self) -> std::slice::Iter<Rtattr<T, P>> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Rtattr<T, P>> {
         self.0.iter()
     }
+
+    /// Return a reference iterator that skips pad attributes of type `pad_type` such as
+    /// `Tca::Pad`
+    pub fn iter_without_pad(&self, pad_type: T) -> impl Iterator<Item = &Rtattr<T, P>> {
+        self.0.iter().filter(move |attr| attr.rta_type != pad_type)
+    }
+
+    /// Append an attribute to the set
+    pub fn push(&mut self, attr: Rtattr<T, P>) {
+        self.0.push(attr)
+    }
+
+    /// Get the underlying `Vec` as a mutable reference to change, add, or remove attributes
+    pub fn get_vec_mut(&mut self) -> &mut Vec<Rtattr<T, P>> {
+        &mut self.0
+    }
+
+    /// Get the first attribute of the given type
+    pub fn get_attribute(&self, attr_type: T) -> Option<&Rtattr<T, P>> {
+        self.0.iter().find(|rtattr| rtattr.rta_type == attr_type)
+    }
+
+    /// Iterate over all attributes of the given type in the order they appear - for attributes
+    /// that can legitimately repeat
+    pub fn get_attributes(&self, attr_type: T) -> impl Iterator<Item = &Rtattr<T, P>> {
+        self.0
+            .iter()
+            .filter(move |rtattr| rtattr.rta_type == attr_type)
+    }
+
+    /// Mutably get the first attribute of the given type
+    pub fn get_attribute_mut(&mut self, attr_type: T) -> Option<&mut Rtattr<T, P>> {
+        self.0
+            .iter_mut()
+            .find(|rtattr| rtattr.rta_type == attr_type)
+    }
+
+    /// Check whether an attribute of the given type is present
+    pub fn has_attribute(&self, attr_type: T) -> bool {
+        self.get_attribute(attr_type).is_some()
+    }
+
+    /// Number of attributes in the set
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl<T, P> IntoIterator for Rtattrs<T, P>
@@ -59,21 +116,61 @@ impl<T> Rtattrs<T, Vec<u8>>
 where
     T: RtaType,
 {
+    /// Parse the attributes nested in an attribute contained in the set
+    ///
+    /// The returned set owns the parsed attributes so calls can be chained to descend through
+    /// multiple levels of nesting, for example from `Ifla::Linkinfo` to `IflaInfo::Data`.
+    pub fn get_nested_attributes<S>(
+        &self,
+        attr_type: T,
+    ) -> Result<Option<Rtattrs<S, Vec<u8>>>, DeError>
+    where
+        S: RtaType,
+    {
+        match self.get_attribute(attr_type) {
+            Some(e) => e.get_nested_attributes().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Append an attribute with a 64-bit payload, preceded by an empty attribute of type
+    /// `pad_type` if needed so that the payload is 8 byte aligned the way the kernel expects
+    ///
+    /// `offset` is the position of this set of attributes relative to the start of the netlink
+    /// message - for example `16` for the netlink header plus the size of the family header.
+    pub fn push_64bit<P>(
+        &mut self,
+        offset: usize,
+        pad_type: T,
+        rta_type: T,
+        payload: P,
+    ) -> Result<(), SerError>
+    where
+        P: Nl,
+    {
+        if needs_64bit_pad(offset + self.asize()) {
+            self.0.push(Rtattr::new(pad_type, NlEmpty)?);
+        }
+        self.0.push(Rtattr::new(rta_type, payload)?);
+        Ok(())
+    }
+
+    /// Get the payload of an attribute as the type associated with it by `TypedAttr` or `None` if
+    /// the attribute is not present
+    pub fn get<A>(&self) -> Result<Option<A::Payload>, DeError>
+    where
+        A: TypedAttr<Type = T>,
+    {
+        self.get_attr_payload_as(A::attr_type())
+    }
+
     /// Get an attribute contained in the set as type `R`
     pub fn get_attr_payload_as<R>(&self, attr_type: T) -> Result<Option<R>, DeError>
     where
         R: Nl,
     {
-        let index = self
-            .0
-            .iter()
-            .position(|rtattr| rtattr.rta_type == attr_type);
-        let elem = match index {
-            Some(i) => self.0.get(i),
-            None => return Ok(None),
-        };
-        match elem {
-            Some(ref e) => e.get_payload_as::<R>().map(Some),
+        match self.get_attribute(attr_type) {
+            Some(e) => e.get_payload_as::<R>().map(Some),
             None => Ok(None),
         }
     }
@@ -101,13 +198,15 @@ where
         let mut vec = Vec::new();
         while size_hint > 0 {
             let attr: Rtattr<T, P> = Rtattr::deserialize(buf)?;
-            size_hint = size_hint.checked_sub(attr.asize()).ok_or_else(|| {
-                DeError::new(&format!(
-                    "Rtattr size {} overflowed buffer size {}",
-                    attr.size(),
-                    size_hint
-                ))
-            })?;
+            size_hint = size_hint
+                .checked_sub(alignto(attr.rta_len as usize))
+                .ok_or_else(|| {
+                    DeError::new(&format!(
+                        "Rtattr size {} overflowed buffer size {}",
+                        attr.size(),
+                        size_hint
+                    ))
+                })?;
             vec.push(attr);
         }
         Ok(Rtattrs::new(vec))
@@ -116,10 +215,15 @@ where
     fn size(&self) -> usize {
         self.0.iter().fold(0, |acc, item| acc + item.asize())
     }
+
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.0.iter().try_for_each(|attr| attr.check_attr_types())
+    }
 }
 
 /// Struct representing interface information messages
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ifinfomsg {
     /// Interface address family
     pub ifi_family: RtAddrFamily,
@@ -155,6 +259,19 @@ impl Ifinfomsg {
         }
     }
 
+    /// Request for a dump of all links in `ifi_family` with every other header field zeroed so
+    /// that it is accepted by sockets with `NlSocket::set_strict_chk` enabled
+    pub fn dump(ifi_family: RtAddrFamily) -> Self {
+        Ifinfomsg {
+            ifi_family,
+            ifi_type: Arphrd::from(0),
+            ifi_index: 0,
+            ifi_flags: Vec::new(),
+            ifi_change: Iff::from(0),
+            rtattrs: Rtattrs::empty(),
+        }
+    }
+
     /// Set the link with the given index up (equivalent to `ip link set dev DEV up`)
     pub fn up(
         ifi_family: RtAddrFamily,
@@ -188,6 +305,50 @@ impl Ifinfomsg {
             rtattrs,
         }
     }
+
+    /// Name of the link from `Ifla::Ifname`
+    pub fn name(&self) -> Result<Option<String>, DeError> {
+        self.rtattrs.get::<IflaIfname>()
+    }
+
+    /// MTU of the link from `Ifla::Mtu`
+    pub fn mtu(&self) -> Result<Option<u32>, DeError> {
+        self.rtattrs.get::<IflaMtu>()
+    }
+
+    /// Hardware address of the link from `Ifla::Address`
+    ///
+    /// Returns an error for links with hardware addresses that are not 6 bytes long.
+    pub fn address(&self) -> Result<Option<MacAddress>, DeError> {
+        self.rtattrs.get::<IflaAddress>()
+    }
+
+    /// Operational state of the link from `Ifla::Operstate`
+    pub fn operstate(&self) -> Result<Option<IfOper>, DeError> {
+        self.rtattrs.get::<IflaOperstate>()
+    }
+
+    /// Index of the master device of the link, such as a bridge or bond, from `Ifla::Master`
+    pub fn master(&self) -> Result<Option<u32>, DeError> {
+        self.rtattrs.get::<IflaMaster>()
+    }
+
+    /// Index of the lower device of the link, such as the parent of a VLAN, from `Ifla::Link`
+    pub fn link(&self) -> Result<Option<u32>, DeError> {
+        self.rtattrs.get::<IflaLink>()
+    }
+
+    /// Statistics of the link from `Ifla::Stats64`, or from the 32 bit counters of
+    /// `Ifla::Stats` if the 64 bit counters are missing
+    pub fn stats(&self) -> Result<Option<LinkStats64>, DeError> {
+        if let Some(stats) = self.rtattrs.get_attribute(Ifla::Stats64) {
+            return LinkStats64::from_counters(&stats.rta_payload, mem::size_of::<u64>()).map(Some);
+        }
+        self.rtattrs
+            .get_attribute(Ifla::Stats)
+            .map(|stats| LinkStats64::from_counters(&stats.rta_payload, mem::size_of::<u32>()))
+            .transpose()
+    }
 }
 
 impl Nl for Ifinfomsg {
@@ -265,10 +426,15 @@ impl Nl for Ifinfomsg {
         self.ifi_change.size() +
         self.rtattrs.asize()
     }
+
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.rtattrs.check_attr_types()
+    }
 }
 
 /// Struct representing interface address messages
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ifaddrmsg {
     /// Interface address family
     pub ifa_family: RtAddrFamily,
@@ -340,11 +506,36 @@ impl Nl for Ifaddrmsg {
             + mem::size_of::<libc::c_uchar>()
             + self.ifa_scope.size()
             + self.ifa_index.size()
+            + self.rtattrs.asize()
+    }
+
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.rtattrs.check_attr_types()
+    }
+}
+
+impl Ifaddrmsg {
+    /// Request for a dump of all addresses in `ifa_family` with every other header field zeroed
+    /// so that it is accepted by sockets with `NlSocket::set_strict_chk` enabled
+    pub fn dump(ifa_family: RtAddrFamily) -> Self {
+        Ifaddrmsg {
+            ifa_family,
+            ifa_prefixlen: 0,
+            ifa_flags: Vec::new(),
+            ifa_scope: 0,
+            ifa_index: 0,
+            rtattrs: Rtattrs::empty(),
+        }
     }
 }
 
 /// General form of address family dependent message.  Used for requesting things from via rtnetlink.
+///
+/// Sockets with `NlSocket::set_strict_chk` enabled reject dump requests that do not contain the
+/// full header for the message type, so use `Ifinfomsg::dump`, `Ifaddrmsg::dump`,
+/// `Rtmsg::dump`, `Ndmsg::dump`, or `Tcmsg::dump` instead.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rtgenmsg {
     /// Address family for the request
     pub rtgen_family: RtAddrFamily,
@@ -371,6 +562,7 @@ impl Nl for Rtgenmsg {
 
 /// Route message
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rtmsg {
     /// Address family of route
     pub rtm_family: RtAddrFamily,
@@ -394,6 +586,36 @@ pub struct Rtmsg {
     pub rtattrs: Rtattrs<Rta, Vec<u8>>,
 }
 
+impl Rtmsg {
+    /// Request for a dump of all routes in `rtm_family` with every other header field zeroed so
+    /// that it is accepted by sockets with `NlSocket::set_strict_chk` enabled
+    pub fn dump(rtm_family: RtAddrFamily) -> Self {
+        Rtmsg {
+            rtm_family,
+            rtm_dst_len: 0,
+            rtm_src_len: 0,
+            rtm_tos: 0,
+            rtm_table: RtTable::Unspec,
+            rtm_protocol: Rtprot::Unspec,
+            rtm_scope: RtScope::Universe,
+            rtm_type: Rtn::Unspec,
+            rtm_flags: Vec::new(),
+            rtattrs: Rtattrs::empty(),
+        }
+    }
+
+    /// ID of the routing table of the route
+    ///
+    /// Taken from the `Rta::Table` attribute if present as `rtm_table` is `RtTable::Compat` for
+    /// tables with IDs above 255.
+    pub fn table(&self) -> Result<u32, DeError> {
+        Ok(self
+            .rtattrs
+            .get::<RtaTable>()?
+            .unwrap_or_else(|| u32::from(self.rtm_table.clone())))
+    }
+}
+
 impl Nl for Rtmsg {
     fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
         self.rtm_family.serialize(buf)?;
@@ -483,10 +705,15 @@ impl Nl for Rtmsg {
             + mem::size_of::<libc::c_uint>()
             + self.rtattrs.asize()
     }
+
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.rtattrs.check_attr_types()
+    }
 }
 
 /// Represents an ARP (neighbor table) entry
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ndmsg {
     /// Address family of entry
     pub ndm_family: RtAddrFamily,
@@ -502,6 +729,21 @@ pub struct Ndmsg {
     pub rtattrs: Rtattrs<Nda, Vec<u8>>,
 }
 
+impl Ndmsg {
+    /// Request for a dump of all neighbor table entries in `ndm_family` with every other header
+    /// field zeroed so that it is accepted by sockets with `NlSocket::set_strict_chk` enabled
+    pub fn dump(ndm_family: RtAddrFamily) -> Self {
+        Ndmsg {
+            ndm_family,
+            ndm_index: 0,
+            ndm_state: Vec::new(),
+            ndm_flags: Vec::new(),
+            ndm_type: Rtn::Unspec,
+            rtattrs: Rtattrs::empty(),
+        }
+    }
+}
+
 impl Nl for Ndmsg {
     fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
         self.ndm_family.serialize(buf)?;
@@ -545,7 +787,7 @@ impl Nl for Ndmsg {
             for i in 0..mem::size_of::<u16>() * 8 {
                 let bit = 1 << i;
                 if bit & state == bit {
-                    ndm_state.push((bit as u16).into());
+                    ndm_state.push(bit.into());
                 }
             }
             ndm_state
@@ -556,7 +798,7 @@ impl Nl for Ndmsg {
             for i in 0..mem::size_of::<u8>() * 8 {
                 let bit = 1 << i;
                 if bit & flags == bit {
-                    ndm_flags.push((bit as u8).into());
+                    ndm_flags.push(bit.into());
                 }
             }
             ndm_flags
@@ -594,10 +836,15 @@ impl Nl for Ndmsg {
             + self.ndm_type.size()
             + self.rtattrs.asize()
     }
+
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.rtattrs.check_attr_types()
+    }
 }
 
 /// Struct representing ARP cache info
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NdaCacheinfo {
     /// Confirmed
     pub ndm_confirmed: u32,
@@ -638,8 +885,179 @@ impl Nl for NdaCacheinfo {
     }
 }
 
+macro_rules! impl_link_stats {
+    ( $( $( #[$doc:meta] )* $field:ident ),* ) => {
+        /// Interface statistics, `struct rtnl_link_stats64`, from `Ifla::Stats64` or widened
+        /// from `Ifla::Stats` - see `Ifinfomsg::stats`
+        ///
+        /// Counters added to the struct by kernels newer than the running kernel are 0 and
+        /// counters added by kernels newer than this crate are ignored.
+        #[derive(Clone, Debug, Default, PartialEq, Eq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct LinkStats64 {
+            $(
+                $( #[$doc] )*
+                pub $field: u64,
+            )*
+        }
+
+        impl LinkStats64 {
+            const COUNTERS: usize = [$( stringify!($field) ),*].len();
+
+            // Parse a payload of native endian counters that are `width` bytes long
+            fn from_counters(payload: &[u8], width: usize) -> Result<Self, DeError> {
+                if payload.len() % width != 0 {
+                    return Err(DeError::new(&format!(
+                        "Link statistics of {} bytes are not made of {} byte counters",
+                        payload.len(),
+                        width
+                    )));
+                }
+                let mut counters = payload.chunks_exact(width).map(|counter| {
+                    match <[u8; 8]>::try_from(counter) {
+                        Ok(counter) => u64::from_ne_bytes(counter),
+                        Err(_) => u64::from(u32::from_ne_bytes(
+                            <[u8; 4]>::try_from(counter).unwrap_or_default(),
+                        )),
+                    }
+                });
+                Ok(LinkStats64 {
+                    $( $field: counters.next().unwrap_or(0), )*
+                })
+            }
+        }
+
+        impl Nl for LinkStats64 {
+            fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
+                $( self.$field.serialize(buf)?; )*
+                Ok(())
+            }
+
+            fn deserialize<B>(buf: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+            where
+                B: AsRef<[u8]>,
+            {
+                let payload = Vec::<u8>::deserialize(buf)?;
+                LinkStats64::from_counters(&payload, mem::size_of::<u64>())
+            }
+
+            fn size(&self) -> usize {
+                LinkStats64::COUNTERS * mem::size_of::<u64>()
+            }
+        }
+    };
+}
+
+impl_link_stats!(
+    /// Packets received
+    rx_packets,
+    /// Packets transmitted
+    tx_packets,
+    /// Bytes received
+    rx_bytes,
+    /// Bytes transmitted
+    tx_bytes,
+    /// Bad packets received
+    rx_errors,
+    /// Packet transmit problems
+    tx_errors,
+    /// Packets received but dropped, for example for lack of buffer space
+    rx_dropped,
+    /// Packets dropped on transmission, for example for lack of buffer space
+    tx_dropped,
+    /// Multicast packets received
+    multicast,
+    /// Collisions on transmission
+    collisions,
+    /// Packets received with a bad length
+    rx_length_errors,
+    /// Receive ring buffer overflows
+    rx_over_errors,
+    /// Packets received with a CRC error
+    rx_crc_errors,
+    /// Packets received with a frame alignment error
+    rx_frame_errors,
+    /// Receive FIFO overruns
+    rx_fifo_errors,
+    /// Packets missed by the host
+    rx_missed_errors,
+    /// Transmissions aborted
+    tx_aborted_errors,
+    /// Transmissions failed because of carrier errors
+    tx_carrier_errors,
+    /// Transmit FIFO underruns
+    tx_fifo_errors,
+    /// Heartbeat errors
+    tx_heartbeat_errors,
+    /// Late collisions
+    tx_window_errors,
+    /// Compressed packets received
+    rx_compressed,
+    /// Compressed packets transmitted
+    tx_compressed,
+    /// Packets received and dropped because no protocol handles them
+    rx_nohandler,
+    /// Packets received and dropped because they were addressed to another host
+    rx_otherhost_dropped
+);
+
+/// Traffic control handle as used in `tcm_handle` and `tcm_parent` of `Tcmsg`
+///
+/// The major number is stored in the upper 16 bits and the minor number in the lower 16 bits,
+/// written `major:minor` in hexadecimal by `tc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TcHandle(pub u32);
+
+impl TcHandle {
+    /// Handle that is not set - `TC_H_UNSPEC`
+    pub const UNSPEC: TcHandle = TcHandle(0);
+    /// Parent of the root queuing discipline of an interface - `TC_H_ROOT`
+    pub const ROOT: TcHandle = TcHandle(0xFFFF_FFFF);
+    /// Parent of the ingress queuing discipline of an interface - `TC_H_INGRESS`
+    pub const INGRESS: TcHandle = TcHandle(0xFFFF_FFF1);
+
+    /// Create a handle from its major and minor numbers
+    pub fn new(major: u16, minor: u16) -> Self {
+        TcHandle(u32::from(major) << 16 | u32::from(minor))
+    }
+
+    /// Major number of the handle, identifying a queuing discipline
+    pub fn major(self) -> u16 {
+        (self.0 >> 16) as u16
+    }
+
+    /// Minor number of the handle, identifying a class of the queuing discipline
+    pub fn minor(self) -> u16 {
+        self.0 as u16
+    }
+}
+
+impl From<u32> for TcHandle {
+    fn from(handle: u32) -> Self {
+        TcHandle(handle)
+    }
+}
+
+impl From<TcHandle> for u32 {
+    fn from(handle: TcHandle) -> Self {
+        handle.0
+    }
+}
+
+impl fmt::Display for TcHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TcHandle::ROOT => write!(f, "root"),
+            TcHandle::INGRESS => write!(f, "ingress"),
+            handle => write!(f, "{:x}:{:x}", handle.major(), handle.minor()),
+        }
+    }
+}
+
 /// Message in response to queuing discipline operations
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tcmsg {
     /// Family
     pub tcm_family: libc::c_uchar,
@@ -655,6 +1073,39 @@ pub struct Tcmsg {
     pub rtattrs: Rtattrs<Tca, Vec<u8>>,
 }
 
+impl Tcmsg {
+    /// Request for a dump of the traffic control objects of the interface with index
+    /// `tcm_ifindex`, or of every interface with `0` for queuing disciplines
+    ///
+    /// Dumps of classes and filters require an interface index and filters are dumped for the
+    /// queuing discipline or class set in `tcm_parent`.
+    pub fn dump(tcm_ifindex: libc::c_int) -> Self {
+        Tcmsg {
+            tcm_family: libc::AF_UNSPEC as libc::c_uchar,
+            tcm_ifindex,
+            tcm_handle: 0,
+            tcm_parent: 0,
+            tcm_info: 0,
+            rtattrs: Rtattrs::empty(),
+        }
+    }
+
+    /// Handle of the queuing discipline, class, or filter
+    pub fn handle(&self) -> TcHandle {
+        TcHandle(self.tcm_handle)
+    }
+
+    /// Handle of the parent queuing discipline or class
+    pub fn parent(&self) -> TcHandle {
+        TcHandle(self.tcm_parent)
+    }
+
+    /// Kind of the traffic control object such as `"noqueue"` or `"htb"`
+    pub fn kind(&self) -> Result<Option<String>, DeError> {
+        self.rtattrs.get::<TcaKind>()
+    }
+}
+
 impl Nl for Tcmsg {
     fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
         self.tcm_family.serialize(buf)?;
@@ -716,13 +1167,84 @@ impl Nl for Tcmsg {
             + self.tcm_handle.size()
             + self.tcm_parent.size()
             + self.tcm_info.size()
+            + self.rtattrs.asize()
+    }
+
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.rtattrs.check_attr_types()
+    }
+}
+
+/// Builder for route attributes containing nested attributes, created with `Rtattr::nested`
+///
+/// The first error encountered while adding attributes is returned from `build`.
+pub struct RtattrBuilder<T> {
+    attr: Rtattr<T, Vec<u8>>,
+    err: Option<SerError>,
+}
+
+impl<T> RtattrBuilder<T>
+where
+    T: RtaType,
+{
+    /// Add an already constructed nested attribute
+    pub fn push_attr<TT, P>(mut self, attr: Rtattr<TT, P>) -> Self
+    where
+        TT: RtaType,
+        P: Nl,
+    {
+        if self.err.is_none() {
+            if let Err(e) = self.attr.add_nested_attribute(&attr) {
+                self.err = Some(e);
+            }
+        }
+        self
+    }
+
+    /// Add a nested attribute with a payload of any type that implements `Nl`
+    pub fn attr<TT, P>(self, rta_type: TT, payload: P) -> Self
+    where
+        TT: RtaType,
+        P: Nl,
+    {
+        match Rtattr::new(rta_type, payload) {
+            Ok(attr) => self.push_attr(attr),
+            Err(e) => self.fail(e),
+        }
+    }
+
+    /// Add a nested attribute that itself contains nested attributes
+    pub fn nested<TT>(self, builder: RtattrBuilder<TT>) -> Self
+    where
+        TT: RtaType,
+    {
+        match builder.build() {
+            Ok(attr) => self.push_attr(attr),
+            Err(e) => self.fail(e),
+        }
+    }
+
+    fn fail(mut self, e: SerError) -> Self {
+        if self.err.is_none() {
+            self.err = Some(e);
+        }
+        self
+    }
+
+    /// Build the attribute or return the first error encountered while adding attributes
+    pub fn build(self) -> Result<Rtattr<T, Vec<u8>>, SerError> {
+        match self.err {
+            Some(e) => Err(e),
+            None => Ok(self.attr),
+        }
     }
 }
 
 /// Struct representing route netlink attributes
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rtattr<T, P> {
-    /// Length of the attribute
+    /// Length of the attribute - recomputed from the payload when serializing
     pub rta_len: libc::c_ushort,
     /// Type of the attribute
     pub rta_type: T,
@@ -745,12 +1267,78 @@ impl<T> Rtattr<T, Vec<u8>>
 where
     T: RtaType,
 {
-    /// Get payload as type implementing `Nl`
+    /// Create an attribute by serializing the provided payload
+    pub fn new<P>(rta_type: T, payload: P) -> Result<Self, SerError>
+    where
+        P: Nl,
+    {
+        let mut attr = Rtattr {
+            rta_len: 0,
+            rta_type,
+            rta_payload: Vec::new(),
+        };
+        attr.set_payload(payload)?;
+        Ok(attr)
+    }
+
+    /// Start building an attribute that contains nested attributes
+    pub fn nested(rta_type: T) -> RtattrBuilder<T> {
+        RtattrBuilder {
+            attr: Rtattr {
+                rta_len: RTA_HDRLEN as libc::c_ushort,
+                rta_type,
+                rta_payload: Vec::new(),
+            },
+            err: None,
+        }
+    }
+
+    /// Set the payload to a data type that implements `Nl` -
+    /// this function will overwrite the current payload
+    pub fn set_payload<P>(&mut self, payload: P) -> Result<(), SerError>
+    where
+        P: Nl,
+    {
+        let mut rta_payload = Vec::with_capacity(payload.size());
+        payload.serialize(&mut StreamWriteBuffer::new_growable_ref(&mut rta_payload))?;
+        self.rta_len = libc::c_ushort::try_from(RTA_HDRLEN + rta_payload.len())
+            .map_err(|_| SerError::new("Attribute is too long to fit in an attribute header"))?;
+        self.rta_payload = rta_payload;
+        Ok(())
+    }
+
+    /// Add a nested attribute to the end of the payload
+    pub fn add_nested_attribute<TT, P>(&mut self, attr: &Rtattr<TT, P>) -> Result<(), SerError>
+    where
+        TT: RtaType,
+        P: Nl,
+    {
+        let init_position = self.rta_payload.len();
+        let mut buffer = StreamWriteBuffer::new_growable_ref(&mut self.rta_payload);
+        buffer.set_position(init_position as u64);
+        attr.serialize(&mut buffer)?;
+        self.rta_len = libc::c_ushort::try_from(RTA_HDRLEN + self.rta_payload.len())
+            .map_err(|_| SerError::new("Attribute is too long to fit in an attribute header"))?;
+        Ok(())
+    }
+
+    /// Get payload as type implementing `Nl`, checking the payload length the same way as
+    /// `Nlattr::get_payload_as`
     pub fn get_payload_as<R>(&self) -> Result<R, DeError>
     where
         R: Nl,
     {
-        R::deserialize(&mut StreamReadBuffer::new(&self.rta_payload))
+        deserialize_payload(&self.rta_payload)
+    }
+
+    /// Parse the payload as a set of nested attributes
+    pub fn get_nested_attributes<S>(&self) -> Result<Rtattrs<S, Vec<u8>>, DeError>
+    where
+        S: RtaType,
+    {
+        let mut buf = StreamReadBuffer::new(&self.rta_payload);
+        buf.set_size_hint(self.rta_payload.len());
+        Rtattrs::deserialize(&mut buf)
     }
 }
 
@@ -760,7 +1348,9 @@ where
     P: Nl,
 {
     fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        self.rta_len.serialize(buf)?;
+        let rta_len = libc::c_ushort::try_from(self.size())
+            .map_err(|_| SerError::new("Attribute is too long to fit in an attribute header"))?;
+        rta_len.serialize(buf)?;
         self.rta_type.serialize(buf)?;
         self.rta_payload.serialize(buf)?;
         self.pad(buf)?;
@@ -773,32 +1363,27 @@ where
     {
         let rta_len = libc::c_ushort::deserialize(buf)?;
         let rta_type = T::deserialize(buf)?;
-        buf.set_size_hint(
-            (rta_len as usize)
-                .checked_sub(rta_len.size() + rta_type.size())
-                .ok_or_else(|| {
-                    DeError::new(&format!("Invalid size while reading Rtattr: {}", rta_len))
-                })?,
-        );
-        let rta_payload = P::deserialize(buf)?;
-        let rtattr = Rtattr {
+        let rta_payload = deserialize_attr_payload(buf, rta_len as usize)?;
+        Ok(Rtattr {
             rta_len,
             rta_type,
             rta_payload,
-        };
-        rtattr.strip(buf)?;
-        Ok(rtattr)
+        })
     }
 
     fn size(&self) -> usize {
         self.rta_len.size() + self.rta_type.size() + self.rta_payload.size()
     }
+
+    fn check_attr_types(&self) -> Result<(), DeError> {
+        self.rta_type.check_attr_types()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::consts::Rta;
+    use crate::consts::{Ifla, IflaInfo, IflaVlan, Rta};
 
     #[test]
     fn test_rta_deserialize() {
@@ -806,6 +1391,112 @@ mod test {
         assert!(Rtattr::<Rta, Vec<u8>>::deserialize(&mut buf).is_ok());
     }
 
+    #[test]
+    fn test_dump_header_sizes() {
+        let mut ifaddrmsg = Ifaddrmsg::dump(RtAddrFamily::Inet);
+        assert_eq!(ifaddrmsg.size(), 8);
+        ifaddrmsg
+            .rtattrs
+            .push(Rtattr::new(Ifa::Label, "lo".to_string()).unwrap());
+        assert_eq!(ifaddrmsg.size(), 16);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        ifaddrmsg.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref().len(), ifaddrmsg.size());
+
+        assert_eq!(Ifinfomsg::dump(RtAddrFamily::Unspecified).size(), 16);
+        assert_eq!(Rtmsg::dump(RtAddrFamily::Inet).size(), 12);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        Rtmsg::dump(RtAddrFamily::Inet).serialize(&mut mem).unwrap();
+        assert_eq!(
+            mem.as_ref(),
+            &[libc::AF_INET as u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(Ndmsg::dump(RtAddrFamily::Inet).size(), 12);
+    }
+
+    #[test]
+    fn test_rtmsg_table() {
+        let mut rtmsg = Rtmsg::dump(RtAddrFamily::Inet);
+        rtmsg.rtm_table = RtTable::Main;
+        assert_eq!(rtmsg.table().unwrap(), libc::RT_TABLE_MAIN as u32);
+
+        rtmsg.rtm_table = RtTable::Compat;
+        rtmsg
+            .rtattrs
+            .push(Rtattr::new(Rta::Table, 1000u32).unwrap());
+        assert_eq!(rtmsg.table().unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_tc_handle() {
+        let handle = TcHandle::new(1, 0x10);
+        assert_eq!(u32::from(handle), 0x0001_0010);
+        assert_eq!(handle.major(), 1);
+        assert_eq!(handle.minor(), 0x10);
+        assert_eq!(handle.to_string(), "1:10");
+        assert_eq!(TcHandle::ROOT.to_string(), "root");
+
+        let mut tcmsg = Tcmsg::dump(1);
+        tcmsg.tcm_parent = 0xFFFF_FFF1;
+        assert_eq!(tcmsg.parent(), TcHandle::INGRESS);
+        tcmsg
+            .rtattrs
+            .push(Rtattr::new(Tca::Kind, "ingress".to_string()).unwrap());
+        assert_eq!(tcmsg.size(), 32);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        tcmsg.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref().len(), tcmsg.size());
+    }
+
+    #[test]
+    fn test_link_stats() {
+        let counters = |width: usize, count: u64| {
+            (1..=count)
+                .flat_map(|counter| match width {
+                    8 => counter.to_ne_bytes().to_vec(),
+                    _ => (counter as u32).to_ne_bytes().to_vec(),
+                })
+                .collect::<Vec<u8>>()
+        };
+        let link = |attrs| {
+            Ifinfomsg::new(
+                RtAddrFamily::Unspecified,
+                Arphrd::Ether,
+                2,
+                Vec::new(),
+                Iff::Up,
+                Rtattrs::new(attrs),
+            )
+        };
+
+        // Kernels before 5.19 do not report rx_otherhost_dropped
+        let stats = LinkStats64::from_counters(&counters(8, 24), 8).unwrap();
+        assert_eq!(stats.rx_packets, 1);
+        assert_eq!(stats.rx_nohandler, 24);
+        assert_eq!(stats.rx_otherhost_dropped, 0);
+        assert_eq!(stats.size(), 200);
+
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        stats.serialize(&mut mem).unwrap();
+        let attr = Rtattr::new(Ifla::Stats64, mem.as_ref().to_vec()).unwrap();
+        assert_eq!(attr.get_payload_as::<LinkStats64>().unwrap(), stats);
+
+        // Counters unknown to this crate are ignored
+        let newer = link(vec![Rtattr::new(Ifla::Stats64, counters(8, 27)).unwrap()]);
+        let stats = newer.stats().unwrap().unwrap();
+        assert_eq!(stats.tx_packets, 2);
+        assert_eq!(stats.rx_otherhost_dropped, 25);
+
+        let stats32 = link(vec![Rtattr::new(Ifla::Stats, counters(4, 24)).unwrap()]);
+        let stats = stats32.stats().unwrap().unwrap();
+        assert_eq!(stats.rx_bytes, 3);
+        assert_eq!(stats.rx_nohandler, 24);
+
+        let truncated = link(vec![Rtattr::new(Ifla::Stats64, vec![0u8; 12]).unwrap()]);
+        assert!(truncated.stats().is_err());
+        assert_eq!(link(Vec::new()).stats().unwrap(), None);
+    }
+
     #[test]
     fn test_rta_deserialize_err() {
         // 3 bytes is below minimum length
@@ -834,4 +1525,187 @@ mod test {
         // padding check
         assert_eq!(buf.as_ref().len(), 8);
     }
+
+    #[test]
+    fn test_rtattrs_nested() {
+        let mut vlan = Vec::new();
+        Rtattrs::new(vec![Rtattr::new(IflaVlan::Id, 100u16).unwrap()])
+            .serialize(&mut StreamWriteBuffer::new_growable_ref(&mut vlan))
+            .unwrap();
+        let mut linkinfo = Vec::new();
+        Rtattrs::new(vec![
+            Rtattr::new(IflaInfo::Kind, "vlan").unwrap(),
+            Rtattr::new(IflaInfo::Data, vlan).unwrap(),
+        ])
+        .serialize(&mut StreamWriteBuffer::new_growable_ref(&mut linkinfo))
+        .unwrap();
+        let attrs = Rtattrs::new(vec![
+            Rtattr::new(Ifla::Mtu, 1500u32).unwrap(),
+            Rtattr::new(Ifla::Linkinfo, linkinfo).unwrap(),
+        ]);
+
+        let info = attrs
+            .get_nested_attributes::<IflaInfo>(Ifla::Linkinfo)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            info.get_attr_payload_as::<String>(IflaInfo::Kind).unwrap(),
+            Some("vlan".to_string())
+        );
+        let id = info
+            .get_nested_attributes::<IflaVlan>(IflaInfo::Data)
+            .unwrap()
+            .unwrap()
+            .get_attr_payload_as::<u16>(IflaVlan::Id)
+            .unwrap();
+        assert_eq!(id, Some(100));
+        assert!(attrs
+            .get_nested_attributes::<IflaInfo>(Ifla::Ifname)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_rtattrs_typed() {
+        use crate::consts::{IflaIfname, IflaMaster, IflaMtu};
+
+        let attrs = Rtattrs::new(vec![
+            Rtattr::new(Ifla::Ifname, "eth0").unwrap(),
+            Rtattr::new(Ifla::Mtu, 1500u32).unwrap(),
+        ]);
+        assert_eq!(attrs.get::<IflaMtu>().unwrap(), Some(1500));
+        assert_eq!(attrs.get::<IflaIfname>().unwrap(), Some("eth0".to_string()));
+        assert_eq!(attrs.get::<IflaMaster>().unwrap(), None);
+    }
+
+    #[test]
+    fn test_ifinfomsg_accessors() {
+        let mac = MacAddress::new([2, 0, 0, 0, 0, 1]);
+        let ifinfomsg = Ifinfomsg::new(
+            RtAddrFamily::Unspecified,
+            Arphrd::Ether,
+            2,
+            vec![Iff::Up],
+            Iff::Up,
+            Rtattrs::new(vec![
+                Rtattr::new(Ifla::Ifname, "eth0").unwrap(),
+                Rtattr::new(Ifla::Mtu, 1500u32).unwrap(),
+                Rtattr::new(Ifla::Address, mac).unwrap(),
+                Rtattr::new(Ifla::Operstate, IfOper::Up).unwrap(),
+                Rtattr::new(Ifla::Master, 3u32).unwrap(),
+            ]),
+        );
+        assert_eq!(ifinfomsg.name().unwrap().as_deref(), Some("eth0"));
+        assert_eq!(ifinfomsg.mtu().unwrap(), Some(1500));
+        assert_eq!(ifinfomsg.address().unwrap(), Some(mac));
+        assert_eq!(ifinfomsg.operstate().unwrap(), Some(IfOper::Up));
+        assert_eq!(ifinfomsg.master().unwrap(), Some(3));
+        assert_eq!(ifinfomsg.link().unwrap(), None);
+
+        // Hardware addresses of other lengths are not MAC addresses
+        let tunnel = Ifinfomsg::new(
+            RtAddrFamily::Unspecified,
+            Arphrd::None,
+            4,
+            Vec::new(),
+            Iff::Up,
+            Rtattrs::new(vec![
+                Rtattr::new(Ifla::Address, vec![10u8, 0, 0, 1]).unwrap()
+            ]),
+        );
+        assert!(tunnel.address().is_err());
+    }
+
+    #[test]
+    fn test_rtattrs_repeated() {
+        let attrs = Rtattrs::new(vec![
+            Rtattr::new(Rta::Multipath, 1u32).unwrap(),
+            Rtattr::new(Rta::Table, 254u32).unwrap(),
+            Rtattr::new(Rta::Multipath, 2u32).unwrap(),
+        ]);
+        let legs = attrs
+            .get_attributes(Rta::Multipath)
+            .map(|a| a.get_payload_as::<u32>().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(legs, vec![1, 2]);
+        assert_eq!(attrs.get_attributes(Rta::Oif).count(), 0);
+    }
+
+    #[test]
+    fn test_rtattr_builder() {
+        let linkinfo = Rtattr::nested(Ifla::Linkinfo)
+            .attr(IflaInfo::Kind, "vlan")
+            .nested(Rtattr::nested(IflaInfo::Data).attr(IflaVlan::Id, 100u16))
+            .build()
+            .unwrap();
+        assert_eq!(linkinfo.size(), 4 + 12 + 12);
+
+        let mut vlan = Rtattr::new(IflaInfo::Data, Vec::<u8>::new()).unwrap();
+        vlan.add_nested_attribute(&Rtattr::new(IflaVlan::Id, 100u16).unwrap())
+            .unwrap();
+        let mut expected = Rtattr::new(Ifla::Linkinfo, Vec::<u8>::new()).unwrap();
+        expected
+            .add_nested_attribute(&Rtattr::new(IflaInfo::Kind, "vlan").unwrap())
+            .unwrap();
+        expected.add_nested_attribute(&vlan).unwrap();
+        assert_eq!(linkinfo, expected);
+
+        let mut attrs = Rtattrs::empty();
+        attrs.push(Rtattr::new(Ifla::Mtu, 1500u32).unwrap());
+        attrs.push(linkinfo);
+        assert_eq!(attrs.len(), 2);
+        assert!(attrs.has_attribute(Ifla::Linkinfo));
+        attrs
+            .get_attribute_mut(Ifla::Mtu)
+            .unwrap()
+            .set_payload(9000u32)
+            .unwrap();
+        attrs.get_vec_mut().retain(|a| a.rta_type != Ifla::Linkinfo);
+
+        let mut buf = StreamWriteBuffer::new_growable(None);
+        attrs.serialize(&mut buf).unwrap();
+        let mut read = StreamReadBuffer::new(buf.as_ref());
+        read.set_size_hint(buf.as_ref().len());
+        let parsed = Rtattrs::<Ifla, Vec<u8>>::deserialize(&mut read).unwrap();
+        assert_eq!(parsed, attrs);
+        assert_eq!(
+            parsed.get_attr_payload_as::<u32>(Ifla::Mtu).unwrap(),
+            Some(9000)
+        );
+    }
+
+    #[cfg(target_env = "gnu")]
+    #[test]
+    fn test_rtattrs_64bit() {
+        // Attributes following the netlink header and an Ifinfomsg start at offset 32
+        let mut attrs = Rtattrs::empty();
+        attrs.push_64bit(32, Rta::Pad, Rta::Table, 5u64).unwrap();
+        attrs.push_64bit(32, Rta::Pad, Rta::Priority, 6u64).unwrap();
+        assert_eq!(
+            attrs.iter().map(|a| a.rta_type.clone()).collect::<Vec<_>>(),
+            vec![Rta::Pad, Rta::Table, Rta::Pad, Rta::Priority]
+        );
+
+        let mut buf = StreamWriteBuffer::new_growable(None);
+        attrs.serialize(&mut buf).unwrap();
+        assert_eq!(buf.as_ref().len(), 32);
+        assert_eq!(&buf.as_ref()[..4], &[4, 0, libc::RTA_PAD as u8, 0]);
+
+        let mut read = StreamReadBuffer::new(buf.as_ref());
+        read.set_size_hint(32);
+        let attrs = Rtattrs::<Rta, Vec<u8>>::deserialize(&mut read).unwrap();
+        assert_eq!(attrs.iter_without_pad(Rta::Pad).count(), 2);
+        assert_eq!(
+            attrs.get_attr_payload_as::<u64>(Rta::Table).unwrap(),
+            Some(5)
+        );
+        assert_eq!(
+            attrs.get_attr_payload_as::<u64>(Rta::Priority).unwrap(),
+            Some(6)
+        );
+
+        let mut aligned = Rtattrs::empty();
+        aligned.push_64bit(28, Rta::Pad, Rta::Table, 5u64).unwrap();
+        assert_eq!(aligned.iter().count(), 1);
+    }
 }
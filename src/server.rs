@@ -0,0 +1,313 @@
+//! Receiving end of requests sent by userspace peers
+//!
+//! `NlServer` accepts requests addressed to the port of its socket, typically on a
+//! `NlFamily::Usersock` socket, and answers them the way the kernel answers requests: replies
+//! carry the sequence number of the request, the messages of a multipart reply are flagged with
+//! `NLM_F_MULTI` and followed by `NLMSG_DONE`, and ACKs and errors echo the header of the
+//! request. This allows daemons to implement netlink services for clients using `NlSocket`.
+
+use std::{fmt::Debug, io};
+
+use buffering::StreamWriteBuffer;
+
+#[cfg(feature = "logging")]
+use crate::log;
+use crate::{
+    consts::{NlFamily, NlType, NlmF, Nlmsg},
+    err::{NlError, Nlmsgerr},
+    nl::{NlEmpty, Nlmsghdr},
+    socket::{CheckPolicy, NlAddr, NlSocket},
+    Nl,
+};
+
+/// Request received by an `NlServer` along with the address of the socket that sent it
+#[derive(Debug)]
+pub struct NlRequest<T, P> {
+    /// The request
+    pub msg: Nlmsghdr<T, P>,
+    /// Address of the socket the replies are sent to
+    pub sender: NlAddr,
+}
+
+impl<T, P> NlRequest<T, P> {
+    /// Whether the sender asked for an ACK with `NLM_F_ACK`
+    pub fn wants_ack(&self) -> bool {
+        self.msg.nl_flags.contains(&NlmF::Ack)
+    }
+
+    /// Whether the request is a dump request with `NLM_F_DUMP`
+    ///
+    /// `NLM_F_DUMP` is deserialized as its two bits `NlmF::Root` and `NlmF::Match`.
+    pub fn is_dump(&self) -> bool {
+        let flags = &self.msg.nl_flags;
+        flags.contains(&NlmF::Dump) || (flags.contains(&NlmF::Root) && flags.contains(&NlmF::Match))
+    }
+}
+
+/// Socket accepting requests and sending replies to them
+pub struct NlServer {
+    socket: NlSocket,
+}
+
+impl NlServer {
+    /// Accept requests on `socket`
+    ///
+    /// Requests come from any number of peers so the PID check of the socket is turned off.
+    /// Replies are sent with the sequence number of the request even if the socket tracks
+    /// sequence numbers.
+    pub fn new(mut socket: NlSocket) -> Self {
+        socket.set_pid_policy(CheckPolicy::Off);
+        NlServer { socket }
+    }
+
+    /// Create a socket for `proto` bound to `port`, or to a port chosen by the kernel if `None`,
+    /// and accept requests on it
+    pub fn bind(proto: NlFamily, port: Option<u32>) -> Result<Self, io::Error> {
+        let mut socket = NlSocket::new(proto, false)?;
+        socket.bind_port(port.unwrap_or(0), None)?;
+        Ok(NlServer::new(socket))
+    }
+
+    /// Port ID that clients send their requests to
+    pub fn port_id(&self) -> Result<u32, io::Error> {
+        self.socket.port_id()
+    }
+
+    /// Receive the next request parsed as a message of type `T` with a payload of type `P`
+    ///
+    /// Messages without `NLM_F_REQUEST`, such as multicast messages, are skipped.
+    pub fn accept<T, P>(&mut self) -> Result<NlRequest<T, P>, NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        loop {
+            let (msg, sender) = self.socket.recv_nl_from::<T, P>(None)?;
+            if msg.nl_flags.contains(&NlmF::Request) {
+                return Ok(NlRequest { msg, sender });
+            }
+        }
+    }
+
+    /// Send a single reply to `request`
+    ///
+    /// If the request asked for an ACK, call `ack` after the reply.
+    pub fn reply<RT, RP, T, P>(
+        &self,
+        request: &NlRequest<RT, RP>,
+        nl_type: T,
+        payload: P,
+    ) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        self.send(request, nl_type, Vec::new(), payload)
+    }
+
+    /// Send a multipart reply to `request` flagged with `NLM_F_MULTI` followed by `NLMSG_DONE`
+    ///
+    /// Each message is sent as a datagram of its own.
+    pub fn reply_multi<RT, RP, T, P, I>(
+        &self,
+        request: &NlRequest<RT, RP>,
+        nl_type: T,
+        payloads: I,
+    ) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Clone + Debug,
+        P: Nl + Debug,
+        I: IntoIterator<Item = P>,
+    {
+        for payload in payloads {
+            self.send(request, nl_type.clone(), vec![NlmF::Multi], payload)?;
+        }
+        self.send(request, Nlmsg::Done, vec![NlmF::Multi], 0i32)
+    }
+
+    /// Acknowledge `request` if it asked for an ACK with `NLM_F_ACK`
+    ///
+    /// Like errors, ACKs echo the header of the request without its payload and are flagged with
+    /// `NLM_F_CAPPED`.
+    pub fn ack<RT, RP>(&self, request: &NlRequest<RT, RP>) -> Result<(), NlError>
+    where
+        RT: NlType + Clone + Debug,
+    {
+        if !request.wants_ack() {
+            return Ok(());
+        }
+        self.send_error(request, 0)
+    }
+
+    /// Answer `request` with the error `errno`, such as `libc::ENOENT`
+    ///
+    /// The payload of the request is not echoed and `NLM_F_CAPPED` is set accordingly. The sign
+    /// of `errno` is ignored and `i32::MIN`, which has no positive counterpart, is rejected.
+    pub fn error<RT, RP>(&self, request: &NlRequest<RT, RP>, errno: i32) -> Result<(), NlError>
+    where
+        RT: NlType + Clone + Debug,
+    {
+        let errno = errno
+            .checked_abs()
+            .ok_or_else(|| NlError::new("errno is out of range"))?;
+        self.send_error(request, -errno)
+    }
+
+    fn send_error<RT, RP>(&self, request: &NlRequest<RT, RP>, error: i32) -> Result<(), NlError>
+    where
+        RT: NlType + Clone + Debug,
+    {
+        let req = &request.msg;
+        // The echoed header keeps the length of the request including its payload, like the ACKs
+        // and errors of the kernel
+        let nlmsg = Nlmsghdr {
+            nl_len: req.nl_len,
+            nl_type: req.nl_type.clone(),
+            nl_flags: req.nl_flags.clone(),
+            nl_seq: req.nl_seq,
+            nl_pid: req.nl_pid,
+            nl_payload: NlEmpty,
+        };
+        let payload = Nlmsgerr {
+            error,
+            nlmsg,
+            trailing: Vec::new(),
+        };
+        // NLM_F_CAPPED shares its value with NLM_F_ROOT
+        self.send(request, Nlmsg::Error, vec![NlmF::Root], payload)
+    }
+
+    fn send<RT, RP, T, P>(
+        &self,
+        request: &NlRequest<RT, RP>,
+        nl_type: T,
+        nl_flags: Vec<NlmF>,
+        payload: P,
+    ) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        let msg = Nlmsghdr::new(
+            None,
+            nl_type,
+            nl_flags,
+            Some(request.msg.nl_seq),
+            Some(self.socket.port_id()?),
+            payload,
+        );
+        #[cfg(feature = "logging")]
+        log!("Message sent:\n{:#?}", msg);
+        let mut mem = Vec::new();
+        msg.serialize(&mut StreamWriteBuffer::new_growable_ref(&mut mem))?;
+        self.socket
+            .send_to(&mem, request.sender.pid, 0, 0)
+            .map_err(NlError::from)?;
+        Ok(())
+    }
+
+    /// Reference to the underlying socket
+    pub fn get_ref(&self) -> &NlSocket {
+        &self.socket
+    }
+
+    /// Convert into the underlying socket
+    pub fn into_inner(self) -> NlSocket {
+        self.socket
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::socket::IterationBehavior;
+
+    fn request(flags: Vec<NlmF>, payload: u32) -> Nlmsghdr<u16, u32> {
+        Nlmsghdr::new(None, 100, flags, Some(7), None, payload)
+    }
+
+    #[test]
+    fn test_server_reply() {
+        let mut server = NlServer::bind(NlFamily::Usersock, None).unwrap();
+        let port = server.port_id().unwrap();
+        let mut client = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+
+        // Messages that are not requests are skipped
+        client.send_nl_to(request(Vec::new(), 1), port, 0).unwrap();
+        client
+            .send_nl_to(request(vec![NlmF::Request, NlmF::Ack], 2), port, 0)
+            .unwrap();
+        let req = server.accept::<u16, u32>().unwrap();
+        assert_eq!(req.msg.nl_payload, 2);
+        assert_eq!(req.sender.pid, client.port_id().unwrap());
+        assert!(req.wants_ack());
+        assert!(!req.is_dump());
+        server.reply(&req, 101u16, 3u32).unwrap();
+        server.ack(&req).unwrap();
+
+        let (reply, from) = client.recv_nl_from::<u16, u32>(None).unwrap();
+        assert_eq!(from.pid, port);
+        assert_eq!(reply.nl_type, 101);
+        assert_eq!(reply.nl_seq, 7);
+        assert_eq!(reply.nl_payload, 3);
+        client.recv_ack().unwrap();
+
+        client
+            .send_nl_to(request(vec![NlmF::Request, NlmF::Ack], 4), port, 0)
+            .unwrap();
+        let req = server.accept::<u16, u32>().unwrap();
+        server.error(&req, i32::MIN).unwrap_err();
+        server.error(&req, libc::ENOENT).unwrap();
+        let err = client.recv_ack().unwrap_err();
+        assert_eq!(err.errno(), Some(libc::ENOENT));
+    }
+
+    #[test]
+    fn test_server_echoed_header() {
+        let mut server = NlServer::bind(NlFamily::Usersock, None).unwrap();
+        let port = server.port_id().unwrap();
+        let mut client = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+
+        for errno in [0, libc::ENOENT] {
+            client
+                .send_nl_to(request(vec![NlmF::Request, NlmF::Ack], 1), port, 0)
+                .unwrap();
+            let req = server.accept::<u16, u32>().unwrap();
+            if errno == 0 {
+                server.ack(&req).unwrap();
+            } else {
+                server.error(&req, errno).unwrap();
+            }
+            let msg = client.recv_nl::<Nlmsg, Nlmsgerr<u16>>(None).unwrap();
+            assert_eq!(msg.nl_type, Nlmsg::Error);
+            assert_eq!(msg.nl_flags, vec![NlmF::Root]);
+            assert_eq!(msg.nl_payload.error, -errno);
+            assert_eq!(msg.nl_payload.nlmsg.nl_len, 20);
+            assert_eq!(msg.nl_payload.nlmsg.nl_type, 100);
+            assert_eq!(msg.nl_payload.nlmsg.nl_seq, 7);
+            assert!(msg.nl_payload.trailing.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_server_reply_multi() {
+        let mut server = NlServer::bind(NlFamily::Usersock, None).unwrap();
+        let port = server.port_id().unwrap();
+        let mut client = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+
+        client
+            .send_nl_to(request(vec![NlmF::Request, NlmF::Dump], 0), port, 0)
+            .unwrap();
+        let req = server.accept::<u16, u32>().unwrap();
+        assert!(req.is_dump());
+        server.reply_multi(&req, 101u16, vec![1u32, 2, 3]).unwrap();
+
+        let payloads = client
+            .iter_with::<u16, u32>(IterationBehavior::EndMultiOnDone)
+            .map(|msg| msg.map(|msg| msg.nl_payload))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(payloads, vec![1, 2, 3]);
+    }
+}
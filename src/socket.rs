@@ -11,6 +11,7 @@
 //! * `iter` provides a loop based iteration through messages that are received in a stream over
 //! the socket.
 //! * `recv_ack` receives an ACK message and verifies it matches the request.
+//! * `NlProtoSocket` wraps `NlSocket` and only accepts messages valid for its protocol.
 //!
 //! ## Features
 //! The `stream` feature exposed by `cargo` allows the socket to use Rust's tokio for async IO.
@@ -22,11 +23,20 @@
 //! a Github issue and submit a feature request.
 
 use std::{
+    collections::HashMap,
     fmt::Debug,
+    fs::File,
     io,
     marker::PhantomData,
-    mem::{size_of, zeroed},
-    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+    mem::{size_of, size_of_val, zeroed},
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
 };
 
 use buffering::{StreamReadBuffer, StreamWriteBuffer};
@@ -36,19 +46,35 @@ use libc::{self, c_int, c_void};
 use crate::log;
 use crate::{
     consts::{
-        self, AddrFamily, CtrlAttr, CtrlAttrMcastGrp, CtrlCmd, GenlId, NlAttrType, NlFamily,
-        NlType, NlmF,
+        self, AddrFamily, CtrlAttr, CtrlCmd, GenlId, NlFamily, NlSockOpt, NlType, NlmF,
+        RtAddrFamily, RtTable, Rtm,
     },
-    err::{NlError, Nlmsgerr},
-    genl::Genlmsghdr,
-    nl::Nlmsghdr,
+    err::{ExtAck, NlError, Nlmsgerr},
+    genl::{GenlFamily, Genlmsghdr},
+    nl::{add_flags, DeMode, Nlmsghdr, RawHeader, NLMSG_HDRLEN},
     nlattr::Nlattr,
-    Nl, MAX_NL_LENGTH,
+    rtnl::{Ifaddrmsg, Ifinfomsg, Ndmsg, Rtmsg, TcHandle, Tcmsg},
+    Nl,
 };
 
+/// When an `NlMessageIter` stops yielding messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IterationBehavior {
+    /// Stop after the response to a request: at the `NLMSG_DONE` message ending a dump, after an
+    /// `NLMSG_ERROR` message, or after a message without `NLM_F_MULTI`. `NLMSG_DONE` and ACKs
+    /// are not yielded and errors reported by the kernel are yielded as `NlError::Errno` or
+    /// `NlError::ExtAck`.
+    EndMultiOnDone,
+    /// Keep receiving messages forever, for example to monitor multicast notifications
+    IterIndefinitely,
+}
+
 /// Iterator over messages returned from a `recv_nl` call
 pub struct NlMessageIter<'a, T, P> {
     socket_ref: &'a mut NlSocket,
+    behavior: IterationBehavior,
+    finished: bool,
+    dump_interrupted: bool,
     data_type: PhantomData<T>,
     data_payload: PhantomData<P>,
 }
@@ -60,12 +86,27 @@ where
 {
     /// Construct a new iterator that yields `Nlmsghdr` structs from the provided buffer
     pub fn new(socket_ref: &'a mut NlSocket) -> Self {
+        NlMessageIter::with_behavior(socket_ref, IterationBehavior::IterIndefinitely)
+    }
+
+    /// Construct a new iterator that stops yielding messages according to `behavior`
+    pub fn with_behavior(socket_ref: &'a mut NlSocket, behavior: IterationBehavior) -> Self {
         NlMessageIter {
             socket_ref,
+            behavior,
+            finished: false,
+            dump_interrupted: false,
             data_type: PhantomData,
             data_payload: PhantomData,
         }
     }
+
+    /// Whether a message of the response received so far was flagged with `NLM_F_DUMP_INTR`
+    ///
+    /// Only tracked with `IterationBehavior::EndMultiOnDone`.
+    pub fn dump_interrupted(&self) -> bool {
+        self.dump_interrupted
+    }
 }
 
 impl<'a, T, P> Iterator for NlMessageIter<'a, T, P>
@@ -76,41 +117,481 @@ where
     type Item = Result<Nlmsghdr<T, P>, NlError>;
 
     fn next(&mut self) -> Option<Result<Nlmsghdr<T, P>, NlError>> {
-        match self.socket_ref.recv_nl(None) {
-            Ok(rn) => Some(Ok(rn)),
-            Err(e) => Some(Err(e)),
+        if self.behavior == IterationBehavior::IterIndefinitely {
+            return Some(self.socket_ref.recv_nl(None));
+        }
+        if self.finished {
+            return None;
+        }
+
+        let header = match self.socket_ref.peek_nl_header() {
+            Ok(header) => header,
+            Err(e) => return Some(Err(e)),
+        };
+        if header.nl_flags & u16::from(NlmF::DumpIntr) != 0 {
+            self.dump_interrupted = true;
+        }
+        if header.nl_type == u16::from(consts::Nlmsg::Done) {
+            self.finished = true;
+            match self.socket_ref.recv_nl::<u16, Vec<u8>>(None) {
+                Ok(done) => done_error(&done.nl_payload).map(Err),
+                Err(e) => Some(Err(e)),
+            }
+        } else if header.nl_type == u16::from(consts::Nlmsg::Error) {
+            self.finished = true;
+            self.socket_ref.recv_ack().err().map(Err)
+        } else {
+            let msg = self.socket_ref.recv_nl(None);
+            if msg.is_ok() && header.nl_flags & u16::from(NlmF::Multi) == 0 {
+                self.finished = true;
+            }
+            Some(msg)
+        }
+    }
+}
+
+/// Iterator over the payloads of all messages of a dump response returned from
+/// `NlSocket::dump`
+///
+/// If any message of the response was flagged with `NLM_F_DUMP_INTR`, the last item is
+/// `NlError::DumpInterrupted`.
+pub struct NlDumpIter<'a, T, P> {
+    msgs: NlMessageIter<'a, T, P>,
+    reported_intr: bool,
+}
+
+impl<'a, T, P> Iterator for NlDumpIter<'a, T, P>
+where
+    T: Nl + NlType + Debug,
+    P: Nl + Debug,
+{
+    type Item = Result<P, NlError>;
+
+    fn next(&mut self) -> Option<Result<P, NlError>> {
+        match self.msgs.next() {
+            Some(msg) => Some(msg.map(|m| m.nl_payload)),
+            None if self.msgs.dump_interrupted() && !self.reported_intr => {
+                self.reported_intr = true;
+                Some(Err(NlError::DumpInterrupted))
+            }
+            None => None,
+        }
+    }
+}
+
+/// Netlink address of a socket or the kernel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NlAddr {
+    /// Port ID - 0 for the kernel
+    pub pid: u32,
+    /// Bitmask of multicast groups
+    pub groups: u32,
+}
+
+impl NlAddr {
+    /// Whether the address is the one of the kernel
+    pub fn is_kernel(&self) -> bool {
+        self.pid == 0
+    }
+}
+
+// Dumps that fail after they started report the error in the NLMSG_DONE payload.
+pub(crate) fn done_error(payload: &[u8]) -> Option<NlError> {
+    let bytes = payload.get(..size_of::<c_int>())?;
+    let mut error = [0; size_of::<c_int>()];
+    error.copy_from_slice(bytes);
+    match c_int::from_ne_bytes(error) {
+        e if e < 0 => Some(NlError::Errno(-e)),
+        _ => None,
+    }
+}
+
+// Error reported by an NLMSG_ERROR message that is not an ACK.
+pub(crate) fn nlmsgerr_error(msg: &Nlmsghdr<consts::Nlmsg, Nlmsgerr<consts::Nlmsg>>) -> NlError {
+    let ext_ack = msg.nl_payload.ext_ack(&msg.nl_flags).unwrap_or_default();
+    if ext_ack != ExtAck::default() {
+        return NlError::ExtAck {
+            error: msg.nl_payload.error,
+            ext_ack,
+        };
+    }
+    NlError::Errno(-msg.nl_payload.error)
+}
+
+/// Ancillary information about the datagram a message was received in, returned by
+/// `recv_nl_with_info` and `recv_vectored`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NlRecvInfo {
+    /// Multicast group the message was sent to - `None` for unicast messages or if
+    /// `NlSocket::set_pktinfo` is not enabled
+    pub group: Option<u32>,
+    /// Address of the socket that sent the message, with the bit of the multicast group the
+    /// message was sent to set in `groups` - port ID 0 for messages sent by the kernel
+    pub sender: NlAddr,
+    /// ID of the network namespace the message was sent from, relative to the namespace of the
+    /// socket - `None` if the namespace has no ID assigned or if
+    /// `NlSocket::set_listen_all_nsid` is not enabled
+    pub nsid: Option<i32>,
+    /// The datagram was longer than the buffers it was received into and the rest of it was
+    /// discarded
+    pub truncated: bool,
+}
+
+impl NlRecvInfo {
+    // Fill in the fields carried by the ancillary data of a received message.
+    fn read_cmsgs(&mut self, msg: &libc::msghdr) {
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(msg) };
+        while !cmsg.is_null() {
+            let (level, ty) = unsafe { ((*cmsg).cmsg_level, (*cmsg).cmsg_type) };
+            let data = unsafe { libc::CMSG_DATA(cmsg) };
+            if level == libc::SOL_NETLINK && ty == c_int::from(NlSockOpt::PktInfo) {
+                let group = unsafe { (data as *const u32).read_unaligned() };
+                self.group = if group == 0 { None } else { Some(group) };
+            } else if level == libc::SOL_NETLINK && ty == c_int::from(NlSockOpt::ListenAllNsid) {
+                self.nsid = Some(unsafe { (data as *const c_int).read_unaligned() });
+            }
+            cmsg = unsafe { libc::CMSG_NXTHDR(msg, cmsg) };
+        }
+    }
+}
+
+/// Options applied when creating the socket file descriptor
+///
+/// Sockets are created with `SOCK_CLOEXEC` by default so the file descriptor is not leaked into
+/// child processes across `exec`.
+#[derive(Clone, Debug)]
+pub struct NlSocketOptions {
+    cloexec: bool,
+    nonblock: bool,
+    track_seq: bool,
+}
+
+impl Default for NlSocketOptions {
+    fn default() -> Self {
+        NlSocketOptions {
+            cloexec: true,
+            nonblock: false,
+            track_seq: false,
+        }
+    }
+}
+
+impl NlSocketOptions {
+    /// Start from the default options
+    pub fn new() -> Self {
+        NlSocketOptions::default()
+    }
+
+    /// Set `SOCK_CLOEXEC` on the socket
+    pub fn cloexec(mut self, cloexec: bool) -> Self {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Set `SOCK_NONBLOCK` on the socket - see `NlSocket::set_nonblocking`
+    pub fn nonblock(mut self, nonblock: bool) -> Self {
+        self.nonblock = nonblock;
+        self
+    }
+
+    /// Track sequence numbers of sent messages
+    pub fn track_seq(mut self, track_seq: bool) -> Self {
+        self.track_seq = track_seq;
+        self
+    }
+
+    fn sock_type(&self) -> c_int {
+        let mut ty = libc::SOCK_RAW;
+        if self.cloexec {
+            ty |= libc::SOCK_CLOEXEC;
+        }
+        if self.nonblock {
+            ty |= libc::SOCK_NONBLOCK;
+        }
+        ty
+    }
+}
+
+/// How `NlSocket::recv_nl` handles a message with a PID or sequence number that does not match
+///
+/// Multicast notifications are sent with a PID and sequence number of `0` so sockets that receive
+/// them along with replies should use `Warn` or `Off`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckPolicy {
+    /// Return `NlError::BadPid` or `NlError::BadSeq`
+    Strict,
+    /// Return the message and log a warning if the `logging` feature is enabled
+    Warn,
+    /// Return the message without checking
+    Off,
+}
+
+/// How `NlSocket::recv_nl` handles a blocking receive interrupted by a signal (`EINTR`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterruptPolicy {
+    /// Receive again until a message arrives
+    Retry,
+    /// Return `NlError::Interrupted`, for example to handle the signal before receiving again
+    Return,
+}
+
+/// Which failures `NlSocket::send_and_await_ack` and `NlSocket::dump_consistent` retry and how
+/// long they wait before each new attempt
+///
+/// The request is sent again with a new sequence number if sequence numbers are tracked. The
+/// delay starts at the initial backoff and doubles after each attempt up to the maximum backoff.
+/// By default, a request is attempted up to 3 times for `EAGAIN`, `EBUSY`, `ENOBUFS` including
+/// `NlError::Overrun`, and `NlError::DumpInterrupted`. `NlError::WouldBlock` from a non-blocking
+/// socket is never retried.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    backoff: Duration,
+    max_backoff: Duration,
+    errnos: Vec<c_int>,
+    dump_interrupted: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            errnos: vec![libc::EAGAIN, libc::EBUSY, libc::ENOBUFS],
+            dump_interrupted: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Start from the default policy
+    pub fn new() -> Self {
+        RetryPolicy::default()
+    }
+
+    /// Set the number of attempts including the first one
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Set the delay before the first retry and the maximum delay it doubles up to
+    pub fn backoff(mut self, backoff: Duration, max_backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set the `errno` values of errors to retry
+    pub fn retry_errnos(mut self, errnos: Vec<c_int>) -> Self {
+        self.errnos = errnos;
+        self
+    }
+
+    /// Retry dumps flagged with `NLM_F_DUMP_INTR`
+    pub fn retry_dump_interrupted(mut self, retry: bool) -> Self {
+        self.dump_interrupted = retry;
+        self
+    }
+
+    /// Whether `err` is retried by this policy
+    pub fn is_retryable(&self, err: &NlError) -> bool {
+        match *err {
+            NlError::DumpInterrupted => self.dump_interrupted,
+            NlError::Overrun => self.errnos.contains(&libc::ENOBUFS),
+            ref e => e.errno().map(|e| self.errnos.contains(&e)).unwrap_or(false),
         }
     }
+
+    // Delay before the retry following attempt number `attempt`, counting from 0.
+    fn delay(&self, attempt: usize) -> Duration {
+        let factor = 1u32.checked_shl(attempt as u32).unwrap_or(u32::MAX);
+        self.backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
 }
 
+/// Memory usage and drop counter of a socket returned by `NlSocket::stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NlSocketStats {
+    /// Bytes of received messages queued in the receive buffer
+    pub rmem_alloc: u32,
+    /// Size of the receive buffer in bytes
+    pub rcvbuf: u32,
+    /// Bytes of sent messages not yet freed by the kernel
+    pub wmem_alloc: u32,
+    /// Size of the send buffer in bytes
+    pub sndbuf: u32,
+    /// Number of messages dropped because the receive buffer was full
+    pub drops: u32,
+}
+
+// Callback resynchronizing state after the receive buffer overflowed
+type OverrunHandler = Box<dyn FnMut(&mut NlSocket) -> Result<(), NlError> + Send + Sync>;
+
 /// Handle for the socket file descriptor
 pub struct NlSocket {
     fd: c_int,
     buffer: Option<StreamReadBuffer<Vec<u8>>>,
+    send_buffer: Vec<u8>,
     pid: Option<u32>,
     seq: Option<u32>,
+    de_mode: DeMode,
+    recv_info: NlRecvInfo,
+    // First sequence number and number of messages of the last send
+    sent_seqs: Option<(u32, u32)>,
+    pid_policy: CheckPolicy,
+    seq_policy: CheckPolicy,
+    overrun_handler: Option<OverrunHandler>,
+    grow_recv_buffer: bool,
+    interrupt_policy: InterruptPolicy,
+    genl_cache: Option<Box<GenlCache>>,
+    retry_policy: Option<RetryPolicy>,
+}
+
+// Generic netlink families resolved by a socket, kept up to date with the notifications of the
+// nlctrl family received on a separate socket.
+struct GenlCache {
+    notify: NlSocket,
+    families: HashMap<String, GenlFamily>,
+}
+
+impl GenlCache {
+    // Drop families removed since the last call. If notifications were lost, nothing that is
+    // cached can be trusted anymore.
+    fn process_notifications(&mut self) {
+        loop {
+            match self
+                .notify
+                .recv_nl::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>(None)
+            {
+                Ok(msg) => {
+                    if msg.nl_payload.cmd != CtrlCmd::Delfamily {
+                        continue;
+                    }
+                    let name = msg
+                        .nl_payload
+                        .get_attr_handle()
+                        .get_attr_payload_as::<String>(CtrlAttr::FamilyName);
+                    match name {
+                        Ok(name) => {
+                            self.families.remove(&name);
+                        }
+                        Err(_) => self.families.clear(),
+                    }
+                }
+                Err(NlError::WouldBlock) => break,
+                Err(_) => {
+                    self.families.clear();
+                    break;
+                }
+            }
+        }
+    }
 }
 
 impl NlSocket {
     /// Wrapper around `socket()` syscall filling in the netlink-specific information
     pub fn new(proto: NlFamily, track_seq: bool) -> Result<Self, io::Error> {
-        let fd =
-            match unsafe { libc::socket(AddrFamily::Netlink.into(), libc::SOCK_RAW, proto.into()) }
-            {
-                i if i >= 0 => Ok(i),
-                _ => Err(io::Error::last_os_error()),
-            }?;
+        NlSocket::with_options(proto, NlSocketOptions::new().track_seq(track_seq))
+    }
+
+    /// Wrapper around `socket()` syscall creating the socket with the given options
+    pub fn with_options(proto: NlFamily, options: NlSocketOptions) -> Result<Self, io::Error> {
+        let fd = match unsafe {
+            libc::socket(
+                AddrFamily::Netlink.into(),
+                options.sock_type(),
+                proto.into(),
+            )
+        } {
+            i if i >= 0 => Ok(i),
+            _ => Err(io::Error::last_os_error()),
+        }?;
         Ok(NlSocket {
             fd,
             buffer: None,
+            send_buffer: Vec::new(),
             pid: None,
-            seq: if track_seq { Some(0) } else { None },
+            seq: if options.track_seq { Some(0) } else { None },
+            de_mode: DeMode::default(),
+            recv_info: NlRecvInfo::default(),
+            sent_seqs: None,
+            pid_policy: CheckPolicy::Strict,
+            seq_policy: CheckPolicy::Off,
+            overrun_handler: None,
+            grow_recv_buffer: false,
+            interrupt_policy: InterruptPolicy::Return,
+            genl_cache: None,
+            retry_policy: None,
         })
     }
 
     /// Manually increment sequence number
     pub fn increment_seq(&mut self) {
-        self.seq.map(|seq| seq + 1);
+        if let Some(ref mut seq) = self.seq {
+            *seq = seq.wrapping_add(1);
+        }
+    }
+
+    /// Grow the buffer passed to `recv_nl` as `buf_sz` if the next datagram does not fit into it
+    ///
+    /// By default, receiving a datagram longer than `buf_sz` fails with `NlError::Truncated` and
+    /// the datagram is discarded. With this enabled, the length of each datagram is checked
+    /// before receiving it at the cost of an additional system call. Receiving with a `buf_sz`
+    /// of `None` always sizes the buffer to fit the datagram.
+    pub fn set_grow_recv_buffer(&mut self, enable: bool) {
+        self.grow_recv_buffer = enable;
+    }
+
+    /// Set how receives interrupted by a signal are handled - the default is
+    /// `InterruptPolicy::Return`
+    ///
+    /// Signal handlers installed with `SA_RESTART` restart the receive in the kernel so this
+    /// only applies to handlers without it.
+    pub fn set_interrupt_policy(&mut self, policy: InterruptPolicy) {
+        self.interrupt_policy = policy;
+    }
+
+    /// Set how PIDs of received messages are checked against the PID passed to `bind` - the
+    /// default is `CheckPolicy::Strict`
+    pub fn set_pid_policy(&mut self, policy: CheckPolicy) {
+        self.pid_policy = policy;
+    }
+
+    /// Set how sequence numbers of received messages are checked - the default is
+    /// `CheckPolicy::Off`
+    ///
+    /// Unless the policy is `Off`, sequence numbers are tracked and messages that do not answer
+    /// the messages of the last `send_nl` or `send_all` call do not match.
+    pub fn set_seq_policy(&mut self, policy: CheckPolicy) {
+        if policy != CheckPolicy::Off && self.seq.is_none() {
+            self.seq = Some(0);
+        }
+        self.seq_policy = policy;
+    }
+
+    fn check_failed(policy: CheckPolicy, err: NlError) -> Result<(), NlError> {
+        match policy {
+            CheckPolicy::Strict => Err(err),
+            CheckPolicy::Warn => {
+                #[cfg(feature = "logging")]
+                log::warn!("{}", err);
+                Ok(())
+            }
+            CheckPolicy::Off => Ok(()),
+        }
+    }
+
+    // Whether seq belongs to one of the messages of the last send.
+    fn seq_matches(&self, seq: u32) -> bool {
+        match self.sent_seqs {
+            Some((first, count)) => seq.wrapping_sub(first) < count,
+            None => false,
+        }
     }
 
     /// Set underlying socket file descriptor to be blocking
@@ -141,6 +622,19 @@ impl NlSocket {
         }
     }
 
+    /// Set underlying socket file descriptor to be blocking or non blocking
+    ///
+    /// In non blocking mode, `recv_nl` and `iter` return `NlError::WouldBlock` when no message
+    /// is available so the socket can be driven from a poll loop.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<(), io::Error> {
+        if nonblocking {
+            self.nonblock()?;
+        } else {
+            self.block()?;
+        }
+        Ok(())
+    }
+
     /// Determines if underlying file descriptor is blocking - `Stream` feature will throw an
     /// error if this function returns false
     pub fn is_blocking(&self) -> Result<bool, io::Error> {
@@ -159,10 +653,24 @@ impl NlSocket {
     /// * `Some(0)` turns checking on, but takes the PID from the first received message.
     /// * `Some(pid)` uses the given PID.
     pub fn bind(&mut self, pid: Option<u32>, groups: Option<Vec<u32>>) -> Result<(), io::Error> {
+        // Set before joining the groups, which turns PID checking off again
+        self.pid = pid;
+        self.bind_addr(pid.unwrap_or(0), groups)
+    }
+
+    /// Bind to the caller-chosen port ID `port` and subscribe to groups. Unlike `bind`, this
+    /// does not turn on PID checking.
+    ///
+    /// Port IDs must be unique across all netlink sockets of the same protocol on the system so
+    /// use `local_port_id` to derive one per socket when opening several sockets in one process.
+    pub fn bind_port(&mut self, port: u32, groups: Option<Vec<u32>>) -> Result<(), io::Error> {
+        self.bind_addr(port, groups)
+    }
+
+    fn bind_addr(&mut self, port: u32, groups: Option<Vec<u32>>) -> Result<(), io::Error> {
         let mut nladdr = unsafe { zeroed::<libc::sockaddr_nl>() };
         nladdr.nl_family = libc::c_int::from(AddrFamily::Netlink) as u16;
-        nladdr.nl_pid = pid.unwrap_or(0);
-        self.pid = pid;
+        nladdr.nl_pid = port;
         nladdr.nl_groups = 0;
         match unsafe {
             libc::bind(
@@ -175,12 +683,82 @@ impl NlSocket {
             _ => return Err(io::Error::last_os_error()),
         };
         if let Some(grps) = groups {
-            self.set_mcast_groups(grps)?;
+            self.add_mcast_membership(&grps)?;
+        }
+        Ok(())
+    }
+
+    /// Port ID the socket is bound to, as assigned by the kernel or passed to `bind_port` -
+    /// `0` if the socket is not bound yet
+    pub fn port_id(&self) -> Result<u32, io::Error> {
+        let mut nladdr = unsafe { zeroed::<libc::sockaddr_nl>() };
+        let mut len = size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+        match unsafe {
+            libc::getsockname(
+                self.fd,
+                &mut nladdr as *mut _ as *mut libc::sockaddr,
+                &mut len,
+            )
+        } {
+            0 => Ok(nladdr.nl_pid),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Derive a port ID for the socket number `offset` of this process, using the same scheme
+    /// as libnl: the lower 22 bits hold the process ID and the upper 10 bits hold the offset.
+    /// Offset `0` is the process ID itself, which is also the first port ID the kernel picks
+    /// when binding with `nl_pid` set to 0, so start from `1` when mixing both.
+    ///
+    /// Returns `None` if `offset` does not fit in 10 bits.
+    pub fn local_port_id(offset: u32) -> Option<u32> {
+        if offset >= 1 << 10 {
+            return None;
+        }
+        Some((std::process::id() & 0x3f_ffff) | (offset << 22))
+    }
+
+    /// Equivalent of `socket` and `bind_port` calls.
+    pub fn connect_port(
+        proto: NlFamily,
+        port: u32,
+        groups: Option<Vec<u32>>,
+        track_seq: bool,
+    ) -> Result<Self, io::Error> {
+        let mut s = NlSocket::new(proto, track_seq)?;
+        s.bind_port(port, groups)?;
+        Ok(s)
+    }
+
+    /// Join the multicast groups with the given IDs with `NETLINK_ADD_MEMBERSHIP`
+    ///
+    /// Any group ID is accepted, including IDs above 32 assigned dynamically by generic netlink.
+    /// PID checking is turned off as multicast messages are not addressed to the socket.
+    pub fn add_mcast_membership(&mut self, groups: &[u32]) -> Result<(), io::Error> {
+        for group in groups {
+            match unsafe {
+                libc::setsockopt(
+                    self.fd,
+                    libc::SOL_NETLINK,
+                    NlSockOpt::AddMembership.into(),
+                    group as *const _ as *const libc::c_void,
+                    size_of::<u32>() as libc::socklen_t,
+                )
+            } {
+                0 => (),
+                _ => return Err(io::Error::last_os_error()),
+            }
         }
+        self.pid = None;
         Ok(())
     }
 
     /// Set multicast groups for socket
+    ///
+    /// The groups are combined into a bitmask so only groups 1 through 32 can be joined and
+    /// joining more than one group at a time does not work as the kernel expects a single group
+    /// ID.
+    #[deprecated(note = "use add_mcast_membership, which accepts any group ID")]
     pub fn set_mcast_groups(&mut self, groups: Vec<u32>) -> Result<(), io::Error> {
         let grps = groups
             .into_iter()
@@ -189,12 +767,12 @@ impl NlSocket {
             libc::setsockopt(
                 self.fd,
                 libc::SOL_NETLINK,
-                libc::NETLINK_ADD_MEMBERSHIP,
+                NlSockOpt::AddMembership.into(),
                 &grps as *const _ as *const libc::c_void,
                 size_of::<u32>() as libc::socklen_t,
             )
         } {
-            i if i == 0 => {
+            0 => {
                 self.pid = None;
                 Ok(())
             }
@@ -202,491 +780,3840 @@ impl NlSocket {
         }
     }
 
-    /// Send message encoded as byte slice to the netlink ID specified in the netlink header
-    /// (`neli::nl::Nlmsghdr`)
-    pub fn send<B>(&self, buf: B, flags: i32) -> Result<libc::size_t, io::Error>
-    where
-        B: AsRef<[u8]>,
-    {
+    /// Leave the multicast groups with the given IDs with `NETLINK_DROP_MEMBERSHIP`
+    pub fn drop_mcast_membership(&mut self, groups: &[u32]) -> Result<(), io::Error> {
+        for group in groups {
+            match unsafe {
+                libc::setsockopt(
+                    self.fd,
+                    libc::SOL_NETLINK,
+                    NlSockOpt::DropMembership.into(),
+                    group as *const _ as *const libc::c_void,
+                    size_of::<u32>() as libc::socklen_t,
+                )
+            } {
+                0 => (),
+                _ => return Err(io::Error::last_os_error()),
+            }
+        }
+        Ok(())
+    }
+
+    /// List the IDs of the multicast groups the socket is a member of with
+    /// `NETLINK_LIST_MEMBERSHIPS`
+    pub fn list_mcast_memberships(&self) -> Result<Vec<u32>, io::Error> {
+        // The kernel returns a bitmask of groups in 32 bit words and reports the length needed
+        // for all of them if the buffer is too short
+        let mut words = vec![0u32; 1];
+        loop {
+            let mut len = (words.len() * size_of::<u32>()) as libc::socklen_t;
+            match unsafe {
+                libc::getsockopt(
+                    self.fd,
+                    libc::SOL_NETLINK,
+                    NlSockOpt::ListMemberships.into(),
+                    words.as_mut_ptr() as *mut libc::c_void,
+                    &mut len,
+                )
+            } {
+                0 => (),
+                _ => return Err(io::Error::last_os_error()),
+            }
+            let needed = len as usize / size_of::<u32>();
+            if needed <= words.len() {
+                words.truncate(needed);
+                break;
+            }
+            words = vec![0; needed];
+        }
+        let mut groups = Vec::new();
+        for (i, word) in words.iter().enumerate() {
+            for bit in 0..32 {
+                if word & (1 << bit) != 0 {
+                    groups.push(i as u32 * 32 + bit + 1);
+                }
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Receive the multicast group of each message with `NETLINK_PKTINFO` - see
+    /// `recv_nl_with_info`
+    pub fn set_pktinfo(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_netlink_opt(NlSockOpt::PktInfo, enable)
+    }
+
+    /// Receive multicast messages from all network namespaces that have an ID assigned in the
+    /// namespace of the socket with `NETLINK_LISTEN_ALL_NSID`
+    ///
+    /// The namespace each message was sent from is returned by `recv_nl_with_info`. Enabling
+    /// this option requires `CAP_NET_BROADCAST`.
+    pub fn set_listen_all_nsid(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_netlink_opt(NlSockOpt::ListenAllNsid, enable)
+    }
+
+    /// Request extended ACK information from the kernel with `NETLINK_EXT_ACK`
+    ///
+    /// When enabled, `recv_ack` returns `NlError::ExtAck` with the error message and offset of
+    /// the offending attribute if the kernel provided them. `Nlmsgerr::ext_ack` parses the
+    /// information from errors received with `recv_nl`.
+    pub fn set_ext_ack(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_netlink_opt(NlSockOpt::ExtAck, enable)
+    }
+
+    /// Only echo the header of a failed request in error messages with `NETLINK_CAP_ACK`
+    /// instead of the whole request
+    ///
+    /// Errors received with this option set `NLM_F_CAPPED` and `Nlmsgerr::request_payload` is
+    /// empty.
+    pub fn set_cap_ack(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_netlink_opt(NlSockOpt::CapAck, enable)
+    }
+
+    /// Memory usage of the socket buffers and number of dropped messages with `SO_MEMINFO`
+    ///
+    /// Comparing `rmem_alloc` to `rcvbuf` shows how close a subscriber is to losing messages
+    /// before receiving fails with `NlError::Overrun`.
+    pub fn stats(&self) -> Result<NlSocketStats, io::Error> {
+        let mut meminfo = [0u32; libc::SK_MEMINFO_DROPS as usize + 1];
+        let mut len = size_of_val(&meminfo) as libc::socklen_t;
         match unsafe {
-            libc::send(
+            libc::getsockopt(
                 self.fd,
-                buf.as_ref() as *const _ as *const c_void,
-                buf.as_ref().len(),
-                flags,
+                libc::SOL_SOCKET,
+                libc::SO_MEMINFO,
+                meminfo.as_mut_ptr() as *mut libc::c_void,
+                &mut len,
             )
         } {
-            i if i >= 0 => Ok(i as libc::size_t),
-            _ => Err(io::Error::last_os_error()),
+            0 => (),
+            _ => return Err(io::Error::last_os_error()),
         }
+        Ok(NlSocketStats {
+            rmem_alloc: meminfo[libc::SK_MEMINFO_RMEM_ALLOC as usize],
+            rcvbuf: meminfo[libc::SK_MEMINFO_RCVBUF as usize],
+            wmem_alloc: meminfo[libc::SK_MEMINFO_WMEM_ALLOC as usize],
+            sndbuf: meminfo[libc::SK_MEMINFO_SNDBUF as usize],
+            drops: meminfo[libc::SK_MEMINFO_DROPS as usize],
+        })
     }
 
-    /// Receive message encoded as byte slice from the netlink socket
-    pub fn recv<B>(&self, mut buf: B, flags: i32) -> Result<libc::size_t, io::Error>
-    where
-        B: AsMut<[u8]>,
-    {
+    /// Get and clear the pending error of the socket with `SO_ERROR`
+    ///
+    /// A pending `ENOBUFS` means that messages were dropped. It is cleared by this call so the
+    /// next receive does not fail with `NlError::Overrun`.
+    pub fn take_error(&self) -> Result<Option<io::Error>, io::Error> {
+        let mut error: c_int = 0;
+        let mut len = size_of::<c_int>() as libc::socklen_t;
         match unsafe {
-            libc::recv(
+            libc::getsockopt(
                 self.fd,
-                buf.as_mut() as *mut _ as *mut c_void,
-                buf.as_mut().len(),
-                flags,
+                libc::SOL_SOCKET,
+                libc::SO_ERROR,
+                &mut error as *mut _ as *mut libc::c_void,
+                &mut len,
             )
         } {
-            i if i >= 0 => Ok(i as libc::size_t),
-            _ => Err(io::Error::last_os_error()),
+            0 => (),
+            _ => return Err(io::Error::last_os_error()),
         }
+        Ok(match error {
+            0 => None,
+            e => Some(io::Error::from_raw_os_error(e)),
+        })
     }
 
-    /// Equivalent of `socket` and `bind` calls.
-    pub fn connect(
-        proto: NlFamily,
-        pid: Option<u32>,
-        groups: Option<Vec<u32>>,
-        track_seq: bool,
-    ) -> Result<Self, io::Error> {
-        let mut s = NlSocket::new(proto, track_seq)?;
-        s.bind(pid, groups)?;
-        Ok(s)
+    /// Report failures to deliver multicast messages to this socket with
+    /// `NETLINK_BROADCAST_ERROR`
+    ///
+    /// By default, a multicast message that cannot be delivered to a subscribed socket, for
+    /// example because its receive buffer is full, is dropped without the broadcaster being
+    /// told. With this option enabled, the failure is returned to the broadcaster so a sender
+    /// that checks the result, such as a kernel subsystem using `nlmsg_multicast`, can react to
+    /// it. Errors seen by this socket are returned as `NlError::Errno`.
+    pub fn set_broadcast_error(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_netlink_opt(NlSockOpt::BroadcastError, enable)
     }
 
-    fn get_genl_family<T>(
-        &mut self,
-        family_name: &str,
-    ) -> Result<Nlmsghdr<GenlId, Genlmsghdr<CtrlCmd, T>>, NlError>
+    /// Suppress `ENOBUFS` errors with `NETLINK_NO_ENOBUFS`
+    ///
+    /// When the receive buffer of a socket overflows, the kernel drops messages and by default
+    /// reports the loss by failing the next receive with `ENOBUFS` so that the application can
+    /// resynchronize its state, for example with a new dump. With this option enabled, messages
+    /// are still dropped but the loss is not reported. Only enable it if missing multicast
+    /// notifications is acceptable, as the socket has no other way to detect the gap.
+    ///
+    /// This trades correctness for simplicity: an application that keeps a cache of kernel state,
+    /// such as the list of links or routes, is never told that the notifications for a change
+    /// were dropped, so the cache silently becomes stale until it is refreshed with a new dump.
+    pub fn set_no_enobufs(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_netlink_opt(NlSockOpt::NoEnobufs, enable)
+    }
+
+    /// Check whether `ENOBUFS` errors are suppressed - see `set_no_enobufs`
+    pub fn no_enobufs(&self) -> Result<bool, io::Error> {
+        self.get_netlink_opt(NlSockOpt::NoEnobufs)
+    }
+
+    /// Recover from overflows of the receive buffer with `handler`
+    ///
+    /// Without a handler, receiving fails with `NlError::Overrun` after the kernel dropped
+    /// messages. With a handler, the handler is called with the socket instead, for example to
+    /// request a new dump of the state the dropped notifications described, and receiving
+    /// continues with the next message if it succeeds. An error returned by the handler is
+    /// returned by the receive call. Overruns while the handler runs are returned as
+    /// `NlError::Overrun` to the handler.
+    ///
+    /// The handler is not shared with handles created by `try_clone`.
+    pub fn set_overrun_handler<F>(&mut self, handler: F)
     where
-        T: NlAttrType + Debug,
+        F: FnMut(&mut NlSocket) -> Result<(), NlError> + Send + Sync + 'static,
     {
-        let attrs = vec![Nlattr::new(None, CtrlAttr::FamilyName, family_name)?];
-        let genlhdr = Genlmsghdr::new(CtrlCmd::Getfamily, 2, attrs)?;
-        let nlhdr = Nlmsghdr::new(
-            None,
-            GenlId::Ctrl,
-            vec![NlmF::Request, NlmF::Ack],
-            None,
-            None,
-            genlhdr,
-        );
-        self.send_nl(nlhdr)?;
+        self.overrun_handler = Some(Box::new(handler));
+    }
+
+    /// Remove the handler set with `set_overrun_handler`
+    pub fn clear_overrun_handler(&mut self) {
+        self.overrun_handler = None;
+    }
+
+    /// Retry `send_and_await_ack` and `dump_consistent` according to `policy` when they fail
+    /// with a transient error
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = Some(policy);
+    }
+
+    /// Return errors of `send_and_await_ack` and `dump_consistent` without retrying - this is
+    /// the default
+    pub fn clear_retry_policy(&mut self) {
+        self.retry_policy = None;
+    }
+
+    // Run `op` again after the failures the retry policy considers transient.
+    fn with_retries<R, F>(&mut self, mut op: F) -> Result<R, NlError>
+    where
+        F: FnMut(&mut NlSocket) -> Result<R, NlError>,
+    {
+        let policy = match self.retry_policy {
+            Some(ref policy) => policy.clone(),
+            None => return op(self),
+        };
+        let mut attempt = 0;
+        loop {
+            match op(self) {
+                Err(ref e) if attempt + 1 < policy.max_attempts && policy.is_retryable(e) => {
+                    #[cfg(feature = "logging")]
+                    log!("Retrying request after error: {}", e);
+                    thread::sleep(policy.delay(attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Enable strict checking of requests with `NETLINK_GET_STRICT_CHK`
+    ///
+    /// The kernel rejects dump requests with a truncated header, non-zero header fields that are
+    /// not used as filters, or unknown attributes instead of ignoring them. `Ifinfomsg::dump`,
+    /// `Ifaddrmsg::dump`, and `Rtmsg::dump` build requests that are valid in this mode.
+    pub fn set_strict_chk(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_netlink_opt(NlSockOpt::GetStrictChk, enable)
+    }
+
+    fn set_netlink_opt(&mut self, opt: NlSockOpt, enable: bool) -> Result<(), io::Error> {
+        let value = enable as c_int;
+        match unsafe {
+            libc::setsockopt(
+                self.fd,
+                libc::SOL_NETLINK,
+                opt.into(),
+                &value as *const _ as *const libc::c_void,
+                size_of::<c_int>() as libc::socklen_t,
+            )
+        } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    fn get_netlink_opt(&self, opt: NlSockOpt) -> Result<bool, io::Error> {
+        let mut value: c_int = 0;
+        let mut len = size_of::<c_int>() as libc::socklen_t;
+        match unsafe {
+            libc::getsockopt(
+                self.fd,
+                libc::SOL_NETLINK,
+                opt.into(),
+                &mut value as *mut _ as *mut libc::c_void,
+                &mut len,
+            )
+        } {
+            0 => Ok(value != 0),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Set the timeout for receiving on a blocking socket - `None` blocks indefinitely
+    ///
+    /// `recv_nl`, `recv_ack`, and `iter` return `NlError::Timeout` when the timeout expires.
+    /// A zero duration is rejected as it cannot be distinguished from no timeout.
+    pub fn set_recv_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error> {
+        self.set_timeout(libc::SO_RCVTIMEO, timeout)
+    }
+
+    /// Set the timeout for sending on a blocking socket - `None` blocks indefinitely
+    ///
+    /// `send_nl` returns `NlError::Timeout` when the timeout expires. A zero duration is rejected
+    /// as it cannot be distinguished from no timeout.
+    pub fn set_send_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error> {
+        self.set_timeout(libc::SO_SNDTIMEO, timeout)
+    }
+
+    fn set_timeout(&mut self, opt: c_int, timeout: Option<Duration>) -> Result<(), io::Error> {
+        let tv = match timeout {
+            Some(d) if d == Duration::from_secs(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Cannot set a zero duration timeout",
+                ));
+            }
+            Some(d) => {
+                let mut tv = libc::timeval {
+                    tv_sec: d.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+                    tv_usec: d.subsec_micros() as libc::suseconds_t,
+                };
+                // Round durations shorter than the resolution of timeval up instead of to zero
+                if tv.tv_sec == 0 && tv.tv_usec == 0 {
+                    tv.tv_usec = 1;
+                }
+                tv
+            }
+            None => libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+        };
+        match unsafe {
+            libc::setsockopt(
+                self.fd,
+                libc::SOL_SOCKET,
+                opt,
+                &tv as *const _ as *const libc::c_void,
+                size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    // A blocking socket only fails with EAGAIN when SO_RCVTIMEO or SO_SNDTIMEO expires.
+    pub(crate) fn io_to_nl_error(&self, e: io::Error) -> NlError {
+        if e.kind() == io::ErrorKind::WouldBlock && self.is_blocking().unwrap_or(false) {
+            NlError::Timeout
+        } else {
+            NlError::from(e)
+        }
+    }
+
+    // Receiving fails with ENOBUFS once after messages were dropped.
+    pub(crate) fn recv_to_nl_error(&self, e: io::Error) -> NlError {
+        if e.raw_os_error() == Some(libc::ENOBUFS) {
+            NlError::Overrun
+        } else {
+            self.io_to_nl_error(e)
+        }
+    }
+
+    /// Send message encoded as byte slice to the netlink ID specified in the netlink header
+    /// (`neli::nl::Nlmsghdr`)
+    pub fn send<B>(&self, buf: B, flags: i32) -> Result<libc::size_t, io::Error>
+    where
+        B: AsRef<[u8]>,
+    {
+        match unsafe {
+            libc::send(
+                self.fd,
+                buf.as_ref() as *const _ as *const c_void,
+                buf.as_ref().len(),
+                flags,
+            )
+        } {
+            i if i >= 0 => Ok(i as libc::size_t),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Send message encoded as byte slice to the socket bound to `port` and to the members of the
+    /// multicast groups in the bitmask `groups` with `sendto()`
+    ///
+    /// A `port` of 0 addresses the kernel, or no socket if `groups` is not empty. Sending to
+    /// multicast groups from userspace is only allowed for some protocols, such as
+    /// `NlFamily::Usersock`, and requires `CAP_NET_ADMIN`.
+    pub fn send_to<B>(
+        &self,
+        buf: B,
+        port: u32,
+        groups: u32,
+        flags: i32,
+    ) -> Result<libc::size_t, io::Error>
+    where
+        B: AsRef<[u8]>,
+    {
+        let mut addr = unsafe { zeroed::<libc::sockaddr_nl>() };
+        addr.nl_family = libc::AF_NETLINK as u16;
+        addr.nl_pid = port;
+        addr.nl_groups = groups;
+        match unsafe {
+            libc::sendto(
+                self.fd,
+                buf.as_ref() as *const _ as *const c_void,
+                buf.as_ref().len(),
+                flags,
+                &addr as *const _ as *const libc::sockaddr,
+                size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        } {
+            i if i >= 0 => Ok(i as libc::size_t),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Receive message encoded as byte slice from the netlink socket
+    pub fn recv<B>(&self, mut buf: B, flags: i32) -> Result<libc::size_t, io::Error>
+    where
+        B: AsMut<[u8]>,
+    {
+        match unsafe {
+            libc::recv(
+                self.fd,
+                buf.as_mut() as *mut _ as *mut c_void,
+                buf.as_mut().len(),
+                flags,
+            )
+        } {
+            i if i >= 0 => Ok(i as libc::size_t),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Length of the next pending datagram without removing it from the socket, using
+    /// `MSG_PEEK | MSG_TRUNC`
+    pub fn peek_len(&self) -> Result<libc::size_t, io::Error> {
+        self.recv([0u8; 0], libc::MSG_PEEK | libc::MSG_TRUNC)
+    }
+
+    // Receive a datagram with recvmsg and record the ancillary data sent with it.
+    fn recv_with_info(&mut self, buf: &mut [u8]) -> Result<libc::size_t, io::Error> {
+        let (read, info) = self.recv_vectored(&mut [io::IoSliceMut::new(buf)], libc::MSG_TRUNC)?;
+        self.recv_info = info;
+        Ok(read)
+    }
+
+    /// Wrapper around `recvmsg()` reading one datagram into several buffers in order
+    ///
+    /// This allows reading headers and payloads into separate buffers in one system call. The
+    /// ancillary data sent with the datagram is returned with the number of bytes read.
+    pub fn recv_vectored(
+        &self,
+        bufs: &mut [io::IoSliceMut<'_>],
+        flags: i32,
+    ) -> Result<(libc::size_t, NlRecvInfo), io::Error> {
+        // u64 elements keep the control buffer aligned for cmsghdr
+        let mut control = [0u64; 8];
+        let mut addr = unsafe { zeroed::<libc::sockaddr_nl>() };
+        let mut msg = unsafe { zeroed::<libc::msghdr>() };
+        msg.msg_name = &mut addr as *mut _ as *mut c_void;
+        msg.msg_namelen = size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+        // IoSliceMut is guaranteed to be ABI compatible with iovec on Unix
+        msg.msg_iov = bufs.as_mut_ptr() as *mut libc::iovec;
+        msg.msg_iovlen = bufs.len() as _;
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = size_of_val(&control) as _;
+        let read = match unsafe { libc::recvmsg(self.fd, &mut msg, flags) } {
+            i if i >= 0 => i as libc::size_t,
+            _ => return Err(io::Error::last_os_error()),
+        };
 
-        let msg = self.recv_nl(None)?;
+        let mut info = NlRecvInfo {
+            sender: NlAddr {
+                pid: addr.nl_pid,
+                groups: addr.nl_groups,
+            },
+            truncated: msg.msg_flags & libc::MSG_TRUNC != 0,
+            ..Default::default()
+        };
+        info.read_cmsgs(&msg);
+        Ok((read, info))
+    }
+
+    /// Equivalent of `socket` and `bind` calls.
+    pub fn connect(
+        proto: NlFamily,
+        pid: Option<u32>,
+        groups: Option<Vec<u32>>,
+        track_seq: bool,
+    ) -> Result<Self, io::Error> {
+        let mut s = NlSocket::new(proto, track_seq)?;
+        s.bind(pid, groups)?;
+        Ok(s)
+    }
+
+    /// Create a socket in the network namespace at the path `netns`, for example
+    /// `/var/run/netns/<name>` or `/proc/<pid>/ns/net`, and bind it - see `connect_in_netns_fd`
+    pub fn connect_in_netns<N>(
+        netns: N,
+        proto: NlFamily,
+        pid: Option<u32>,
+        groups: Option<Vec<u32>>,
+        track_seq: bool,
+    ) -> Result<Self, io::Error>
+    where
+        N: AsRef<Path>,
+    {
+        let netns = File::open(netns)?;
+        NlSocket::connect_in_netns_fd(netns.as_fd(), proto, pid, groups, track_seq)
+    }
+
+    /// Create a socket in the network namespace referred to by the file descriptor `netns` and
+    /// bind it
+    ///
+    /// The calling thread enters the namespace with `setns()` only while the socket is created
+    /// and then returns to its original namespace. The socket stays in the namespace it was
+    /// created in, so requests sent over it manage the interfaces, routes, etc. of that
+    /// namespace. Entering a network namespace requires `CAP_SYS_ADMIN`.
+    pub fn connect_in_netns_fd(
+        netns: BorrowedFd<'_>,
+        proto: NlFamily,
+        pid: Option<u32>,
+        groups: Option<Vec<u32>>,
+        track_seq: bool,
+    ) -> Result<Self, io::Error> {
+        let original = File::open("/proc/thread-self/ns/net")?;
+        if unsafe { libc::setns(netns.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = NlSocket::new(proto, track_seq);
+        if unsafe { libc::setns(original.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut socket = socket?;
+        socket.bind(pid, groups)?;
+        Ok(socket)
+    }
+
+    fn request_genl_family(&mut self, family_name: &str) -> Result<GenlFamily, NlError> {
+        let attrs = vec![Nlattr::new(None, CtrlAttr::FamilyName, family_name)?];
+        let genlhdr = Genlmsghdr::new(CtrlCmd::Getfamily, 2, attrs)?;
+        self.send_nl(Nlmsghdr::ack_request(GenlId::Ctrl, genlhdr))?;
+
+        // Unknown families are reported with an error in place of the reply
+        let nl_type = self.peek_nl_header()?.nl_type;
+        if nl_type == u16::from(consts::Nlmsg::Error) {
+            self.recv_ack()?;
+            return Err(NlError::new("Failed to resolve generic netlink family"));
+        }
+        let msg = self.recv_nl::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>(None)?;
         self.recv_ack()?;
-        Ok(msg)
+        GenlFamily::parse(&msg.nl_payload)
+    }
+
+    /// Request the description of a generic netlink family by name
+    ///
+    /// The result is cached if `set_genl_cache` is enabled.
+    pub fn get_genl_family(&mut self, family_name: &str) -> Result<GenlFamily, NlError> {
+        if let Some(ref mut cache) = self.genl_cache {
+            cache.process_notifications();
+            if let Some(family) = cache.families.get(family_name) {
+                return Ok(family.clone());
+            }
+        }
+
+        let family = self.request_genl_family(family_name)?;
+        if let Some(ref mut cache) = self.genl_cache {
+            cache
+                .families
+                .insert(family_name.to_string(), family.clone());
+        }
+        Ok(family)
+    }
+
+    /// Request the descriptions of all registered generic netlink families
+    ///
+    /// This is the equivalent of `genl ctrl list`. The families are added to the cache if
+    /// `set_genl_cache` is enabled.
+    pub fn get_all_genl_families(&mut self) -> Result<Vec<GenlFamily>, NlError> {
+        let genlhdr = Genlmsghdr::<CtrlCmd, CtrlAttr>::new(CtrlCmd::Getfamily, 2, Vec::new())?;
+        let nlhdr = Nlmsghdr::new(None, GenlId::Ctrl, Vec::new(), None, None, genlhdr);
+        let families = self
+            .dump::<_, _, Genlmsghdr<CtrlCmd, CtrlAttr>>(nlhdr)?
+            .map(|genl| GenlFamily::parse(&genl?))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(ref mut cache) = self.genl_cache {
+            cache.process_notifications();
+            for family in families.iter() {
+                cache.families.insert(family.name.clone(), family.clone());
+            }
+        }
+        Ok(families)
+    }
+
+    /// Cache the generic netlink families resolved with `resolve_genl_family` and
+    /// `resolve_nl_mcast_group` on this socket
+    ///
+    /// Families are removed from the cache when the kernel announces that they were
+    /// unregistered, for example because the module providing them was unloaded. The
+    /// announcements are received on a separate socket subscribed to the `notify` group of the
+    /// `nlctrl` family so they do not show up among the messages received on this socket. The
+    /// cache is not shared with handles created by `try_clone`.
+    pub fn set_genl_cache(&mut self, enable: bool) -> Result<(), NlError> {
+        if !enable {
+            self.genl_cache = None;
+        } else if self.genl_cache.is_none() {
+            let mut notify = NlSocket::connect(NlFamily::Generic, None, None, false)?;
+            let group = notify.resolve_nl_mcast_group("nlctrl", "notify")?;
+            notify.add_mcast_membership(&[group])?;
+            notify.nonblock()?;
+            self.genl_cache = Some(Box::new(GenlCache {
+                notify,
+                families: HashMap::new(),
+            }));
+        }
+        Ok(())
     }
 
     /// Convenience function for resolving a `&str` containing the multicast group name to a
     /// numeric netlink ID
+    ///
+    /// The result is cached if `set_genl_cache` is enabled.
     pub fn resolve_genl_family(&mut self, family_name: &str) -> Result<u16, NlError> {
-        let nlhdr = self.get_genl_family(family_name)?;
-        let handle = nlhdr.nl_payload.get_attr_handle();
-        Ok(handle.get_attr_payload_as::<u16>(CtrlAttr::FamilyId)?)
+        Ok(self.get_genl_family(family_name)?.id)
     }
 
     /// Convenience function for resolving a `&str` containing the multicast group name to a
     /// numeric netlink ID
+    ///
+    /// The result is cached if `set_genl_cache` is enabled.
     pub fn resolve_nl_mcast_group(
         &mut self,
         family_name: &str,
         mcast_name: &str,
     ) -> Result<u32, NlError> {
-        let nlhdr = self.get_genl_family(family_name)?;
-        let mut handle = nlhdr.nl_payload.get_attr_handle();
-        let mcast_groups =
-            handle.get_nested_attributes::<CtrlAttrMcastGrp>(CtrlAttr::McastGroups)?;
-        mcast_groups
-            .iter()
-            .filter_map(|item| {
-                let nested_attrs = item.get_nested_attributes::<CtrlAttrMcastGrp>().ok()?;
-                let string = nested_attrs
-                    .get_attr_payload_as::<String>(CtrlAttrMcastGrp::Name)
-                    .ok()?;
-                if string.as_str() == mcast_name {
-                    nested_attrs
-                        .get_attr_payload_as::<u32>(CtrlAttrMcastGrp::Id)
-                        .ok()
-                } else {
-                    None
-                }
-            })
-            .next()
+        self.get_genl_family(family_name)?
+            .mcast_group(mcast_name)
             .ok_or_else(|| NlError::new("Failed to resolve multicast group ID"))
     }
 
+    /// List all network interfaces on a `NlFamily::Route` socket (equivalent to `ip link show`)
+    ///
+    /// The dump is retried according to `set_retry_policy` if it is interrupted.
+    pub fn get_links(&mut self) -> Result<Vec<Ifinfomsg>, NlError> {
+        let request = Nlmsghdr::new(
+            None,
+            Rtm::Getlink,
+            Vec::new(),
+            None,
+            None,
+            Ifinfomsg::dump(RtAddrFamily::Unspecified),
+        );
+        self.dump_consistent(request, 0)
+    }
+
+    /// Dump the addresses of all interfaces in the address family `family`, or of every family
+    /// with `RtAddrFamily::Unspecified`
+    ///
+    /// The dump is restarted if it is interrupted by a concurrent change to the addresses.
+    pub fn get_addrs(&mut self, family: RtAddrFamily) -> Result<Vec<Ifaddrmsg>, NlError> {
+        let request = Nlmsghdr::new(
+            None,
+            Rtm::Getaddr,
+            Vec::new(),
+            None,
+            None,
+            Ifaddrmsg::dump(family),
+        );
+        self.dump_consistent(request, 0)
+    }
+
+    /// Dump the neighbor table entries, such as ARP or NDP cache entries, in the address family
+    /// `family`, or of every family with `RtAddrFamily::Unspecified`
+    ///
+    /// Join `Rtnlgrp::Neigh` to be notified of changes to the neighbor tables.
+    pub fn get_neighbors(&mut self, family: RtAddrFamily) -> Result<Vec<Ndmsg>, NlError> {
+        let request = Nlmsghdr::new(
+            None,
+            Rtm::Getneigh,
+            Vec::new(),
+            None,
+            None,
+            Ndmsg::dump(family),
+        );
+        self.dump_consistent(request, 0)
+    }
+
+    /// Dump the queuing disciplines of every interface
+    ///
+    /// Join `Rtnlgrp::Tc` to be notified of changes to queuing disciplines, classes, and
+    /// filters.
+    pub fn get_qdiscs(&mut self) -> Result<Vec<Tcmsg>, NlError> {
+        let request = Nlmsghdr::new(None, Rtm::Getqdisc, Vec::new(), None, None, Tcmsg::dump(0));
+        self.dump_consistent(request, 0)
+    }
+
+    /// Dump the traffic control classes of the interface with index `ifindex`
+    pub fn get_tc_classes(&mut self, ifindex: i32) -> Result<Vec<Tcmsg>, NlError> {
+        let request = Nlmsghdr::new(
+            None,
+            Rtm::Gettclass,
+            Vec::new(),
+            None,
+            None,
+            Tcmsg::dump(ifindex),
+        );
+        self.dump_consistent(request, 0)
+    }
+
+    /// Dump the traffic control filters attached to the queuing discipline or class `parent` of
+    /// the interface with index `ifindex`, such as `TcHandle::ROOT` for the root queuing
+    /// discipline
+    pub fn get_tc_filters(
+        &mut self,
+        ifindex: i32,
+        parent: TcHandle,
+    ) -> Result<Vec<Tcmsg>, NlError> {
+        let mut tcmsg = Tcmsg::dump(ifindex);
+        tcmsg.tcm_parent = parent.into();
+        let request = Nlmsghdr::new(None, Rtm::Gettfilter, Vec::new(), None, None, tcmsg);
+        self.dump_consistent(request, 0)
+    }
+
+    /// Dump the routes in the address family `family` and return an iterator over the routes in
+    /// the routing table `table`, such as `RtTable::Main.into()`, or in every table if `table`
+    /// is `RtTable::Unspec.into()`
+    ///
+    /// Routes are filtered by the socket as the kernel only filters dumps by table for sockets
+    /// with `NlSocket::set_strict_chk` enabled. A route with a malformed table attribute is
+    /// returned as an error.
+    pub fn iter_routes(
+        &mut self,
+        family: RtAddrFamily,
+        table: u32,
+    ) -> Result<impl Iterator<Item = Result<Rtmsg, NlError>> + '_, NlError> {
+        let request = Nlmsghdr::new(
+            None,
+            Rtm::Getroute,
+            Vec::new(),
+            None,
+            None,
+            Rtmsg::dump(family),
+        );
+        let routes = self.dump::<_, _, Rtmsg>(request)?;
+        Ok(routes.filter_map(move |route| NlSocket::route_in_table(route, table)))
+    }
+
+    // Keep `route` if it is in the routing table `table` or if `table` is `RtTable::Unspec`.
+    fn route_in_table(route: Result<Rtmsg, NlError>, table: u32) -> Option<Result<Rtmsg, NlError>> {
+        let route = match route {
+            Ok(route) if table != u32::from(RtTable::Unspec) => route,
+            route => return Some(route),
+        };
+        match route.table() {
+            Ok(id) if id == table => Some(Ok(route)),
+            Ok(_) => None,
+            Err(e) => Some(Err(NlError::from(e))),
+        }
+    }
+
     /// Convenience function to send an `Nlmsghdr` struct
-    pub fn send_nl<T, P>(&mut self, mut msg: Nlmsghdr<T, P>) -> Result<(), NlError>
+    ///
+    /// The buffer used to serialize the message is kept by the socket and reused for subsequent
+    /// messages so sending does not allocate once the buffer has grown to the size of the
+    /// largest message sent. Use `shrink_send_buffer` to release the memory.
+    pub fn send_nl<T, P>(&mut self, msg: Nlmsghdr<T, P>) -> Result<(), NlError>
     where
         T: Nl + NlType + Debug,
         P: Nl + Debug,
     {
-        if let Some(ref mut seq) = self.seq {
-            msg.nl_seq = *seq;
+        self.send_nl_dest(msg, None)
+    }
+
+    /// Send an `Nlmsghdr` struct to the socket bound to `port` and to the members of the
+    /// multicast groups in the bitmask `groups` - see `send_to`
+    ///
+    /// Together with the sender address returned by `recv_nl_from`, this allows
+    /// userspace processes to exchange netlink messages with each other.
+    pub fn send_nl_to<T, P>(
+        &mut self,
+        msg: Nlmsghdr<T, P>,
+        port: u32,
+        groups: u32,
+    ) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        self.send_nl_dest(msg, Some((port, groups)))
+    }
+
+    fn send_nl_dest<T, P>(
+        &mut self,
+        mut msg: Nlmsghdr<T, P>,
+        dest: Option<(u32, u32)>,
+    ) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        if let Some(seq) = self.seq {
+            msg.nl_seq = seq;
+        }
+
+        #[cfg(feature = "logging")]
+        log!("Message sent:\n{:#?}", msg);
+
+        self.send_buffer.clear();
+        msg.serialize(&mut StreamWriteBuffer::new_growable_ref(
+            &mut self.send_buffer,
+        ))?;
+        match dest {
+            Some((port, groups)) => self.send_to(&self.send_buffer, port, groups, 0),
+            None => self.send(&self.send_buffer, 0),
         }
+        .map_err(|e| self.io_to_nl_error(e))?;
+
+        if let Some(seq) = self.seq {
+            self.sent_seqs = Some((seq, 1));
+            self.seq = Some(seq.wrapping_add(1));
+        }
+
+        Ok(())
+    }
+
+    // Send a serialized message, writing the next sequence number into it if sequence numbers
+    // are tracked, and return the sequence number it was sent with.
+    fn send_serialized(&mut self, mem: &mut [u8]) -> Result<u32, NlError> {
+        if let Some(seq) = self.seq {
+            mem[8..12].copy_from_slice(&seq.to_ne_bytes());
+        }
+        self.send(&*mem, 0).map_err(|e| self.io_to_nl_error(e))?;
+        if let Some(seq) = self.seq {
+            self.sent_seqs = Some((seq, 1));
+            self.seq = Some(seq.wrapping_add(1));
+        }
+        Ok(u32::from_ne_bytes([mem[8], mem[9], mem[10], mem[11]]))
+    }
+
+    /// Send several `Nlmsghdr` structs back to back in a single datagram
+    ///
+    /// Many families process every message of a datagram in order and batching protocols such
+    /// as nftables require it. If sequence numbers are tracked, each message is assigned the
+    /// next sequence number.
+    pub fn send_all<T, P, I>(&mut self, msgs: I) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+        I: IntoIterator<Item = Nlmsghdr<T, P>>,
+    {
+        self.send_buffer.clear();
+        let mut mem = StreamWriteBuffer::new_growable_ref(&mut self.send_buffer);
+        let mut seq = self.seq;
+        let mut count = 0u32;
+        for mut msg in msgs {
+            if let Some(ref mut seq) = seq {
+                msg.nl_seq = *seq;
+                *seq = seq.wrapping_add(1);
+            }
+            count += 1;
+
+            #[cfg(feature = "logging")]
+            log!("Message sent:\n{:#?}", msg);
+
+            msg.serialize(&mut mem)?;
+        }
+        self.send(&self.send_buffer, 0)
+            .map_err(|e| self.io_to_nl_error(e))?;
+        if let Some(first) = self.seq {
+            self.sent_seqs = Some((first, count));
+        }
+        self.seq = seq;
+
+        Ok(())
+    }
+
+    /// Set how `recv_nl` handles payload bytes that are not parsed - the default is
+    /// `DeMode::Lenient`
+    pub fn set_de_mode(&mut self, mode: DeMode) {
+        self.de_mode = mode;
+    }
+
+    pub(crate) fn de_mode(&self) -> DeMode {
+        self.de_mode
+    }
+
+    /// Release the memory held by the buffer reused for serializing messages in `send_nl`
+    pub fn shrink_send_buffer(&mut self) {
+        self.send_buffer = Vec::new();
+    }
+
+    /// Convenience function to begin receiving a stream of `Nlmsghdr` structs
+    ///
+    /// If `buf_sz` is `None`, the buffer is sized to the pending datagram with `peek_len` so
+    /// large dumps are never truncated. Otherwise datagrams longer than `buf_sz` are truncated.
+    pub fn recv_nl<T, P>(&mut self, buf_sz: Option<usize>) -> Result<Nlmsghdr<T, P>, NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        self.fill_buffer(buf_sz)?;
+        let msg = match self.buffer {
+            Some(ref mut b) => match Nlmsghdr::deserialize_with_mode(b, self.de_mode) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    // A datagram that fails to parse cannot be resynchronized so drop the rest
+                    // of it
+                    self.buffer = None;
+                    return Err(NlError::from(e));
+                }
+            },
+            None => unreachable!(),
+        };
+
+        #[cfg(feature = "logging")]
+        log!("Message received:\n{:#?}", msg);
+
+        if self.buffer.as_ref().map(|b| b.at_end()).unwrap_or(false) {
+            self.buffer = None;
+        }
+        match self.pid {
+            // PID checking turned off.
+            None => (),
+            // No PID set yet, store the current one.
+            Some(0) => self.pid = Some(msg.nl_pid),
+            // PID check OK
+            Some(pid) if pid == msg.nl_pid => (),
+            // PID doesn't match
+            Some(_) => NlSocket::check_failed(self.pid_policy, NlError::BadPid)?,
+        }
+        if self.seq_policy != CheckPolicy::Off && !self.seq_matches(msg.nl_seq) {
+            NlSocket::check_failed(self.seq_policy, NlError::BadSeq)?;
+        }
+        Ok(msg)
+    }
+
+    // Receive the next datagram into the buffer if all messages of the last one have been read.
+    fn fill_buffer(&mut self, buf_sz: Option<usize>) -> Result<(), NlError> {
+        while self.buffer.is_none() {
+            let mem = match self.recv_datagram(buf_sz) {
+                Ok((mem, len)) if len > mem.len() => return Err(NlError::Truncated { len }),
+                Ok((mem, _)) => mem,
+                Err(ref e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                    match self.overrun_handler.take() {
+                        Some(mut handler) => {
+                            let resync = handler(self);
+                            self.overrun_handler = Some(handler);
+                            resync?;
+                            continue;
+                        }
+                        None => return Err(NlError::Overrun),
+                    }
+                }
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::Interrupted
+                        && self.interrupt_policy == InterruptPolicy::Retry =>
+                {
+                    continue
+                }
+                Err(e) => return Err(self.io_to_nl_error(e)),
+            };
+            if mem.is_empty() {
+                return Err(NlError::new("No data could be read from the socket"));
+            }
+            self.buffer = Some(StreamReadBuffer::new(mem));
+        }
+        Ok(())
+    }
+
+    // Receive the next datagram and return it with its length before it was truncated to fit
+    // the buffer.
+    fn recv_datagram(&mut self, buf_sz: Option<usize>) -> Result<(Vec<u8>, usize), io::Error> {
+        let len = match buf_sz {
+            Some(sz) if self.grow_recv_buffer => sz.max(self.peek_len()?),
+            Some(sz) => sz,
+            None => self.peek_len()?,
+        };
+        let mut mem = vec![0; len];
+        let datagram_len = self.recv_with_info(&mut mem)?;
+        mem.truncate(datagram_len);
+        Ok((mem, datagram_len))
+    }
+
+    // Header of the next message without consuming it.
+    fn peek_nl_header(&mut self) -> Result<RawHeader, NlError> {
+        self.fill_buffer(None)?;
+        let mut header = [0u8; NLMSG_HDRLEN];
+        match self.buffer {
+            Some(ref mut b) => {
+                io::Read::read_exact(b, &mut header)?;
+                b.rewind();
+            }
+            None => unreachable!(),
+        }
+        Ok(RawHeader::read(&header))
+    }
+
+    // Read and drop the rest of the response to the dump request with sequence number `seq`
+    // after it was abandoned because of an error, up to and including the message that ends it.
+    // Until then the kernel rejects new dump requests with EBUSY, and the stale messages would be
+    // read as the response to the next request.
+    fn drain_dump(&mut self, seq: u32) -> Result<(), NlError> {
+        loop {
+            let header = match self.peek_nl_header() {
+                Ok(header) => header,
+                // Dropped messages are reported once and the dump continues
+                Err(NlError::Overrun) => continue,
+                Err(e) => return Err(e),
+            };
+            match self.recv_nl::<u16, Vec<u8>>(None) {
+                Ok(_) | Err(NlError::BadSeq) | Err(NlError::BadPid) => (),
+                Err(e) => return Err(e),
+            }
+            let ends_dump = header.nl_type == u16::from(consts::Nlmsg::Done)
+                || header.nl_type == u16::from(consts::Nlmsg::Error)
+                || header.nl_flags & u16::from(NlmF::Multi) == 0;
+            if header.nl_seq == seq && ends_dump {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Receive all messages of the next datagram at once
+    ///
+    /// If a previous call to `recv_nl` left messages of a datagram unread, the remaining
+    /// messages of that datagram are returned instead.
+    pub fn recv_all_nl<T, P>(
+        &mut self,
+        buf_sz: Option<usize>,
+    ) -> Result<Vec<Nlmsghdr<T, P>>, NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        let mut msgs = vec![self.recv_nl(buf_sz)?];
+        while self.buffer.is_some() {
+            msgs.push(self.recv_nl(buf_sz)?);
+        }
+        Ok(msgs)
+    }
+
+    /// Receive a message with the ancillary information of the datagram it arrived in, such as
+    /// the multicast group if `set_pktinfo` is enabled
+    pub fn recv_nl_with_info<T, P>(
+        &mut self,
+        buf_sz: Option<usize>,
+    ) -> Result<(Nlmsghdr<T, P>, NlRecvInfo), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        let msg = self.recv_nl(buf_sz)?;
+        Ok((msg, self.recv_info.clone()))
+    }
+
+    /// Receive a message with the address of the socket that sent it, for example to tell
+    /// messages from the kernel apart from messages of userspace peers or to reply to a peer
+    /// with `send_nl_to`
+    pub fn recv_nl_from<T, P>(
+        &mut self,
+        buf_sz: Option<usize>,
+    ) -> Result<(Nlmsghdr<T, P>, NlAddr), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        let msg = self.recv_nl(buf_sz)?;
+        Ok((msg, self.recv_info.sender))
+    }
+
+    /// Send `msg` with `NLM_F_ACK` set and wait for the ACK or error answering it
+    ///
+    /// Replies and other messages received before the ACK are discarded. The PID of the ACK is
+    /// checked according to `set_pid_policy` and an ACK with a sequence number other than the
+    /// one of `msg` is returned as `NlError::BadSeq`. An error reported by the kernel is returned
+    /// as `NlError::Errno`, or as `NlError::ExtAck` if it has extended ACK information. Transient
+    /// errors are retried according to `set_retry_policy`.
+    pub fn send_and_await_ack<T, P>(&mut self, mut msg: Nlmsghdr<T, P>) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        add_flags(&mut msg, &[NlmF::Ack]);
+        #[cfg(feature = "logging")]
+        log!("Message sent:\n{:#?}", msg);
+        let mut mem = Vec::new();
+        msg.serialize(&mut StreamWriteBuffer::new_growable_ref(&mut mem))?;
+
+        self.with_retries(|socket| {
+            let seq = socket.send_serialized(&mut mem)?;
+            loop {
+                let nl_type = socket.peek_nl_header()?.nl_type;
+                if nl_type != u16::from(consts::Nlmsg::Error) {
+                    socket.recv_nl::<u16, Vec<u8>>(None)?;
+                    continue;
+                }
+                let ack = socket.recv_nl::<consts::Nlmsg, Nlmsgerr<consts::Nlmsg>>(None)?;
+                if ack.nl_seq != seq {
+                    return Err(NlError::BadSeq);
+                }
+                return match ack.nl_payload.error {
+                    0 => Ok(()),
+                    _ => Err(nlmsgerr_error(&ack)),
+                };
+            }
+        })
+    }
+
+    /// Consume an ACK and return an error if an ACK is not found
+    pub fn recv_ack(&mut self) -> Result<(), NlError> {
+        let received = self.recv_nl::<consts::Nlmsg, Nlmsgerr<consts::Nlmsg>>(None);
+        match received {
+            Err(NlError::WouldBlock) => return Err(NlError::WouldBlock),
+            Err(NlError::Timeout) => return Err(NlError::Timeout),
+            Err(NlError::BadPid) => return Err(NlError::BadPid),
+            Err(NlError::BadSeq) => return Err(NlError::BadSeq),
+            _ => (),
+        }
+        if let Ok(ack) = received {
+            if ack.nl_type == consts::Nlmsg::Error && ack.nl_payload.error == 0 {
+                // PID and sequence number checks done as part of recv_nl already
+                Ok(())
+            } else {
+                if let Some(b) = self.buffer.as_mut() {
+                    b.rewind()
+                }
+                if ack.nl_type == consts::Nlmsg::Error {
+                    Err(nlmsgerr_error(&ack))
+                } else {
+                    Err(NlError::NoAck)
+                }
+            }
+        } else {
+            Err(NlError::NoAck)
+        }
+    }
+
+    /// Return an iterator object
+    pub fn iter<T, P>(&mut self) -> NlMessageIter<'_, T, P>
+    where
+        T: NlType,
+        P: Nl,
+    {
+        NlMessageIter::new(self)
+    }
+
+    /// Return an iterator object that stops according to `behavior`
+    pub fn iter_with<T, P>(&mut self, behavior: IterationBehavior) -> NlMessageIter<'_, T, P>
+    where
+        T: NlType,
+        P: Nl,
+    {
+        NlMessageIter::with_behavior(self, behavior)
+    }
+
+    /// Send `request` as a dump request and return an iterator over the payloads of all messages
+    /// of the response
+    ///
+    /// `NLM_F_REQUEST` and `NLM_F_DUMP` are added to the flags of the request. The iterator stops
+    /// at the `NLMSG_DONE` message ending the dump and yields an error reported by the kernel,
+    /// either in place of the response or in the `NLMSG_DONE` payload, as its last item. A
+    /// response flagged with `NLM_F_DUMP_INTR` ends with `NlError::DumpInterrupted` - see
+    /// `dump_consistent` to retry the dump in that case.
+    pub fn dump<T, Q, P>(
+        &mut self,
+        mut request: Nlmsghdr<T, Q>,
+    ) -> Result<NlDumpIter<'_, T, P>, NlError>
+    where
+        T: Nl + NlType + Debug,
+        Q: Nl + Debug,
+        P: Nl,
+    {
+        add_flags(&mut request, &[NlmF::Request, NlmF::Dump]);
+        self.send_nl(request)?;
+        Ok(NlDumpIter {
+            msgs: self.iter_with(IterationBehavior::EndMultiOnDone),
+            reported_intr: false,
+        })
+    }
+
+    /// Send `request` as a dump request and collect the payloads of the response, re-issuing the
+    /// request up to `max_retries` times while the kernel flags the response with
+    /// `NLM_F_DUMP_INTR`
+    ///
+    /// Returns `NlError::DumpInterrupted` if the last attempt was still interrupted. Errors
+    /// including `NlError::DumpInterrupted` after `max_retries` are retried again according to
+    /// `set_retry_policy`.
+    pub fn dump_consistent<T, Q, P>(
+        &mut self,
+        mut request: Nlmsghdr<T, Q>,
+        max_retries: usize,
+    ) -> Result<Vec<P>, NlError>
+    where
+        T: Nl + NlType + Debug,
+        Q: Nl + Debug,
+        P: Nl + Debug,
+    {
+        add_flags(&mut request, &[NlmF::Request, NlmF::Dump]);
+        #[cfg(feature = "logging")]
+        log!("Message sent:\n{:#?}", request);
+        let mut mem = Vec::new();
+        request.serialize(&mut StreamWriteBuffer::new_growable_ref(&mut mem))?;
+
+        // Sequence number of a dump that could not be drained before the error was returned
+        let mut abandoned = None;
+        self.with_retries(|socket| {
+            if let Some(seq) = abandoned {
+                socket.drain_dump(seq)?;
+                abandoned = None;
+            }
+            let mut retries = 0;
+            loop {
+                let seq = socket.send_serialized(&mut mem)?;
+                let mut iter = NlDumpIter {
+                    msgs: socket.iter_with::<T, P>(IterationBehavior::EndMultiOnDone),
+                    reported_intr: false,
+                };
+                let payloads = (&mut iter).collect();
+                // Collecting stops at the first error, which may leave the rest of the dump
+                // queued on the socket
+                if !iter.msgs.finished {
+                    abandoned = Some(seq);
+                    socket.drain_dump(seq)?;
+                    abandoned = None;
+                }
+                match payloads {
+                    Err(NlError::DumpInterrupted) if retries < max_retries => retries += 1,
+                    payloads => return payloads,
+                }
+            }
+        })
+    }
+
+    /// Create a new handle for the same socket with `dup()`
+    ///
+    /// Both handles send and receive on the same port and share socket options and multicast
+    /// memberships, but each keeps its own receive buffer, PID checking and sequence number.
+    pub fn try_clone(&self) -> Result<Self, io::Error> {
+        let fd = match unsafe { libc::fcntl(self.fd, libc::F_DUPFD_CLOEXEC, 0) } {
+            i if i >= 0 => i,
+            _ => return Err(io::Error::last_os_error()),
+        };
+        Ok(NlSocket {
+            fd,
+            buffer: None,
+            send_buffer: Vec::new(),
+            pid: self.pid,
+            seq: self.seq,
+            de_mode: self.de_mode,
+            recv_info: NlRecvInfo::default(),
+            sent_seqs: self.sent_seqs,
+            pid_policy: self.pid_policy,
+            seq_policy: self.seq_policy,
+            overrun_handler: None,
+            grow_recv_buffer: self.grow_recv_buffer,
+            interrupt_policy: self.interrupt_policy,
+            genl_cache: None,
+            retry_policy: self.retry_policy.clone(),
+        })
+    }
+
+    /// Split the socket into a receiving half and a sending half that can be used from
+    /// different threads, for example to stream multicast events while issuing requests
+    ///
+    /// The halves are handles for the same socket created with `try_clone`. The receiving half
+    /// does not know which sequence numbers the sending half used so it does not validate
+    /// sequence numbers.
+    pub fn split(mut self) -> Result<(NlReadHalf, NlWriteHalf), io::Error> {
+        let writer = self.try_clone()?;
+        self.seq = None;
+        self.seq_policy = CheckPolicy::Off;
+        Ok((NlReadHalf { socket: self }, NlWriteHalf { socket: writer }))
+    }
+
+    /// Join the multicast groups `groups` and receive the messages sent to them on a background
+    /// thread, returning them parsed as messages of type `T` with payloads of type `P` over a
+    /// channel
+    ///
+    /// Errors are delivered in the channel in place of the message that caused them. The thread
+    /// keeps receiving after errors such as messages that fail to parse or
+    /// `NlError::Overrun`, and stops after an error reported by the operating system or when
+    /// the `NlSubscription` is dropped. PIDs and sequence numbers are not checked as
+    /// notifications are not replies to requests. A non-blocking socket is switched to blocking
+    /// mode.
+    pub fn subscribe<T, P>(mut self, groups: &[u32]) -> Result<NlSubscription<T, P>, io::Error>
+    where
+        T: Nl + NlType + Debug + Send + 'static,
+        P: Nl + Debug + Send + 'static,
+    {
+        self.add_mcast_membership(groups)?;
+        self.pid_policy = CheckPolicy::Off;
+        self.seq_policy = CheckPolicy::Off;
+        self.block()?;
+        // Wake up regularly to notice that the subscription was dropped
+        self.set_recv_timeout(Some(SUBSCRIPTION_POLL_INTERVAL))?;
+        self.set_interrupt_policy(InterruptPolicy::Retry);
+
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                let msg = match self.recv_nl(None) {
+                    Err(NlError::Timeout) => continue,
+                    msg => msg,
+                };
+                let fatal = matches!(msg, Err(NlError::Errno(_)));
+                if sender.send(msg).is_err() || fatal {
+                    break;
+                }
+            }
+        });
+        Ok(NlSubscription { receiver, stop })
+    }
+}
+
+const SUBSCRIPTION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Channel of multicast messages received by a background thread, created with
+/// `NlSocket::subscribe`
+///
+/// Dropping the subscription stops the thread and closes the socket.
+pub struct NlSubscription<T, P> {
+    receiver: mpsc::Receiver<Result<Nlmsghdr<T, P>, NlError>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl<T, P> NlSubscription<T, P> {
+    /// Block until the next message or error is received - `None` if the thread stopped
+    pub fn recv(&self) -> Option<Result<Nlmsghdr<T, P>, NlError>> {
+        self.receiver.recv().ok()
+    }
+
+    /// Return the next message or error if one was received already
+    pub fn try_recv(&self) -> Result<Result<Nlmsghdr<T, P>, NlError>, mpsc::TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// Block until the next message or error is received or `timeout` expires
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Result<Nlmsghdr<T, P>, NlError>, mpsc::RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+}
+
+impl<T, P> Iterator for NlSubscription<T, P> {
+    type Item = Result<Nlmsghdr<T, P>, NlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl<T, P> Drop for NlSubscription<T, P> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Receiving half of a socket split with `NlSocket::split`
+pub struct NlReadHalf {
+    socket: NlSocket,
+}
+
+impl NlReadHalf {
+    /// Receive a message - see `NlSocket::recv_nl`
+    pub fn recv_nl<T, P>(&mut self, buf_sz: Option<usize>) -> Result<Nlmsghdr<T, P>, NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        self.socket.recv_nl(buf_sz)
+    }
+
+    /// Receive all messages of the next datagram - see `NlSocket::recv_all_nl`
+    pub fn recv_all_nl<T, P>(
+        &mut self,
+        buf_sz: Option<usize>,
+    ) -> Result<Vec<Nlmsghdr<T, P>>, NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        self.socket.recv_all_nl(buf_sz)
+    }
+
+    /// Receive a message with the ancillary information of its datagram - see
+    /// `NlSocket::recv_nl_with_info`
+    pub fn recv_nl_with_info<T, P>(
+        &mut self,
+        buf_sz: Option<usize>,
+    ) -> Result<(Nlmsghdr<T, P>, NlRecvInfo), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        self.socket.recv_nl_with_info(buf_sz)
+    }
+
+    /// Receive a message with the address of its sender - see `NlSocket::recv_nl_from`
+    pub fn recv_nl_from<T, P>(
+        &mut self,
+        buf_sz: Option<usize>,
+    ) -> Result<(Nlmsghdr<T, P>, NlAddr), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        self.socket.recv_nl_from(buf_sz)
+    }
+
+    /// Consume an ACK - see `NlSocket::recv_ack`
+    pub fn recv_ack(&mut self) -> Result<(), NlError> {
+        self.socket.recv_ack()
+    }
+
+    /// Return an iterator object - see `NlSocket::iter`
+    pub fn iter<T, P>(&mut self) -> NlMessageIter<'_, T, P>
+    where
+        T: NlType,
+        P: Nl,
+    {
+        self.socket.iter()
+    }
+
+    /// Return an iterator object that stops according to `behavior` - see
+    /// `NlSocket::iter_with`
+    pub fn iter_with<T, P>(&mut self, behavior: IterationBehavior) -> NlMessageIter<'_, T, P>
+    where
+        T: NlType,
+        P: Nl,
+    {
+        self.socket.iter_with(behavior)
+    }
+
+    /// Get a reference to the underlying socket
+    pub fn as_socket(&self) -> &NlSocket {
+        &self.socket
+    }
+}
+
+/// Sending half of a socket split with `NlSocket::split`
+pub struct NlWriteHalf {
+    socket: NlSocket,
+}
+
+impl NlWriteHalf {
+    /// Send a message - see `NlSocket::send_nl`
+    pub fn send_nl<T, P>(&mut self, msg: Nlmsghdr<T, P>) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        self.socket.send_nl(msg)
+    }
+
+    /// Send a message to another socket or multicast groups - see `NlSocket::send_nl_to`
+    pub fn send_nl_to<T, P>(
+        &mut self,
+        msg: Nlmsghdr<T, P>,
+        port: u32,
+        groups: u32,
+    ) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        self.socket.send_nl_to(msg, port, groups)
+    }
+
+    /// Send several messages in a single datagram - see `NlSocket::send_all`
+    pub fn send_all<T, P, I>(&mut self, msgs: I) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+        I: IntoIterator<Item = Nlmsghdr<T, P>>,
+    {
+        self.socket.send_all(msgs)
+    }
+
+    /// Get a reference to the underlying socket
+    pub fn as_socket(&self) -> &NlSocket {
+        &self.socket
+    }
+}
+
+impl AsRawFd for NlSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for NlSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl IntoRawFd for NlSocket {
+    fn into_raw_fd(mut self) -> RawFd {
+        let fd = self.fd;
+        // Release the buffers without closing the file descriptor
+        self.fd = -1;
+        fd
+    }
+}
+
+impl FromRawFd for NlSocket {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        NlSocket {
+            fd,
+            buffer: None,
+            send_buffer: Vec::new(),
+            pid: None,
+            seq: None,
+            de_mode: DeMode::default(),
+            recv_info: NlRecvInfo::default(),
+            sent_seqs: None,
+            pid_policy: CheckPolicy::Strict,
+            seq_policy: CheckPolicy::Off,
+            overrun_handler: None,
+            grow_recv_buffer: false,
+            interrupt_policy: InterruptPolicy::Return,
+            genl_cache: None,
+            retry_policy: None,
+        }
+    }
+}
+
+/// Socket that only sends and receives messages valid for the netlink protocol `F`
+///
+/// Payloads and message types are checked against the protocol at compile time using the marker
+/// traits in the `protocol` module so that, for example, a `Genlmsghdr` cannot be sent over a
+/// route netlink socket. Use `as_socket_mut` or `into_inner` to fall back to the untyped
+/// `NlSocket` for anything the marker traits do not cover.
+pub struct NlProtoSocket<F> {
+    socket: NlSocket,
+    protocol: PhantomData<F>,
+}
+
+impl<F> NlProtoSocket<F>
+where
+    F: protocol::NlProtocol,
+{
+    /// Create and bind a socket for protocol `F` - see `NlSocket::connect`
+    pub fn connect(
+        pid: Option<u32>,
+        groups: Option<Vec<u32>>,
+        track_seq: bool,
+    ) -> Result<Self, io::Error> {
+        Ok(NlProtoSocket {
+            socket: NlSocket::connect(F::family(), pid, groups, track_seq)?,
+            protocol: PhantomData,
+        })
+    }
+
+    /// Send a message valid for protocol `F` - see `NlSocket::send_nl`
+    pub fn send_nl<T, P>(&mut self, msg: Nlmsghdr<T, P>) -> Result<(), NlError>
+    where
+        T: Nl + NlType + protocol::ProtocolType<F> + Debug,
+        P: Nl + protocol::ProtocolPayload<F> + Debug,
+    {
+        self.socket.send_nl(msg)
+    }
+
+    /// Receive a message valid for protocol `F` - see `NlSocket::recv_nl`
+    pub fn recv_nl<T, P>(&mut self, buf_sz: Option<usize>) -> Result<Nlmsghdr<T, P>, NlError>
+    where
+        T: Nl + NlType + protocol::ProtocolType<F> + Debug,
+        P: Nl + protocol::ProtocolPayload<F> + Debug,
+    {
+        self.socket.recv_nl(buf_sz)
+    }
+
+    /// Consume an ACK - see `NlSocket::recv_ack`
+    pub fn recv_ack(&mut self) -> Result<(), NlError> {
+        self.socket.recv_ack()
+    }
+
+    /// Send a message valid for protocol `F` and wait for its ACK - see
+    /// `NlSocket::send_and_await_ack`
+    pub fn send_and_await_ack<T, P>(&mut self, msg: Nlmsghdr<T, P>) -> Result<(), NlError>
+    where
+        T: Nl + NlType + protocol::ProtocolType<F> + Debug,
+        P: Nl + protocol::ProtocolPayload<F> + Debug,
+    {
+        self.socket.send_and_await_ack(msg)
+    }
+
+    /// Get a reference to the untyped socket
+    pub fn as_socket(&self) -> &NlSocket {
+        &self.socket
+    }
+
+    /// Get a mutable reference to the untyped socket
+    pub fn as_socket_mut(&mut self) -> &mut NlSocket {
+        &mut self.socket
+    }
+
+    /// Convert into the untyped socket
+    pub fn into_inner(self) -> NlSocket {
+        self.socket
+    }
+}
+
+impl<F> AsRawFd for NlProtoSocket<F> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+pub mod protocol {
+    //! Marker types and traits binding netlink protocols to the message types and payloads that
+    //! are valid for them. These are used by `NlProtoSocket`.
+    //!
+    //! Messages defined by the core netlink protocol such as errors, ACKs, and `NlEmpty`
+    //! payloads are valid for every protocol.
+
+    use crate::{
+        consts::{netfilter::NetfilterMsg, Cmd, GenlId, NlAttrType, NlFamily, Nlmsg, Rtm},
+        err::Nlmsgerr,
+        genl::Genlmsghdr,
+        netfilter::{LogConfigReq, LogPacket},
+        nl::NlEmpty,
+        rtnl::{Ifaddrmsg, Ifinfomsg, Ndmsg, Rtgenmsg, Rtmsg, Tcmsg},
+    };
+
+    /// Netlink protocol known at compile time
+    pub trait NlProtocol {
+        /// Protocol family used to create the socket
+        fn family() -> NlFamily;
+    }
+
+    /// Marker trait for `Nlmsghdr.nl_type` values valid for protocol `F`
+    pub trait ProtocolType<F> {}
+
+    /// Marker trait for `Nlmsghdr` payloads valid for protocol `F`
+    pub trait ProtocolPayload<F> {}
+
+    macro_rules! protocol {
+        ( $(#[$outer:meta])* $name:ident, $family:ident ) => {
+            $(#[$outer])*
+            #[derive(Debug)]
+            pub struct $name;
+
+            impl NlProtocol for $name {
+                fn family() -> NlFamily {
+                    NlFamily::$family
+                }
+            }
+        };
+    }
+
+    protocol!(
+        /// Route netlink (`NETLINK_ROUTE`)
+        Route,
+        Route
+    );
+    protocol!(
+        /// Generic netlink (`NETLINK_GENERIC`)
+        Generic,
+        Generic
+    );
+    protocol!(
+        /// Netfilter netlink (`NETLINK_NETFILTER`)
+        Netfilter,
+        Netfilter
+    );
+
+    impl<F> ProtocolType<F> for Nlmsg where F: NlProtocol {}
+    impl<F> ProtocolPayload<F> for NlEmpty where F: NlProtocol {}
+    impl<F, T> ProtocolPayload<F> for Nlmsgerr<T> where F: NlProtocol {}
+
+    impl ProtocolType<Route> for Rtm {}
+    impl ProtocolPayload<Route> for Ifinfomsg {}
+    impl ProtocolPayload<Route> for Ifaddrmsg {}
+    impl ProtocolPayload<Route> for Rtgenmsg {}
+    impl ProtocolPayload<Route> for Rtmsg {}
+    impl ProtocolPayload<Route> for Ndmsg {}
+    impl ProtocolPayload<Route> for Tcmsg {}
+
+    // Generic netlink family IDs are resolved at runtime so any u16 is allowed.
+    impl ProtocolType<Generic> for GenlId {}
+    impl ProtocolType<Generic> for u16 {}
+    impl<C, T> ProtocolPayload<Generic> for Genlmsghdr<C, T>
+    where
+        C: Cmd,
+        T: NlAttrType,
+    {
+    }
+
+    impl ProtocolType<Netfilter> for NetfilterMsg {}
+    impl ProtocolPayload<Netfilter> for LogPacket {}
+    impl ProtocolPayload<Netfilter> for LogConfigReq {}
+}
+
+#[cfg(feature = "async")]
+pub mod asynchronous {
+    //! Runtime-agnostic async support for neli
+    //!
+    //! `AsyncNlSocket` is a `Stream` of parsed messages and a `Sink` for messages to send. It
+    //! waits for the socket to become ready through an `AsyncNlIo` implementation for the
+    //! reactor of an async runtime. neli implements `AsyncNlIo` for tokio's `AsyncFd` with the
+    //! `stream` feature and for `async_io::Async`, which async-std and smol use, with the
+    //! `async-io` feature. Other runtimes can be supported by implementing `AsyncNlIo`.
+    //!
+    //! Receiving and sending are cancel safe so they can be used in `select!` loops: a datagram
+    //! is only removed from the socket once it has been read in full and a message passed to the
+    //! `Sink` stays buffered until it has been sent, so dropping a pending receive or send future
+    //! does not lose messages.
+    use super::*;
+
+    use std::{
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    };
+
+    use futures_core::{ready, Stream};
+    use futures_sink::Sink;
+
+    /// Readiness-based IO on an `NlSocket` registered with the reactor of an async runtime
+    pub trait AsyncNlIo: Unpin {
+        /// Reference to the registered socket
+        fn socket(&self) -> &super::NlSocket;
+
+        /// Wait until the socket is readable and run `op`, waiting again whenever `op` fails with
+        /// `io::ErrorKind::WouldBlock`
+        fn poll_read_with<R, F>(&self, cx: &mut Context, op: F) -> Poll<io::Result<R>>
+        where
+            F: FnMut(&super::NlSocket) -> io::Result<R>;
+
+        /// Wait until the socket is writable and run `op`, waiting again whenever `op` fails with
+        /// `io::ErrorKind::WouldBlock`
+        fn poll_write_with<R, F>(&self, cx: &mut Context, op: F) -> Poll<io::Result<R>>
+        where
+            F: FnMut(&super::NlSocket) -> io::Result<R>;
+    }
+
+    /// Async netlink socket generic over the runtime it is registered with
+    pub struct AsyncNlSocket<S, T, P> {
+        pub(super) io: S,
+        buffer: Option<StreamReadBuffer<Vec<u8>>>,
+        send_buffer: Vec<u8>,
+        seq: Option<u32>,
+        type_data: PhantomData<T>,
+        payload_data: PhantomData<P>,
+    }
+
+    impl<S, T, P> AsyncNlSocket<S, T, P>
+    where
+        S: AsyncNlIo,
+    {
+        /// Wrap a socket that is registered with a runtime - sequence numbers are tracked if
+        /// they are tracked by the socket
+        pub fn from_io(io: S) -> Self {
+            let seq = io.socket().seq;
+            AsyncNlSocket {
+                io,
+                buffer: None,
+                send_buffer: Vec::new(),
+                seq,
+                type_data: PhantomData,
+                payload_data: PhantomData,
+            }
+        }
+
+        /// Reference to the underlying socket
+        pub fn get_ref(&self) -> &super::NlSocket {
+            self.io.socket()
+        }
+
+        /// Check if underlying received message buffer is empty
+        pub fn empty(&self) -> bool {
+            buffer_empty(&self.buffer)
+        }
+
+        /// Split the socket into a `Stream` half and a `Sink` half sharing the registered socket
+        /// so that one task can receive messages while another sends requests
+        pub fn split(self) -> (AsyncNlReadHalf<S, T, P>, AsyncNlWriteHalf<S>) {
+            let io = Arc::new(self.io);
+            (
+                AsyncNlReadHalf {
+                    io: Arc::clone(&io),
+                    buffer: self.buffer,
+                    type_data: PhantomData,
+                    payload_data: PhantomData,
+                },
+                AsyncNlWriteHalf {
+                    io,
+                    send_buffer: self.send_buffer,
+                    seq: self.seq,
+                },
+            )
+        }
+    }
+
+    impl<S, T, P> AsRawFd for AsyncNlSocket<S, T, P>
+    where
+        S: AsyncNlIo,
+    {
+        fn as_raw_fd(&self) -> RawFd {
+            self.io.socket().as_raw_fd()
+        }
+    }
+
+    impl<S, T, P> AsFd for AsyncNlSocket<S, T, P>
+    where
+        S: AsyncNlIo,
+    {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.io.socket().as_fd()
+        }
+    }
+
+    /// `Stream` half of an `AsyncNlSocket` created with `AsyncNlSocket::split`
+    pub struct AsyncNlReadHalf<S, T, P> {
+        io: Arc<S>,
+        buffer: Option<StreamReadBuffer<Vec<u8>>>,
+        type_data: PhantomData<T>,
+        payload_data: PhantomData<P>,
+    }
+
+    impl<S, T, P> AsyncNlReadHalf<S, T, P>
+    where
+        S: AsyncNlIo,
+    {
+        /// Reference to the underlying socket
+        pub fn get_ref(&self) -> &super::NlSocket {
+            self.io.socket()
+        }
+    }
+
+    /// `Sink` half of an `AsyncNlSocket` created with `AsyncNlSocket::split`
+    pub struct AsyncNlWriteHalf<S> {
+        io: Arc<S>,
+        send_buffer: Vec<u8>,
+        seq: Option<u32>,
+    }
+
+    impl<S> AsyncNlWriteHalf<S>
+    where
+        S: AsyncNlIo,
+    {
+        /// Reference to the underlying socket
+        pub fn get_ref(&self) -> &super::NlSocket {
+            self.io.socket()
+        }
+    }
+
+    fn buffer_empty(buffer: &Option<StreamReadBuffer<Vec<u8>>>) -> bool {
+        if let Some(ref buf) = buffer {
+            buf.at_end()
+        } else {
+            true
+        }
+    }
+
+    fn poll_next_msg<S, T, P>(
+        io: &S,
+        buffer: &mut Option<StreamReadBuffer<Vec<u8>>>,
+        cx: &mut Context,
+    ) -> Poll<Option<io::Result<Nlmsghdr<T, P>>>>
+    where
+        S: AsyncNlIo,
+        T: NlType,
+        P: Nl,
+    {
+        if buffer_empty(buffer) {
+            let mem = ready!(io.poll_read_with(cx, |s| {
+                let mut mem = vec![0; s.peek_len()?];
+                let read = s.recv(&mut mem, 0)?;
+                mem.truncate(read);
+                Ok(mem)
+            }))?;
+            if mem.is_empty() {
+                return Poll::Ready(None);
+            }
+            *buffer = Some(StreamReadBuffer::new(mem));
+        }
+
+        let mode = io.socket().de_mode;
+        let buf = match buffer {
+            Some(ref mut buf) => buf,
+            None => unreachable!(),
+        };
+        let msg = Nlmsghdr::<T, P>::deserialize_with_mode(buf, mode)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+        // A datagram that fails to parse cannot be resynchronized so drop the rest of it
+        if msg.is_err() || buf.at_end() {
+            *buffer = None;
+        }
+        Poll::Ready(Some(msg))
+    }
+
+    fn start_send_msg<T, P>(
+        send_buffer: &mut Vec<u8>,
+        seq: &mut Option<u32>,
+        mut msg: Nlmsghdr<T, P>,
+    ) -> Result<(), NlError>
+    where
+        T: Nl + NlType + Debug,
+        P: Nl + Debug,
+    {
+        if let Some(ref mut seq) = seq {
+            msg.nl_seq = *seq;
+            *seq = seq.wrapping_add(1);
+        }
+
+        #[cfg(feature = "logging")]
+        log!("Message sent:\n{:#?}", msg);
+
+        send_buffer.clear();
+        msg.serialize(&mut StreamWriteBuffer::new_growable_ref(send_buffer))?;
+        Ok(())
+    }
+
+    fn poll_flush_msg<S>(
+        io: &S,
+        send_buffer: &mut Vec<u8>,
+        cx: &mut Context,
+    ) -> Poll<Result<(), NlError>>
+    where
+        S: AsyncNlIo,
+    {
+        if !send_buffer.is_empty() {
+            let buf = &*send_buffer;
+            ready!(io.poll_write_with(cx, |s| s.send(buf, 0)))?;
+            send_buffer.clear();
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    impl<S, T, P> Stream for AsyncNlSocket<S, T, P>
+    where
+        S: AsyncNlIo,
+        T: NlType,
+        P: Nl,
+    {
+        type Item = std::io::Result<Nlmsghdr<T, P>>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            poll_next_msg(&this.io, &mut this.buffer, cx)
+        }
+    }
+
+    impl<S, T, P> Stream for AsyncNlReadHalf<S, T, P>
+    where
+        S: AsyncNlIo,
+        T: NlType,
+        P: Nl,
+    {
+        type Item = std::io::Result<Nlmsghdr<T, P>>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            poll_next_msg(&*this.io, &mut this.buffer, cx)
+        }
+    }
+
+    impl<S, T, P, ST, SP> Sink<Nlmsghdr<ST, SP>> for AsyncNlSocket<S, T, P>
+    where
+        S: AsyncNlIo,
+        ST: Nl + NlType + Debug,
+        SP: Nl + Debug,
+    {
+        type Error = NlError;
+
+        fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), NlError>> {
+            let this = self.get_mut();
+            poll_flush_msg(&this.io, &mut this.send_buffer, cx)
+        }
+
+        fn start_send(self: Pin<&mut Self>, msg: Nlmsghdr<ST, SP>) -> Result<(), NlError> {
+            let this = self.get_mut();
+            start_send_msg(&mut this.send_buffer, &mut this.seq, msg)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), NlError>> {
+            let this = self.get_mut();
+            poll_flush_msg(&this.io, &mut this.send_buffer, cx)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), NlError>> {
+            let this = self.get_mut();
+            poll_flush_msg(&this.io, &mut this.send_buffer, cx)
+        }
+    }
+
+    impl<S, ST, SP> Sink<Nlmsghdr<ST, SP>> for AsyncNlWriteHalf<S>
+    where
+        S: AsyncNlIo,
+        ST: Nl + NlType + Debug,
+        SP: Nl + Debug,
+    {
+        type Error = NlError;
+
+        fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), NlError>> {
+            let this = self.get_mut();
+            poll_flush_msg(&*this.io, &mut this.send_buffer, cx)
+        }
+
+        fn start_send(self: Pin<&mut Self>, msg: Nlmsghdr<ST, SP>) -> Result<(), NlError> {
+            let this = self.get_mut();
+            start_send_msg(&mut this.send_buffer, &mut this.seq, msg)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), NlError>> {
+            let this = self.get_mut();
+            poll_flush_msg(&*this.io, &mut this.send_buffer, cx)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), NlError>> {
+            let this = self.get_mut();
+            poll_flush_msg(&*this.io, &mut this.send_buffer, cx)
+        }
+    }
+
+    impl<S, T, P> Unpin for AsyncNlSocket<S, T, P> {}
+
+    impl<S, T, P> Unpin for AsyncNlReadHalf<S, T, P> {}
+}
+
+#[cfg(feature = "stream")]
+pub mod tokio {
+    //! Tokio-specific features for neli
+    //!
+    //! This module contains `NlSocket`, an `AsyncNlSocket` registered with tokio 1.x. See the
+    //! `asynchronous` module for its `Stream` and `Sink` implementations.
+    use super::*;
+
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use ::tokio::io::{unix::AsyncFd, AsyncRead, ReadBuf};
+    use futures_core::ready;
+
+    use super::asynchronous::{AsyncNlIo, AsyncNlSocket};
+
+    /// Tokio-enabled Netlink socket struct
+    pub type NlSocket<T, P> = AsyncNlSocket<AsyncFd<super::NlSocket>, T, P>;
+
+    impl<T, P> NlSocket<T, P>
+    where
+        T: NlType,
+    {
+        /// Setup NlSocket for use with tokio - set to nonblocking state and register it with the
+        /// reactor of the current runtime
+        pub fn new(socket: super::NlSocket) -> io::Result<Self> {
+            Ok(AsyncNlSocket::from_io(register(socket)?))
+        }
+    }
+
+    /// Deregisters the socket from the reactor - messages that were received but not yet read
+    /// from the stream are discarded
+    impl<T, P> IntoRawFd for NlSocket<T, P> {
+        fn into_raw_fd(self) -> RawFd {
+            self.io.into_inner().into_raw_fd()
+        }
+    }
+
+    /// Wraps a netlink socket file descriptor, for example one inherited through socket
+    /// activation - see `NlSocket::new`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the socket cannot be registered with the reactor, for example when called
+    /// outside of a tokio runtime.
+    impl<T, P> FromRawFd for NlSocket<T, P>
+    where
+        T: NlType,
+    {
+        unsafe fn from_raw_fd(fd: RawFd) -> Self {
+            NlSocket::new(super::NlSocket::from_raw_fd(fd))
+                .expect("Failed to register the socket with the tokio reactor")
+        }
+    }
+
+    // Set the socket to nonblocking state and register it with the reactor of the current
+    // runtime.
+    pub(crate) fn register(mut socket: super::NlSocket) -> io::Result<AsyncFd<super::NlSocket>> {
+        if socket.is_blocking()? {
+            socket.nonblock()?;
+        }
+        // The file descriptor is owned by the socket and only closed when it is dropped
+        Ok(unsafe { AsyncFd::register(socket)? })
+    }
+
+    impl AsyncNlIo for AsyncFd<super::NlSocket> {
+        fn socket(&self) -> &super::NlSocket {
+            self.get_ref()
+        }
+
+        fn poll_read_with<R, F>(&self, cx: &mut Context, mut op: F) -> Poll<io::Result<R>>
+        where
+            F: FnMut(&super::NlSocket) -> io::Result<R>,
+        {
+            loop {
+                let mut guard = ready!(self.poll_read_ready(cx))?;
+                if let Ok(res) = guard.try_io(|s| op(s.get_ref())) {
+                    return Poll::Ready(res);
+                }
+            }
+        }
+
+        fn poll_write_with<R, F>(&self, cx: &mut Context, mut op: F) -> Poll<io::Result<R>>
+        where
+            F: FnMut(&super::NlSocket) -> io::Result<R>,
+        {
+            loop {
+                let mut guard = ready!(self.poll_write_ready(cx))?;
+                if let Ok(res) = guard.try_io(|s| op(s.get_ref())) {
+                    return Poll::Ready(res);
+                }
+            }
+        }
+    }
+
+    impl io::Read for super::NlSocket {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.is_blocking()? {
+                self.nonblock()?;
+            }
+            self.recv(buf, 0)
+        }
+    }
+
+    impl io::Write for super::NlSocket {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.is_blocking()? {
+                self.nonblock()?;
+            }
+            self.send(buf, 0)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<T, P> AsyncRead for NlSocket<T, P> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let read = ready!(self
+                .io
+                .poll_read_with(cx, |s| s.recv(buf.initialize_unfilled(), 0)))?;
+            buf.advance(read);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::io::Read;
+
+        use futures::{poll, SinkExt, StreamExt};
+
+        use crate::{consts::Nlmsg, nl::NlEmpty};
+
+        use super::*;
+
+        #[test]
+        fn test_socket_nonblock() {
+            let mut s =
+                super::super::NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+            s.nonblock().unwrap();
+            assert!(!s.is_blocking().unwrap());
+            let buf = &mut [0; 4];
+            match s.read_exact(buf) {
+                Err(e) => {
+                    if e.kind() != io::ErrorKind::WouldBlock {
+                        panic!("Error: {}", e);
+                    }
+                }
+                Ok(()) => {
+                    panic!("Should not return data");
+                }
+            }
+        }
+
+        #[test]
+        fn test_socket_raw_fd() {
+            let rt = ::tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()
+                .unwrap();
+            rt.block_on(async {
+                let s = super::super::NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+                let port = s.port_id().unwrap();
+                let s = NlSocket::<Nlmsg, Nlmsgerr<Nlmsg>>::new(s).unwrap();
+                let fd = s.into_raw_fd();
+                let mut s = unsafe { NlSocket::<Nlmsg, Nlmsgerr<Nlmsg>>::from_raw_fd(fd) };
+                assert_eq!(s.as_raw_fd(), fd);
+                assert_eq!(s.get_ref().port_id().unwrap(), port);
+
+                let msg = Nlmsghdr::new(
+                    None,
+                    Nlmsg::Noop,
+                    vec![NlmF::Request, NlmF::Ack],
+                    None,
+                    None,
+                    NlEmpty,
+                );
+                s.send(msg).await.unwrap();
+                let ack = s.next().await.unwrap().unwrap();
+                assert_eq!(ack.nl_payload.error, 0);
+            });
+        }
+
+        #[test]
+        fn test_socket_stream_sink() {
+            let rt = ::tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()
+                .unwrap();
+            rt.block_on(async {
+                let s = super::super::NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+                let mut s = NlSocket::<Nlmsg, Nlmsgerr<Nlmsg>>::new(s).unwrap();
+
+                // Nothing has been received yet so dropping the pending future loses nothing
+                assert!(poll!(s.next()).is_pending());
+
+                for _ in 0..2 {
+                    let msg = Nlmsghdr::new(
+                        None,
+                        Nlmsg::Noop,
+                        vec![NlmF::Request, NlmF::Ack],
+                        None,
+                        None,
+                        NlEmpty,
+                    );
+                    s.send(msg).await.unwrap();
+                }
+                for seq in 0..2 {
+                    let ack = s.next().await.unwrap().unwrap();
+                    assert_eq!(ack.nl_type, Nlmsg::Error);
+                    assert_eq!(ack.nl_seq, seq);
+                    assert_eq!(ack.nl_payload.error, 0);
+                }
+            });
+        }
+
+        #[test]
+        fn test_socket_split() {
+            let rt = ::tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()
+                .unwrap();
+            rt.block_on(async {
+                let s = super::super::NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+                let (mut reader, mut writer) =
+                    NlSocket::<Nlmsg, Nlmsgerr<Nlmsg>>::new(s).unwrap().split();
+                let receive = async {
+                    let ack = reader.next().await.unwrap().unwrap();
+                    assert_eq!(ack.nl_seq, 0);
+                    assert_eq!(ack.nl_payload.error, 0);
+                };
+                let send = async {
+                    let msg = Nlmsghdr::new(
+                        None,
+                        Nlmsg::Noop,
+                        vec![NlmF::Request, NlmF::Ack],
+                        None,
+                        None,
+                        NlEmpty,
+                    );
+                    writer.send(msg).await.unwrap();
+                };
+                futures::join!(receive, send);
+            });
+        }
+    }
+}
+
+#[cfg(feature = "async-io")]
+pub mod async_io {
+    //! async-io features for neli, for use with async-std and smol
+    //!
+    //! This module contains `NlSocket`, an `AsyncNlSocket` registered with the `async-io`
+    //! reactor. See the `asynchronous` module for its `Stream` and `Sink` implementations.
+    use super::*;
+
+    use std::task::{Context, Poll};
+
+    use ::async_io::Async;
+    use futures_core::ready;
+
+    use super::asynchronous::{AsyncNlIo, AsyncNlSocket};
+
+    /// async-io enabled Netlink socket struct
+    pub type NlSocket<T, P> = AsyncNlSocket<Async<super::NlSocket>, T, P>;
+
+    impl<T, P> NlSocket<T, P>
+    where
+        T: NlType,
+    {
+        /// Setup NlSocket for use with async-io - set to nonblocking state and register it with
+        /// the reactor
+        pub fn new(socket: super::NlSocket) -> io::Result<Self> {
+            Ok(AsyncNlSocket::from_io(Async::new(socket)?))
+        }
+    }
+
+    /// Deregisters the socket from the reactor - messages that were received but not yet read
+    /// from the stream are discarded
+    ///
+    /// # Panics
+    ///
+    /// Panics if the socket cannot be deregistered from the reactor.
+    impl<T, P> IntoRawFd for NlSocket<T, P> {
+        fn into_raw_fd(self) -> RawFd {
+            self.io
+                .into_inner()
+                .expect("Failed to deregister the socket from the async-io reactor")
+                .into_raw_fd()
+        }
+    }
+
+    /// Wraps a netlink socket file descriptor, for example one inherited through socket
+    /// activation - see `NlSocket::new`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the socket cannot be registered with the reactor.
+    impl<T, P> FromRawFd for NlSocket<T, P>
+    where
+        T: NlType,
+    {
+        unsafe fn from_raw_fd(fd: RawFd) -> Self {
+            NlSocket::new(super::NlSocket::from_raw_fd(fd))
+                .expect("Failed to register the socket with the async-io reactor")
+        }
+    }
+
+    impl AsyncNlIo for Async<super::NlSocket> {
+        fn socket(&self) -> &super::NlSocket {
+            self.get_ref()
+        }
+
+        fn poll_read_with<R, F>(&self, cx: &mut Context, mut op: F) -> Poll<io::Result<R>>
+        where
+            F: FnMut(&super::NlSocket) -> io::Result<R>,
+        {
+            loop {
+                match op(self.get_ref()) {
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                    res => return Poll::Ready(res),
+                }
+                ready!(self.poll_readable(cx))?;
+            }
+        }
+
+        fn poll_write_with<R, F>(&self, cx: &mut Context, mut op: F) -> Poll<io::Result<R>>
+        where
+            F: FnMut(&super::NlSocket) -> io::Result<R>,
+        {
+            loop {
+                match op(self.get_ref()) {
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                    res => return Poll::Ready(res),
+                }
+                ready!(self.poll_writable(cx))?;
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use futures::{SinkExt, StreamExt};
+
+        use crate::{consts::Nlmsg, nl::NlEmpty};
+
+        use super::*;
+
+        #[test]
+        fn test_socket_stream_sink() {
+            ::async_io::block_on(async {
+                let s = super::super::NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+                let mut s = NlSocket::<Nlmsg, Nlmsgerr<Nlmsg>>::new(s).unwrap();
+                let msg = Nlmsghdr::new(
+                    None,
+                    Nlmsg::Noop,
+                    vec![NlmF::Request, NlmF::Ack],
+                    None,
+                    None,
+                    NlEmpty,
+                );
+                s.send(msg).await.unwrap();
+                let ack = s.next().await.unwrap().unwrap();
+                assert_eq!(ack.nl_type, Nlmsg::Error);
+                assert_eq!(ack.nl_seq, 0);
+                assert_eq!(ack.nl_payload.error, 0);
+            });
+        }
+    }
+}
+
+#[cfg(feature = "mio")]
+mod mio_source {
+    //! Integration with `mio` event loops
+    //!
+    //! `NlSocket` implements `mio::event::Source` so it can be registered with a `mio::Poll`
+    //! alongside other sockets. Put the socket in non blocking mode with
+    //! `NlSocket::set_nonblocking` and receive until `NlError::WouldBlock` is returned after
+    //! each readable event.
+
+    use super::*;
+
+    use mio::{event::Source, unix::SourceFd, Interest, Registry, Token};
+
+    impl Source for NlSocket {
+        fn register(
+            &mut self,
+            registry: &Registry,
+            token: Token,
+            interests: Interest,
+        ) -> io::Result<()> {
+            SourceFd(&self.fd).register(registry, token, interests)
+        }
+
+        fn reregister(
+            &mut self,
+            registry: &Registry,
+            token: Token,
+            interests: Interest,
+        ) -> io::Result<()> {
+            SourceFd(&self.fd).reregister(registry, token, interests)
+        }
+
+        fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+            SourceFd(&self.fd).deregister(registry)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        use mio::{Events, Poll};
+
+        use crate::{consts::Nlmsg, nl::NlEmpty};
+
+        #[test]
+        fn test_socket_mio_poll() {
+            let mut poll = Poll::new().unwrap();
+            let mut events = Events::with_capacity(4);
+            let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+            s.set_nonblocking(true).unwrap();
+            poll.registry()
+                .register(&mut s, Token(0), Interest::READABLE)
+                .unwrap();
+
+            let msg = Nlmsghdr::new(
+                None,
+                Nlmsg::Noop,
+                vec![NlmF::Request, NlmF::Ack],
+                None,
+                None,
+                NlEmpty,
+            );
+            s.send_nl(msg).unwrap();
+            poll.poll(&mut events, Some(Duration::from_secs(5)))
+                .unwrap();
+            assert!(events
+                .iter()
+                .any(|e| e.token() == Token(0) && e.is_readable()));
+            s.recv_ack().unwrap();
+            match s.recv_nl::<Nlmsg, NlEmpty>(None) {
+                Err(NlError::WouldBlock) => (),
+                r => panic!("Expected WouldBlock, got {:?}", r),
+            }
+
+            poll.registry().deregister(&mut s).unwrap();
+        }
+    }
+}
+
+impl Drop for NlSocket {
+    /// Closes underlying file descriptor to avoid file descriptor leaks.
+    fn drop(&mut self) {
+        if self.fd >= 0 {
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        consts::{GenlOpFlag, Nlmsg},
+        nl::NlEmpty,
+        MAX_NL_LENGTH,
+    };
+
+    #[test]
+    fn test_socket_raw_fd() {
+        let s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        let port = s.port_id().unwrap();
+        let fd = s.into_raw_fd();
+        // The file descriptor is still open after the socket was converted
+        assert_ne!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, -1);
+        let mut s = unsafe { NlSocket::from_raw_fd(fd) };
+        assert_eq!(s.port_id().unwrap(), port);
+        s.send_nl(Nlmsghdr::new(
+            None,
+            Nlmsg::Noop,
+            vec![NlmF::Request, NlmF::Ack],
+            None,
+            None,
+            NlEmpty,
+        ))
+        .unwrap();
+        s.recv_ack().unwrap();
+    }
+
+    #[test]
+    fn test_socket_nonblock() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        s.nonblock().unwrap();
+        assert!(!s.is_blocking().unwrap());
+        let buf = &mut [0; 4];
+        match s.recv(buf, 0) {
+            Err(e) => {
+                if e.kind() != io::ErrorKind::WouldBlock {
+                    panic!("Error: {}", e);
+                }
+            }
+            Ok(_) => {
+                panic!("Should not return data");
+            }
+        }
+    }
+
+    #[test]
+    fn test_socket_set_nonblocking() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        s.set_nonblocking(true).unwrap();
+        assert!(!s.is_blocking().unwrap());
+        match s.recv_nl::<Nlmsg, NlEmpty>(None) {
+            Err(NlError::WouldBlock) => (),
+            other => panic!("Expected WouldBlock, got {:?}", other),
+        }
+        match s.iter::<Nlmsg, NlEmpty>().next() {
+            Some(Err(NlError::WouldBlock)) => (),
+            other => panic!("Expected WouldBlock, got {:?}", other),
+        }
+        s.set_nonblocking(false).unwrap();
+        assert!(s.is_blocking().unwrap());
+    }
+
+    #[test]
+    fn test_socket_recv_timeout() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        assert!(s.set_recv_timeout(Some(Duration::from_secs(0))).is_err());
+        s.set_recv_timeout(Some(Duration::from_millis(10))).unwrap();
+        match s.recv_nl::<Nlmsg, NlEmpty>(None) {
+            Err(NlError::Timeout) => (),
+            other => panic!("Expected Timeout, got {:?}", other),
+        }
+        s.set_recv_timeout(None).unwrap();
+        s.set_send_timeout(Some(Duration::from_millis(10))).unwrap();
+    }
+
+    #[test]
+    fn test_socket_options() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        s.set_ext_ack(true).unwrap();
+        s.set_ext_ack(false).unwrap();
+        s.set_broadcast_error(true).unwrap();
+        s.set_broadcast_error(false).unwrap();
+        // Requires CAP_NET_BROADCAST
+        match s.set_listen_all_nsid(true) {
+            Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => (),
+            res => res.unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_recv_info_cmsgs() {
+        let mut control = [0u64; 8];
+        let mut msg = unsafe { zeroed::<libc::msghdr>() };
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = unsafe { 2 * libc::CMSG_SPACE(size_of::<c_int>() as u32) } as _;
+        let values = [
+            (NlSockOpt::ListenAllNsid, 7),
+            (NlSockOpt::PktInfo, libc::RTNLGRP_LINK as c_int),
+        ];
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            for (opt, value) in values.iter() {
+                (*cmsg).cmsg_level = libc::SOL_NETLINK;
+                (*cmsg).cmsg_type = c_int::from(opt.clone());
+                (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<c_int>() as u32) as _;
+                (libc::CMSG_DATA(cmsg) as *mut c_int).write_unaligned(*value);
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+
+        let mut info = NlRecvInfo::default();
+        info.read_cmsgs(&msg);
+        assert_eq!(info.nsid, Some(7));
+        assert_eq!(info.group, Some(libc::RTNLGRP_LINK));
+    }
+
+    #[test]
+    fn test_socket_no_enobufs() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        assert!(!s.no_enobufs().unwrap());
+        s.set_no_enobufs(true).unwrap();
+        assert!(s.no_enobufs().unwrap());
+        s.set_no_enobufs(false).unwrap();
+        assert!(!s.no_enobufs().unwrap());
+    }
+
+    fn dump_strict<T, P>(nl_type: T, payload: P)
+    where
+        T: NlType + Debug,
+        P: Nl + Debug,
+    {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        s.set_strict_chk(true).unwrap();
+        let msg = Nlmsghdr::new(
+            None,
+            nl_type,
+            vec![NlmF::Request, NlmF::Dump],
+            None,
+            None,
+            payload,
+        );
+        s.send_nl(msg).unwrap();
+        loop {
+            let msg = s.recv_nl::<u16, Vec<u8>>(None).unwrap();
+            match Nlmsg::from(msg.nl_type) {
+                // Errors from validating a dump request are reported in the done message
+                Nlmsg::Done => {
+                    let error =
+                        libc::c_int::deserialize(&mut StreamReadBuffer::new(&msg.nl_payload))
+                            .unwrap();
+                    assert_eq!(error, 0, "Strict dump request was rejected");
+                    break;
+                }
+                Nlmsg::Error => panic!("Strict dump request was rejected"),
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn test_socket_iter_dump() {
+        use crate::{
+            consts::{RtAddrFamily, Rtm},
+            rtnl::Ifinfomsg,
+        };
+
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        let dump = || {
+            Nlmsghdr::new(
+                None,
+                Rtm::Getlink,
+                vec![NlmF::Request, NlmF::Dump],
+                None,
+                None,
+                Ifinfomsg::dump(RtAddrFamily::Unspecified),
+            )
+        };
+        s.send_nl(dump()).unwrap();
+        let links = s
+            .iter_with::<Rtm, Ifinfomsg>(IterationBehavior::EndMultiOnDone)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(!links.is_empty());
+        assert!(links.iter().all(|l| l.nl_type == Rtm::Newlink));
+
+        let request = Nlmsghdr::new(
+            None,
+            Rtm::Getlink,
+            Vec::new(),
+            None,
+            None,
+            Ifinfomsg::dump(RtAddrFamily::Unspecified),
+        );
+        let dumped = s
+            .dump::<_, _, Ifinfomsg>(request)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(dumped.len(), links.len());
+
+        let request = Nlmsghdr::new(
+            None,
+            Rtm::Getlink,
+            Vec::new(),
+            None,
+            None,
+            Ifinfomsg::dump(RtAddrFamily::Unspecified),
+        );
+        let consistent = s.dump_consistent::<_, _, Ifinfomsg>(request, 3).unwrap();
+        assert_eq!(consistent.len(), links.len());
+
+        // A request answered with an error ends the iteration with the error
+        let mut request = dump();
+        request.nl_type = Rtm::from(0xffff);
+        request.nl_flags = vec![NlmF::Request, NlmF::Ack];
+        s.send_nl(request).unwrap();
+        let mut iter = s.iter_with::<Rtm, Ifinfomsg>(IterationBehavior::EndMultiOnDone);
+        match iter.next() {
+            Some(Err(NlError::Errno(_))) => (),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_socket_strict_chk() {
+        use crate::{
+            consts::{RtAddrFamily, Rtm},
+            rtnl::{Ifaddrmsg, Ifinfomsg, Ndmsg, Rtmsg, Tcmsg},
+        };
+
+        dump_strict(Rtm::Getlink, Ifinfomsg::dump(RtAddrFamily::Unspecified));
+        dump_strict(Rtm::Getaddr, Ifaddrmsg::dump(RtAddrFamily::Unspecified));
+        dump_strict(Rtm::Getroute, Rtmsg::dump(RtAddrFamily::Inet));
+        dump_strict(Rtm::Getneigh, Ndmsg::dump(RtAddrFamily::Inet));
+        dump_strict(Rtm::Getqdisc, Tcmsg::dump(0));
+    }
+
+    #[test]
+    fn test_socket_send_nl_to() {
+        let mut a = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let mut b = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+
+        let msg = Nlmsghdr::new(None, 100u16, vec![], None, None, vec![1u8, 2, 3, 4]);
+        a.send_nl_to(msg, b.port_id().unwrap(), 0).unwrap();
+        let (received, info) = b.recv_nl_with_info::<u16, Vec<u8>>(None).unwrap();
+        assert_eq!(received.nl_payload, vec![1, 2, 3, 4]);
+        assert_eq!(info.sender.pid, a.port_id().unwrap());
+        assert_eq!(info.group, None);
+
+        let reply = Nlmsghdr::new(None, 101u16, vec![], None, None, vec![5u8, 6, 7, 8]);
+        b.send_nl_to(reply, info.sender.pid, 0).unwrap();
+        let (received, sender) = a.recv_nl_from::<u16, Vec<u8>>(None).unwrap();
+        assert_eq!(received.nl_type, 101);
+        assert_eq!(
+            sender,
+            NlAddr {
+                pid: b.port_id().unwrap(),
+                groups: 0,
+            }
+        );
+        assert!(!sender.is_kernel());
+
+        let mut route = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        route
+            .send_nl(Nlmsghdr::new(
+                None,
+                Nlmsg::Noop,
+                vec![NlmF::Request, NlmF::Ack],
+                None,
+                None,
+                NlEmpty,
+            ))
+            .unwrap();
+        let (_, sender) = route.recv_nl_from::<Nlmsg, Nlmsgerr<Nlmsg>>(None).unwrap();
+        assert!(sender.is_kernel());
+    }
+
+    #[test]
+    fn test_socket_pktinfo() {
+        let mut receiver =
+            NlSocket::connect(NlFamily::Usersock, None, Some(vec![3]), false).unwrap();
+        receiver.set_pktinfo(true).unwrap();
+        let sender = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+
+        let msg = Nlmsghdr::new(None, 100u16, vec![], None, None, vec![1u8, 2, 3, 4]);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        msg.serialize(&mut mem).unwrap();
+        // The message is broadcast before the kernel tries to deliver it to port 0, which has no
+        // listener for this protocol, so sending reports an error after the broadcast succeeded
+        let err = sender.send_to(mem.as_ref(), 0, 1 << 2, 0).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ECONNREFUSED));
+
+        let (received, info) = receiver.recv_nl_with_info::<u16, Vec<u8>>(None).unwrap();
+        assert_eq!(received.nl_payload, vec![1, 2, 3, 4]);
+        assert_eq!(info.group, Some(3));
+        assert_eq!(
+            info.sender,
+            NlAddr {
+                pid: sender.port_id().unwrap(),
+                groups: 1 << 2,
+            }
+        );
+        assert_eq!(info.nsid, None);
+    }
+
+    #[test]
+    fn test_socket_recv_peek_len() {
+        let mut s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let payload = vec![0xabu8; MAX_NL_LENGTH + 1000];
+        let msg = Nlmsghdr::new(None, 100u16, vec![], None, None, payload.clone());
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        msg.serialize(&mut mem).unwrap();
+        let sent = s.send_to(mem.as_ref(), s.port_id().unwrap(), 0, 0).unwrap();
+        assert_eq!(sent, mem.as_ref().len());
+
+        assert_eq!(s.peek_len().unwrap(), mem.as_ref().len());
+        let received = s.recv_nl::<u16, Vec<u8>>(None).unwrap();
+        assert_eq!(received.nl_payload, payload);
+    }
+
+    extern "C" fn ignore_signal(_: c_int) {}
+
+    // Receive on a socket while another thread interrupts the receive with a signal and then
+    // sends a message to the socket.
+    fn recv_interrupted(policy: InterruptPolicy) -> Result<Nlmsghdr<u16, Vec<u8>>, NlError> {
+        let mut action = unsafe { zeroed::<libc::sigaction>() };
+        action.sa_sigaction = ignore_signal as extern "C" fn(c_int) as libc::sighandler_t;
+        assert_eq!(
+            unsafe { libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut()) },
+            0
+        );
+
+        let mut s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        s.set_recv_timeout(Some(Duration::from_secs(5))).unwrap();
+        s.set_interrupt_policy(policy);
+        let port = s.port_id().unwrap();
+        let receiver = unsafe { libc::pthread_self() };
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            assert_eq!(unsafe { libc::pthread_kill(receiver, libc::SIGUSR1) }, 0);
+            std::thread::sleep(Duration::from_millis(100));
+            let sender = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+            let msg = Nlmsghdr::new(None, 100u16, vec![], None, None, vec![1u8; 4]);
+            let mut mem = StreamWriteBuffer::new_growable(None);
+            msg.serialize(&mut mem).unwrap();
+            sender.send_to(mem.as_ref(), port, 0, 0).unwrap();
+        });
+        let received = s.recv_nl(None);
+        interrupter.join().unwrap();
+        received
+    }
+
+    #[test]
+    fn test_socket_recv_interrupted() {
+        match recv_interrupted(InterruptPolicy::Return) {
+            Err(NlError::Interrupted) => (),
+            r => panic!("Expected an interrupted receive, got {:?}", r),
+        }
+        let received = recv_interrupted(InterruptPolicy::Retry).unwrap();
+        assert_eq!(received.nl_payload, vec![1u8; 4]);
+    }
+
+    #[test]
+    fn test_socket_recv_truncated() {
+        let mut s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let msg = Nlmsghdr::new(None, 100u16, vec![], None, None, vec![1u8; 84]);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        msg.serialize(&mut mem).unwrap();
+        let port = s.port_id().unwrap();
+        for _ in 0..2 {
+            s.send_to(mem.as_ref(), port, 0, 0).unwrap();
+        }
+
+        match s.recv_nl::<u16, Vec<u8>>(Some(20)) {
+            Err(NlError::Truncated { len: 100 }) => (),
+            r => panic!("Expected a truncated datagram, got {:?}", r),
+        }
+
+        s.set_grow_recv_buffer(true);
+        let received = s.recv_nl::<u16, Vec<u8>>(Some(20)).unwrap();
+        assert_eq!(received.nl_payload, vec![1u8; 84]);
+    }
+
+    #[test]
+    fn test_socket_recv_all() {
+        let mut s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        for i in 0..3u8 {
+            let msg = Nlmsghdr::new(None, 100u16, vec![], None, None, vec![i; 5]);
+            msg.serialize(&mut mem).unwrap();
+        }
+        for _ in 0..2 {
+            let sent = s.send_to(mem.as_ref(), s.port_id().unwrap(), 0, 0).unwrap();
+            assert_eq!(sent, mem.as_ref().len());
+        }
+
+        let msgs = s.recv_all_nl::<u16, Vec<u8>>(None).unwrap();
+        let payloads: Vec<_> = msgs.into_iter().map(|m| m.nl_payload).collect();
+        assert_eq!(payloads, vec![vec![0; 5], vec![1; 5], vec![2; 5]]);
+
+        let first = s.recv_nl::<u16, Vec<u8>>(None).unwrap();
+        assert_eq!(first.nl_payload, vec![0; 5]);
+        let rest = s.recv_all_nl::<u16, Vec<u8>>(None).unwrap();
+        assert_eq!(rest.len(), 2);
+    }
+
+    fn overrun(group: u32) -> NlSocket {
+        let receiver =
+            NlSocket::connect(NlFamily::Usersock, None, Some(vec![group]), false).unwrap();
+        let rcvbuf: c_int = 0;
+        assert_eq!(
+            unsafe {
+                libc::setsockopt(
+                    receiver.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_RCVBUF,
+                    &rcvbuf as *const _ as *const c_void,
+                    size_of::<c_int>() as libc::socklen_t,
+                )
+            },
+            0
+        );
+
+        let sender = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let msg = Nlmsghdr::new(None, 100u16, vec![], None, None, vec![0u8; 1024]);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        msg.serialize(&mut mem).unwrap();
+        for _ in 0..64 {
+            // Broadcasting succeeds even though delivery to port 0 fails - see
+            // `test_socket_pktinfo`
+            sender
+                .send_to(mem.as_ref(), 0, 1 << (group - 1), 0)
+                .unwrap_err();
+        }
+        receiver
+    }
+
+    #[test]
+    fn test_socket_connect_in_netns() {
+        use crate::{
+            consts::{RtAddrFamily, Rtm},
+            rtnl::Ifinfomsg,
+        };
+
+        // Keep a reference to a new network namespace that only has a loopback interface
+        let netns = std::thread::spawn(|| {
+            assert_eq!(unsafe { libc::unshare(libc::CLONE_NEWNET) }, 0);
+            File::open("/proc/thread-self/ns/net").unwrap()
+        })
+        .join()
+        .unwrap();
+        let own_netns = std::fs::read_link("/proc/thread-self/ns/net").unwrap();
+
+        let mut s =
+            NlSocket::connect_in_netns_fd(netns.as_fd(), NlFamily::Route, None, None, false)
+                .unwrap();
+        assert_eq!(
+            std::fs::read_link("/proc/thread-self/ns/net").unwrap(),
+            own_netns
+        );
+
+        let request = Nlmsghdr::new(
+            None,
+            Rtm::Getlink,
+            Vec::new(),
+            None,
+            None,
+            Ifinfomsg::dump(RtAddrFamily::Unspecified),
+        );
+        let links = s
+            .dump::<_, _, Ifinfomsg>(request)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(links.len(), 1);
+    }
+
+    #[test]
+    fn test_socket_stats() {
+        let s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let stats = s.stats().unwrap();
+        assert_eq!(stats.rmem_alloc, 0);
+        assert_eq!(stats.drops, 0);
+        assert!(stats.rcvbuf > 0);
+        assert!(s.take_error().unwrap().is_none());
+
+        let s = overrun(6);
+        let stats = s.stats().unwrap();
+        assert!(stats.rmem_alloc > 0);
+        assert!(stats.drops > 0);
+        assert_eq!(
+            s.take_error().unwrap().and_then(|e| e.raw_os_error()),
+            Some(libc::ENOBUFS)
+        );
+        assert!(s.take_error().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_socket_subscribe() {
+        let s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let subscription = s.subscribe::<u16, u32>(&[7]).unwrap();
+
+        let sender = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        Nlmsghdr::new(None, 100u16, vec![], None, None, vec![1u8, 2])
+            .serialize(&mut mem)
+            .unwrap();
+        let invalid = mem.as_ref().to_vec();
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        Nlmsghdr::new(None, 100u16, vec![], None, None, 5u32)
+            .serialize(&mut mem)
+            .unwrap();
+        // Broadcasting succeeds even though delivery to port 0 fails - see `test_socket_pktinfo`
+        sender.send_to(&invalid, 0, 1 << 6, 0).unwrap_err();
+        sender.send_to(mem.as_ref(), 0, 1 << 6, 0).unwrap_err();
+
+        let timeout = Duration::from_secs(5);
+        assert!(subscription.recv_timeout(timeout).unwrap().is_err());
+        let msg = subscription.recv_timeout(timeout).unwrap().unwrap();
+        assert_eq!(msg.nl_type, 100);
+        assert_eq!(msg.nl_payload, 5);
+        assert!(subscription.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_socket_subscribe_nonblocking() {
+        let mut s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        s.nonblock().unwrap();
+        let subscription = s.subscribe::<u16, u32>(&[9]).unwrap();
+
+        // Nothing is received until after the thread polled the empty socket
+        thread::sleep(Duration::from_millis(50));
+        let sender = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        Nlmsghdr::new(None, 100u16, vec![], None, None, 5u32)
+            .serialize(&mut mem)
+            .unwrap();
+        sender.send_to(mem.as_ref(), 0, 1 << 8, 0).unwrap_err();
+
+        let msg = subscription
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.nl_payload, 5);
+    }
+
+    #[test]
+    fn test_socket_overrun() {
+        let mut s = overrun(4);
+        match s.recv_nl::<u16, Vec<u8>>(None) {
+            Err(NlError::Overrun) => (),
+            r => panic!("Expected an overrun, got {:?}", r),
+        }
+        // Messages received before the overrun are still queued
+        s.recv_nl::<u16, Vec<u8>>(None).unwrap();
+    }
+
+    #[test]
+    fn test_socket_overrun_handler() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let mut s = overrun(5);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler_calls = Arc::clone(&calls);
+        s.set_overrun_handler(move |_| {
+            handler_calls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        let msg = s.recv_nl::<u16, Vec<u8>>(None).unwrap();
+        assert_eq!(msg.nl_payload, vec![0u8; 1024]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_socket_dump_interrupted() {
+        let mut s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        let msg = Nlmsghdr::new(
+            None,
+            100u16,
+            vec![NlmF::Multi, NlmF::DumpIntr],
+            None,
+            None,
+            vec![1u8; 4],
+        );
+        msg.serialize(&mut mem).unwrap();
+        let done = Nlmsghdr::new(None, Nlmsg::Done, vec![NlmF::Multi], None, None, 0i32);
+        done.serialize(&mut mem).unwrap();
+        let sent = s.send_to(mem.as_ref(), s.port_id().unwrap(), 0, 0).unwrap();
+        assert_eq!(sent, mem.as_ref().len());
+
+        let mut iter = NlDumpIter::<u16, Vec<u8>> {
+            msgs: s.iter_with(IterationBehavior::EndMultiOnDone),
+            reported_intr: false,
+        };
+        assert_eq!(iter.next().unwrap().unwrap(), vec![1u8; 4]);
+        match iter.next() {
+            Some(Err(NlError::DumpInterrupted)) => (),
+            r => panic!("Expected an interrupted dump, got {:?}", r),
+        }
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_socket_recv_vectored() {
+        let mut s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        s.set_pktinfo(true).unwrap();
+        let msg = Nlmsghdr::new(
+            None,
+            100u16,
+            vec![],
+            None,
+            None,
+            vec![1u8, 2, 3, 4, 5, 6, 7, 8],
+        );
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        msg.serialize(&mut mem).unwrap();
+        for _ in 0..2 {
+            let sent = s.send_to(mem.as_ref(), s.port_id().unwrap(), 0, 0).unwrap();
+            assert_eq!(sent, mem.as_ref().len());
+        }
+
+        let mut header = [0u8; 16];
+        let mut payload = [0u8; 8];
+        let (read, info) = s
+            .recv_vectored(
+                &mut [
+                    io::IoSliceMut::new(&mut header),
+                    io::IoSliceMut::new(&mut payload),
+                ],
+                0,
+            )
+            .unwrap();
+        assert_eq!(read, 24);
+        assert_eq!(&header[..], &mem.as_ref()[..16]);
+        assert_eq!(payload, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(
+            info,
+            NlRecvInfo {
+                group: None,
+                sender: NlAddr {
+                    pid: s.port_id().unwrap(),
+                    groups: 0,
+                },
+                nsid: None,
+                truncated: false,
+            }
+        );
+
+        let (read, info) = s
+            .recv_vectored(&mut [io::IoSliceMut::new(&mut header)], 0)
+            .unwrap();
+        assert_eq!(read, 16);
+        assert!(info.truncated);
+    }
+
+    #[test]
+    fn test_socket_mcast_memberships() {
+        let mut s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        assert!(s.list_mcast_memberships().unwrap().is_empty());
+        s.add_mcast_membership(&[3, 32]).unwrap();
+        assert_eq!(s.list_mcast_memberships().unwrap(), vec![3, 32]);
+        s.drop_mcast_membership(&[3]).unwrap();
+        assert_eq!(s.list_mcast_memberships().unwrap(), vec![32]);
+    }
+
+    #[test]
+    fn test_socket_with_options() {
+        let cloexec = |s: &NlSocket| unsafe { libc::fcntl(s.as_raw_fd(), libc::F_GETFD) } & libc::FD_CLOEXEC != 0;
+
+        let s = NlSocket::new(NlFamily::Route, false).unwrap();
+        assert!(cloexec(&s));
+        assert!(s.is_blocking().unwrap());
+
+        let s = NlSocket::with_options(
+            NlFamily::Route,
+            NlSocketOptions::new().cloexec(false).nonblock(true),
+        )
+        .unwrap();
+        assert!(!cloexec(&s));
+        assert!(!s.is_blocking().unwrap());
+    }
+
+    #[test]
+    fn test_socket_bind_port() {
+        assert_eq!(NlSocket::local_port_id(1 << 10), None);
+        let ports = [
+            NlSocket::local_port_id(1).unwrap(),
+            NlSocket::local_port_id(2).unwrap(),
+        ];
+        assert_ne!(ports[0], ports[1]);
+        let s1 = NlSocket::connect_port(NlFamily::Route, ports[0], None, false).unwrap();
+        let s2 = NlSocket::connect_port(NlFamily::Route, ports[1], None, false).unwrap();
+        assert_eq!(s1.port_id().unwrap(), ports[0]);
+        assert_eq!(s2.port_id().unwrap(), ports[1]);
+        let err = NlSocket::connect_port(NlFamily::Route, ports[0], None, false)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+    }
+
+    #[test]
+    fn test_socket_bind_pid_with_groups() {
+        let port = NlSocket::local_port_id(3).unwrap();
+        let mut s =
+            NlSocket::connect(NlFamily::Usersock, Some(port), Some(vec![10]), false).unwrap();
+        assert_eq!(s.port_id().unwrap(), port);
+
+        let sender = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let sender_port = sender.port_id().unwrap();
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        Nlmsghdr::new(None, 100u16, vec![], None, Some(sender_port), 5u32)
+            .serialize(&mut mem)
+            .unwrap();
+        // Broadcasting succeeds even though delivery to port 0 fails - see `test_socket_pktinfo`
+        sender.send_to(mem.as_ref(), 0, 1 << 9, 0).unwrap_err();
+
+        let msg = s.recv_nl::<u16, u32>(None).unwrap();
+        assert_eq!(msg.nl_pid, sender_port);
+        assert_eq!(msg.nl_payload, 5);
+    }
+
+    #[test]
+    fn test_socket_mcast_group_above_32() {
+        // RTNLGRP_BRVLAN
+        let group = 33;
+        let mut s = NlSocket::connect(NlFamily::Route, None, Some(vec![1, group]), false).unwrap();
+        assert_eq!(s.list_mcast_memberships().unwrap(), vec![1, group]);
+        s.drop_mcast_membership(&[group]).unwrap();
+        assert_eq!(s.list_mcast_memberships().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_socket_check_seq() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        s.set_seq_policy(CheckPolicy::Strict);
+        let noop = || {
+            Nlmsghdr::new(
+                None,
+                Nlmsg::Noop,
+                vec![NlmF::Request, NlmF::Ack],
+                None,
+                None,
+                NlEmpty,
+            )
+        };
 
-        #[cfg(feature = "logging")]
-        log!("Message sent:\n{:#?}", msg);
+        s.send_nl(noop()).unwrap();
+        s.recv_ack().unwrap();
+        s.send_all(vec![noop(), noop()]).unwrap();
+        s.recv_ack().unwrap();
+        s.recv_ack().unwrap();
 
-        let mut mem = StreamWriteBuffer::new_growable(Some(msg.asize()));
-        msg.serialize(&mut mem)?;
-        self.send(mem, 0)?;
+        // The ACK of the first message does not answer the last send
+        s.send_nl(noop()).unwrap();
+        s.increment_seq();
+        s.send_nl(noop()).unwrap();
+        match s.recv_nl::<Nlmsg, Nlmsgerr<Nlmsg>>(None) {
+            Err(NlError::BadSeq) => (),
+            r => panic!("Expected BadSeq, got {:?}", r),
+        }
+        let ack = s.recv_nl::<Nlmsg, Nlmsgerr<Nlmsg>>(None).unwrap();
+        assert_eq!(ack.nl_seq, 5);
+    }
 
-        if let Some(ref mut seq) = self.seq {
-            *seq += 1;
+    #[test]
+    fn test_socket_check_policy() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        let port = s.port_id().unwrap();
+        s.pid = Some(port + 1);
+        let noop = || {
+            Nlmsghdr::new(
+                None,
+                Nlmsg::Noop,
+                vec![NlmF::Request, NlmF::Ack],
+                None,
+                None,
+                NlEmpty,
+            )
+        };
+
+        s.send_nl(noop()).unwrap();
+        match s.recv_ack() {
+            Err(NlError::BadPid) => (),
+            r => panic!("Expected BadPid, got {:?}", r),
         }
+        s.set_pid_policy(CheckPolicy::Warn);
+        s.send_nl(noop()).unwrap();
+        s.recv_ack().unwrap();
 
-        Ok(())
+        s.set_pid_policy(CheckPolicy::Off);
+        s.set_seq_policy(CheckPolicy::Warn);
+        s.send_nl(noop()).unwrap();
+        s.increment_seq();
+        s.send_nl(noop()).unwrap();
+        let ack = s.recv_nl::<Nlmsg, Nlmsgerr<Nlmsg>>(None).unwrap();
+        assert_eq!(ack.nl_seq, 0);
+        s.recv_ack().unwrap();
     }
 
-    /// Convenience function to begin receiving a stream of `Nlmsghdr` structs
-    pub fn recv_nl<T, P>(&mut self, buf_sz: Option<usize>) -> Result<Nlmsghdr<T, P>, NlError>
-    where
-        T: Nl + NlType + Debug,
-        P: Nl + Debug,
-    {
-        if self.buffer.is_none() {
-            let mut mem = vec![0; buf_sz.unwrap_or(MAX_NL_LENGTH)];
-            let mem_read = self.recv(&mut mem, 0)?;
-            if mem_read == 0 {
-                return Err(NlError::new("No data could be read from the socket"));
+    #[test]
+    fn test_socket_split() {
+        let s = NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+        let port = s.port_id().unwrap();
+        let (mut reader, mut writer) = s.split().unwrap();
+        assert_eq!(reader.as_socket().port_id().unwrap(), port);
+        assert_eq!(writer.as_socket().port_id().unwrap(), port);
+
+        let sender = std::thread::spawn(move || {
+            for _ in 0..2 {
+                let msg = Nlmsghdr::new(
+                    None,
+                    Nlmsg::Noop,
+                    vec![NlmF::Request, NlmF::Ack],
+                    None,
+                    None,
+                    NlEmpty,
+                );
+                writer.send_nl(msg).unwrap();
             }
-            mem.truncate(mem_read as usize);
-            self.buffer = Some(StreamReadBuffer::new(mem));
+        });
+        for seq in 0..2 {
+            let ack = reader.recv_nl::<Nlmsg, Nlmsgerr<Nlmsg>>(None).unwrap();
+            assert_eq!(ack.nl_seq, seq);
+            assert_eq!(ack.nl_payload.error, 0);
         }
-        let msg = match self.buffer {
-            Some(ref mut b) => Nlmsghdr::deserialize(b)?,
-            None => unreachable!(),
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn test_socket_send_and_await_ack() {
+        use crate::{
+            consts::{RtAddrFamily, Rtm},
+            rtnl::Ifinfomsg,
         };
 
-        #[cfg(feature = "logging")]
-        log!("Message received:\n{:#?}", msg);
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+        s.send_and_await_ack(Nlmsghdr::new(
+            None,
+            Nlmsg::Noop,
+            vec![NlmF::Request],
+            None,
+            None,
+            NlEmpty,
+        ))
+        .unwrap();
 
-        match self.pid {
-            // PID checking turned off.
-            None => (),
-            // No PID set yet, store the current one.
-            Some(0) => self.pid = Some(msg.nl_pid),
-            // PID check OK
-            Some(pid) if pid == msg.nl_pid => (),
-            // PID doesn't match
-            Some(_) => return Err(NlError::BadPid),
-        }
-        if self.buffer.as_ref().map(|b| b.at_end()).unwrap_or(false) {
-            self.buffer = None;
+        // The reply with the loopback interface is discarded
+        let mut ifi = Ifinfomsg::dump(RtAddrFamily::Unspecified);
+        ifi.ifi_index = 1;
+        let request = Nlmsghdr::new(None, Rtm::Getlink, vec![NlmF::Request], None, None, ifi);
+        s.send_and_await_ack(request).unwrap();
+        s.set_nonblocking(true).unwrap();
+        match s.recv_nl::<u16, Vec<u8>>(None) {
+            Err(NlError::WouldBlock) => (),
+            r => panic!("Expected no message, got {:?}", r),
         }
-        Ok(msg)
-    }
+        s.set_nonblocking(false).unwrap();
 
-    /// Consume an ACK and return an error if an ACK is not found
-    pub fn recv_ack(&mut self) -> Result<(), NlError> {
-        if let Ok(ack) = self.recv_nl::<consts::Nlmsg, Nlmsgerr<consts::Nlmsg>>(None) {
-            if ack.nl_type == consts::Nlmsg::Error && ack.nl_payload.error == 0 {
-                // PID check done as part of recv_nl already
-                if let Some(seq) = self.seq {
-                    if seq != ack.nl_seq {
-                        return Err(NlError::BadSeq);
-                    }
-                }
-                Ok(())
-            } else {
-                if let Some(b) = self.buffer.as_mut() {
-                    b.rewind()
-                }
-                if ack.nl_type == consts::Nlmsg::Error {
-                    let err = std::io::Error::from_raw_os_error(-ack.nl_payload.error as _);
-                    Err(NlError::Msg(err.to_string()))
-                } else {
-                    Err(NlError::NoAck)
-                }
-            }
-        } else {
-            Err(NlError::NoAck)
+        let request = Nlmsghdr::new(
+            None,
+            Rtm::from(0xffff),
+            vec![NlmF::Request],
+            None,
+            None,
+            NlEmpty,
+        );
+        match s.send_and_await_ack(request) {
+            Err(NlError::Errno(_)) | Err(NlError::ExtAck { .. }) => (),
+            r => panic!("Expected an error, got {:?}", r),
         }
     }
 
-    /// Return an iterator object
-    pub fn iter<T, P>(&mut self) -> NlMessageIter<T, P>
-    where
-        T: NlType,
-        P: Nl,
-    {
-        NlMessageIter::new(self)
+    #[test]
+    fn test_socket_get_genl_family() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        let family = s.get_genl_family("nlctrl").unwrap();
+        assert_eq!(family.id, u16::from(GenlId::Ctrl));
+        assert_eq!(family.name, "nlctrl");
+        assert!(family.version > 0);
+        let getfamily = family.op(u8::from(CtrlCmd::Getfamily).into()).unwrap();
+        assert!(getfamily.flags.contains(&GenlOpFlag::CmdCapDo));
+        assert!(getfamily.flags.contains(&GenlOpFlag::CmdCapDump));
+        assert!(family.mcast_group("notify").is_some());
+        assert!(s.get_genl_family("neli-no-such-family").is_err());
     }
-}
 
-impl AsRawFd for NlSocket {
-    fn as_raw_fd(&self) -> RawFd {
-        self.fd
-    }
-}
+    #[test]
+    fn test_socket_get_all_genl_families() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        let families = s.get_all_genl_families().unwrap();
+        let nlctrl = families.iter().find(|f| f.name == "nlctrl").unwrap();
+        assert_eq!(*nlctrl, s.get_genl_family("nlctrl").unwrap());
 
-impl IntoRawFd for NlSocket {
-    fn into_raw_fd(self) -> RawFd {
-        let fd = self.fd;
-        std::mem::forget(self);
-        fd
+        s.set_genl_cache(true).unwrap();
+        let families = s.get_all_genl_families().unwrap();
+        let cache = s.genl_cache.as_ref().unwrap();
+        assert_eq!(cache.families.len(), families.len());
     }
-}
 
-impl FromRawFd for NlSocket {
-    unsafe fn from_raw_fd(fd: RawFd) -> Self {
-        NlSocket {
-            fd,
-            buffer: None,
-            pid: None,
-            seq: None,
-        }
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::new()
+            .max_attempts(0)
+            .backoff(Duration::from_millis(10), Duration::from_millis(35));
+        assert_eq!(policy.max_attempts, 1);
+        assert_eq!(policy.delay(0), Duration::from_millis(10));
+        assert_eq!(policy.delay(1), Duration::from_millis(20));
+        assert_eq!(policy.delay(2), Duration::from_millis(35));
+        assert_eq!(policy.delay(100), Duration::from_millis(35));
+
+        assert!(policy.is_retryable(&NlError::Errno(libc::EBUSY)));
+        assert!(policy.is_retryable(&NlError::Overrun));
+        assert!(policy.is_retryable(&NlError::DumpInterrupted));
+        assert!(!policy.is_retryable(&NlError::WouldBlock));
+        assert!(!policy.is_retryable(&NlError::Errno(libc::ENODEV)));
+        let policy = policy
+            .retry_errnos(vec![libc::ENODEV])
+            .retry_dump_interrupted(false);
+        assert!(policy.is_retryable(&NlError::Errno(libc::ENODEV)));
+        assert!(!policy.is_retryable(&NlError::Overrun));
+        assert!(!policy.is_retryable(&NlError::DumpInterrupted));
     }
-}
 
-#[cfg(feature = "stream")]
-pub mod tokio {
-    //! Tokio-specific features for neli
-    //!
-    //! This module contains a struct that wraps `NlSocket` for async IO.
-    use super::*;
+    #[test]
+    fn test_socket_retry_policy() {
+        use crate::{
+            consts::{RtAddrFamily, Rtm},
+            rtnl::Ifinfomsg,
+        };
 
-    use std::{
-        pin::Pin,
-        task::{Context, Poll},
-    };
+        let getlink = || {
+            let mut ifi = Ifinfomsg::dump(RtAddrFamily::Unspecified);
+            ifi.ifi_index = i32::MAX;
+            Nlmsghdr::request(Rtm::Getlink, ifi)
+        };
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+        let seq = s.seq.unwrap();
+        let err = s.send_and_await_ack(getlink()).unwrap_err();
+        assert_eq!(err.errno(), Some(libc::ENODEV));
+        assert_eq!(s.seq, Some(seq + 1));
 
-    use ::tokio::{
-        io::{AsyncRead, PollEvented},
-        stream::Stream,
-    };
-    use mio::{self, Evented};
+        // Each attempt is sent with its own sequence number
+        s.set_retry_policy(
+            RetryPolicy::new()
+                .retry_errnos(vec![libc::ENODEV])
+                .backoff(Duration::from_millis(1), Duration::from_millis(1)),
+        );
+        let err = s.send_and_await_ack(getlink()).unwrap_err();
+        assert_eq!(err.errno(), Some(libc::ENODEV));
+        assert_eq!(s.seq, Some(seq + 4));
 
-    fn poll_read_priv(
-        socket: &mut PollEvented<super::NlSocket>,
-        cx: &mut Context,
-        buf: &mut [u8],
-    ) -> Poll<io::Result<usize>> {
-        Pin::new(socket).poll_read(cx, buf)
-    }
+        let mut request = getlink();
+        request.nl_payload.ifi_index = 0;
+        let links = s.dump_consistent::<_, _, Ifinfomsg>(request, 0).unwrap();
+        assert!(!links.is_empty());
+        assert_eq!(s.seq, Some(seq + 5));
 
-    /// Tokio-enabled Netlink socket struct
-    pub struct NlSocket<T, P> {
-        socket: PollEvented<super::NlSocket>,
-        buffer: Option<StreamReadBuffer<Vec<u8>>>,
-        type_data: PhantomData<T>,
-        payload_data: PhantomData<P>,
+        s.clear_retry_policy();
+        s.send_and_await_ack(getlink()).unwrap_err();
+        assert_eq!(s.seq, Some(seq + 6));
     }
 
-    impl<'a, T, P> NlSocket<T, P>
-    where
-        T: NlType,
-    {
-        /// Setup NlSocket for use with tokio - set to nonblocking state and wrap in polling mechanism
-        pub fn new(mut socket: super::NlSocket) -> io::Result<Self> {
-            if socket.is_blocking()? {
-                socket.nonblock()?;
-            }
-            Ok(NlSocket {
-                socket: PollEvented::new(socket)?,
-                buffer: None,
-                type_data: PhantomData,
-                payload_data: PhantomData,
-            })
-        }
+    #[test]
+    fn test_socket_retry_mid_dump() {
+        let group = 8;
+        let mut s = NlSocket::connect(NlFamily::Usersock, None, Some(vec![group]), true).unwrap();
+        let rcvbuf: c_int = 0;
+        assert_eq!(
+            unsafe {
+                libc::setsockopt(
+                    s.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_RCVBUF,
+                    &rcvbuf as *const _ as *const c_void,
+                    size_of::<c_int>() as libc::socklen_t,
+                )
+            },
+            0
+        );
+        let peer = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        // Send the dump requests to the peer instead of the kernel
+        let mut peer_addr = unsafe { zeroed::<libc::sockaddr_nl>() };
+        peer_addr.nl_family = libc::AF_NETLINK as u16;
+        peer_addr.nl_pid = peer.port_id().unwrap();
+        assert_eq!(
+            unsafe {
+                libc::connect(
+                    s.as_raw_fd(),
+                    &peer_addr as *const _ as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+                )
+            },
+            0
+        );
 
-        /// Check if underlying received message buffer is empty
-        pub fn empty(&self) -> bool {
-            if let Some(ref buf) = self.buffer {
-                buf.at_end()
+        let first = s.seq.unwrap();
+        let port = s.port_id().unwrap();
+        let send = move |peer: &NlSocket, seq: u32, done: bool, fill: u8| {
+            let mut mem = StreamWriteBuffer::new_growable(None);
+            if done {
+                Nlmsghdr::new(None, Nlmsg::Done, vec![NlmF::Multi], Some(seq), None, 0i32)
+                    .serialize(&mut mem)
+                    .unwrap();
             } else {
-                true
+                Nlmsghdr::new(
+                    None,
+                    100u16,
+                    vec![NlmF::Multi],
+                    Some(seq),
+                    None,
+                    vec![fill; 4],
+                )
+                .serialize(&mut mem)
+                .unwrap();
             }
-        }
-    }
+            assert_eq!(
+                peer.send_to(mem.as_ref(), port, 0, 0).unwrap(),
+                mem.as_ref().len()
+            );
+        };
 
-    impl io::Read for super::NlSocket {
-        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-            if self.is_blocking()? {
-                self.nonblock()?;
-            }
-            self.recv(buf, 0).map(|i| i as usize)
+        // The first response is interrupted by dropped multicast messages after its first part
+        send(&peer, first, false, 1);
+        let flood = Nlmsghdr::new(None, 100u16, vec![], None, None, vec![0u8; 1024]);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        flood.serialize(&mut mem).unwrap();
+        for _ in 0..64 {
+            // Broadcasting succeeds even though delivery to port 0 fails - see
+            // `test_socket_pktinfo`
+            peer.send_to(mem.as_ref(), 0, 1 << (group - 1), 0)
+                .unwrap_err();
         }
+        // The rest of both responses is sent as soon as the receive buffer has room
+        let responder = thread::spawn(move || {
+            send(&peer, first, false, 2);
+            send(&peer, first, true, 0);
+            send(&peer, first + 1, false, 3);
+            send(&peer, first + 1, true, 0);
+            peer
+        });
+
+        s.set_retry_policy(
+            RetryPolicy::new().backoff(Duration::from_millis(1), Duration::from_millis(1)),
+        );
+        let request = Nlmsghdr::new(None, 100u16, vec![NlmF::Request], None, None, NlEmpty);
+        let payloads = s.dump_consistent::<_, _, Vec<u8>>(request, 0).unwrap();
+        assert_eq!(payloads, vec![vec![3u8; 4]]);
+        assert_eq!(s.seq, Some(first + 2));
+        responder.join().unwrap();
     }
 
-    impl io::Write for super::NlSocket {
-        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-            if self.is_blocking()? {
-                self.nonblock()?;
-            }
-            self.send(buf, 0)
-        }
+    #[test]
+    fn test_socket_get_links() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        let links = s.get_links().unwrap();
+        let lo = links.iter().find(|link| link.ifi_index == 1).unwrap();
+        assert!(lo.ifi_flags.contains(&consts::Iff::Loopback));
+        assert_eq!(lo.name().unwrap().as_deref(), Some("lo"));
+        assert!(lo.mtu().unwrap().unwrap() > 0);
+        assert_eq!(
+            lo.address().unwrap(),
+            Some(crate::types::MacAddress::default())
+        );
+        assert!(lo.operstate().unwrap().is_some());
+        assert_eq!(lo.master().unwrap(), None);
+        assert!(lo.stats().unwrap().is_some());
+    }
 
-        fn flush(&mut self) -> io::Result<()> {
-            Ok(())
-        }
+    #[test]
+    fn test_socket_get_addrs() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        let addrs = s.get_addrs(RtAddrFamily::Inet).unwrap();
+        assert!(addrs
+            .iter()
+            .all(|addr| addr.ifa_family == RtAddrFamily::Inet));
+        assert!(addrs.iter().any(|addr| {
+            addr.ifa_index == 1
+                && addr
+                    .rtattrs
+                    .get_attr_payload_as::<Vec<u8>>(consts::Ifa::Local)
+                    .unwrap()
+                    == Some(vec![127, 0, 0, 1])
+        }));
     }
 
-    impl<T, P> AsyncRead for NlSocket<T, P> {
-        fn poll_read(
-            mut self: Pin<&mut Self>,
-            cx: &mut Context,
-            buf: &mut [u8],
-        ) -> Poll<io::Result<usize>> {
-            let mut mut_ref = self.as_mut();
-            poll_read_priv(&mut mut_ref.socket, cx, buf)
-        }
+    #[test]
+    fn test_socket_get_neighbors() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        let neighbors = s.get_neighbors(RtAddrFamily::Inet).unwrap();
+        assert!(neighbors
+            .iter()
+            .all(|neigh| neigh.ndm_family == RtAddrFamily::Inet && neigh.ndm_index > 0));
     }
 
-    impl<T, P> Stream for NlSocket<T, P>
-    where
-        T: NlType,
-        P: Nl,
-    {
-        type Item = std::io::Result<Nlmsghdr<T, P>>;
+    #[test]
+    fn test_socket_get_tc() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        let qdiscs = s.get_qdiscs().unwrap();
+        let lo = qdiscs.iter().find(|qdisc| qdisc.tcm_ifindex == 1).unwrap();
+        assert_eq!(lo.parent(), TcHandle::ROOT);
+        assert_eq!(lo.kind().unwrap().as_deref(), Some("noqueue"));
 
-        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-            if self.empty() {
-                let mut mem = vec![0; MAX_NL_LENGTH];
-                let mut mut_ref = self.as_mut();
-                let bytes_read = match poll_read_priv(&mut mut_ref.socket, cx, &mut mem) {
-                    Poll::Ready(Ok(0)) => return Poll::Ready(None),
-                    Poll::Ready(Ok(i)) => i,
-                    Poll::Pending => return Poll::Pending,
-                    Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
-                };
-                mem.truncate(bytes_read);
+        // noqueue has neither classes nor filters
+        assert!(s.get_tc_classes(1).unwrap().is_empty());
+        assert!(s.get_tc_filters(1, TcHandle::ROOT).unwrap().is_empty());
+    }
 
-                mut_ref.buffer = Some(StreamReadBuffer::new(mem));
-            }
+    #[test]
+    fn test_socket_iter_routes() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        let local = s
+            .iter_routes(RtAddrFamily::Inet, RtTable::Local.into())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(local
+            .iter()
+            .all(|route| route.table().unwrap() == u32::from(RtTable::Local)));
+        assert!(local.iter().any(|route| {
+            route.rtm_type == consts::Rtn::Local
+                && route
+                    .rtattrs
+                    .get_attr_payload_as::<Vec<u8>>(consts::Rta::Dst)
+                    .unwrap()
+                    == Some(vec![127, 0, 0, 1])
+        }));
 
-            match self.as_mut().buffer {
-                Some(ref mut buf) => {
-                    Poll::Ready(Some(Ok(Nlmsghdr::<T, P>::deserialize(buf)
-                        .map_err(|_| io::ErrorKind::InvalidData)?)))
-                }
-                None => Poll::Ready(Some(Err(io::Error::from(io::ErrorKind::UnexpectedEof)))),
-            }
-        }
+        let all = s
+            .iter_routes(RtAddrFamily::Inet, RtTable::Unspec.into())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(all.len() >= local.len());
+        assert!(all
+            .iter()
+            .all(|route| route.rtm_family == RtAddrFamily::Inet));
     }
 
-    impl<T, P> Unpin for NlSocket<T, P> {}
-
-    impl Evented for super::NlSocket {
-        fn register(
-            &self,
-            poll: &mio::Poll,
-            token: mio::Token,
-            interest: mio::Ready,
-            opts: mio::PollOpt,
-        ) -> io::Result<()> {
-            poll.register(
-                &mio::unix::EventedFd(&self.as_raw_fd()),
-                token,
-                interest,
-                opts,
-            )
-        }
+    #[test]
+    fn test_socket_route_in_table() {
+        use crate::rtnl::Rtattr;
 
-        fn reregister(
-            &self,
-            poll: &mio::Poll,
-            token: mio::Token,
-            interest: mio::Ready,
-            opts: mio::PollOpt,
-        ) -> io::Result<()> {
-            poll.reregister(
-                &mio::unix::EventedFd(&self.as_raw_fd()),
-                token,
-                interest,
-                opts,
-            )
-        }
+        let main = u32::from(RtTable::Main);
+        let main_route = || {
+            let mut route = Rtmsg::dump(RtAddrFamily::Inet);
+            route.rtm_table = RtTable::Main;
+            route
+        };
+        assert!(NlSocket::route_in_table(Ok(main_route()), main)
+            .unwrap()
+            .is_ok());
+        assert!(NlSocket::route_in_table(Ok(main_route()), RtTable::Local.into()).is_none());
+        assert!(
+            NlSocket::route_in_table(Ok(main_route()), RtTable::Unspec.into())
+                .unwrap()
+                .is_ok()
+        );
 
-        fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
-            poll.deregister(&mio::unix::EventedFd(&self.as_raw_fd()))
+        // A table attribute too short for its type is reported instead of matching every table
+        let mut route = main_route();
+        route
+            .rtattrs
+            .push(Rtattr::new(consts::Rta::Table, vec![1u8, 2]).unwrap());
+        match NlSocket::route_in_table(Ok(route), main) {
+            Some(Err(NlError::Msg(_))) => (),
+            r => panic!("Expected an error, got {:?}", r),
         }
     }
 
-    impl Evented for &mut super::NlSocket {
-        fn register(
-            &self,
-            poll: &mio::Poll,
-            token: mio::Token,
-            interest: mio::Ready,
-            opts: mio::PollOpt,
-        ) -> io::Result<()> {
-            <super::NlSocket as Evented>::register(self, poll, token, interest, opts)
-        }
+    #[test]
+    fn test_socket_addr_events() {
+        use crate::rtnl::{Rtattr, Rtattrs};
 
-        fn reregister(
-            &self,
-            poll: &mio::Poll,
-            token: mio::Token,
-            interest: mio::Ready,
-            opts: mio::PollOpt,
-        ) -> io::Result<()> {
-            <super::NlSocket as Evented>::reregister(self, poll, token, interest, opts)
+        fn addr(nl_type: Rtm, flags: Vec<NlmF>) -> Nlmsghdr<Rtm, Ifaddrmsg> {
+            let mut ifa = Ifaddrmsg::dump(RtAddrFamily::Inet);
+            ifa.ifa_prefixlen = 32;
+            ifa.ifa_index = 1;
+            ifa.rtattrs = Rtattrs::new(vec![
+                Rtattr::new(consts::Ifa::Local, vec![10u8, 1, 2, 3]).unwrap(),
+                Rtattr::new(consts::Ifa::Address, vec![10u8, 1, 2, 3]).unwrap(),
+            ]);
+            Nlmsghdr::new(None, nl_type, flags, None, None, ifa)
         }
 
-        fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
-            <super::NlSocket as Evented>::deregister(self, poll)
-        }
+        // Change addresses in a network namespace of its own to leave the host untouched
+        std::thread::spawn(|| {
+            assert_eq!(unsafe { libc::unshare(libc::CLONE_NEWNET) }, 0);
+            let mut events = NlSocket::connect(
+                NlFamily::Route,
+                None,
+                Some(vec![u32::from(consts::Rtnlgrp::Ipv4Ifaddr)]),
+                false,
+            )
+            .unwrap();
+            let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+
+            s.send_and_await_ack(addr(
+                Rtm::Newaddr,
+                vec![NlmF::Request, NlmF::Ack, NlmF::Create, NlmF::Excl],
+            ))
+            .unwrap();
+            let event = events.recv_nl::<Rtm, Ifaddrmsg>(None).unwrap();
+            assert_eq!(event.nl_type, Rtm::Newaddr);
+            assert_eq!(event.nl_payload.ifa_index, 1);
+            assert_eq!(event.nl_payload.ifa_prefixlen, 32);
+            assert!(s
+                .get_addrs(RtAddrFamily::Inet)
+                .unwrap()
+                .iter()
+                .any(|addr| addr.ifa_index == 1));
+
+            s.send_and_await_ack(addr(Rtm::Deladdr, vec![NlmF::Request, NlmF::Ack]))
+                .unwrap();
+            let event = events.recv_nl::<Rtm, Ifaddrmsg>(None).unwrap();
+            assert_eq!(event.nl_type, Rtm::Deladdr);
+            assert!(s.get_addrs(RtAddrFamily::Inet).unwrap().is_empty());
+        })
+        .join()
+        .unwrap();
     }
 
-    #[cfg(test)]
-    mod test {
-        use std::io::Read;
+    #[test]
+    fn test_socket_genl_cache() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        s.set_genl_cache(true).unwrap();
+        let id = s.resolve_genl_family("nlctrl").unwrap();
+        assert_eq!(id, u16::from(GenlId::Ctrl));
+        let group = s.resolve_nl_mcast_group("nlctrl", "notify").unwrap();
+        assert!(s
+            .genl_cache
+            .as_ref()
+            .unwrap()
+            .families
+            .contains_key("nlctrl"));
+        assert!(s
+            .resolve_nl_mcast_group("nlctrl", "neli-no-such-group")
+            .is_err());
 
-        use crate::socket::NlSocket;
+        // Families announced as removed are resolved again
+        let port = s.genl_cache.as_ref().unwrap().notify.port_id().unwrap();
+        let attrs = vec![Nlattr::new(None, CtrlAttr::FamilyName, "nlctrl").unwrap()];
+        let genl = Genlmsghdr::new(CtrlCmd::Delfamily, 2, attrs).unwrap();
+        let mut sender = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        sender
+            .send_nl_to(
+                Nlmsghdr::new(None, GenlId::Ctrl, vec![], None, None, genl),
+                port,
+                0,
+            )
+            .unwrap();
+        let cache = s.genl_cache.as_mut().unwrap();
+        cache.process_notifications();
+        assert!(cache.families.is_empty());
+        assert_eq!(s.resolve_nl_mcast_group("nlctrl", "notify").unwrap(), group);
+    }
 
-        use super::*;
+    #[test]
+    fn test_socket_send_all() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+        let msgs = (0..3).map(|_| {
+            Nlmsghdr::new(
+                None,
+                Nlmsg::Noop,
+                vec![NlmF::Request, NlmF::Ack],
+                None,
+                None,
+                NlEmpty,
+            )
+        });
+        s.send_all(msgs).unwrap();
 
-        #[test]
-        fn test_socket_nonblock() {
-            let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
-            s.nonblock().unwrap();
-            assert_eq!(s.is_blocking().unwrap(), false);
-            let buf = &mut [0; 4];
-            match s.read(buf) {
-                Err(e) => {
-                    if e.kind() != io::ErrorKind::WouldBlock {
-                        panic!("Error: {}", e);
-                    }
-                }
-                Ok(_) => {
-                    panic!("Should not return data");
-                }
-            }
+        for seq in 0..3 {
+            let ack = s.recv_nl::<Nlmsg, Nlmsgerr<Nlmsg>>(None).unwrap();
+            assert_eq!(ack.nl_type, Nlmsg::Error);
+            assert_eq!(ack.nl_seq, seq);
+            assert_eq!(ack.nl_payload.error, 0);
         }
     }
-}
 
-impl Drop for NlSocket {
-    /// Closes underlying file descriptor to avoid file descriptor leaks.
-    fn drop(&mut self) {
-        unsafe {
-            libc::close(self.fd);
-        }
+    #[test]
+    fn test_socket_cap_ack() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        s.set_cap_ack(true).unwrap();
+        let attrs = vec![Nlattr::new(None, CtrlAttr::FamilyName, "neli-no-such-family").unwrap()];
+        let genl = Genlmsghdr::new(CtrlCmd::Getfamily, 2, attrs).unwrap();
+        let msg = Nlmsghdr::new(
+            None,
+            GenlId::Ctrl,
+            vec![NlmF::Request, NlmF::Ack],
+            None,
+            None,
+            genl,
+        );
+        let nl_len = msg.nl_len;
+        s.send_nl(msg).unwrap();
+
+        let err = s.recv_nl::<Nlmsg, Nlmsgerr<Nlmsg>>(None).unwrap();
+        assert_eq!(err.nl_type, Nlmsg::Error);
+        assert_ne!(err.nl_payload.error, 0);
+        assert_eq!(err.nl_payload.nlmsg.nl_len, nl_len);
+        assert!(err
+            .nl_payload
+            .request_payload(&err.nl_flags)
+            .unwrap()
+            .is_empty());
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    #[test]
+    fn test_proto_socket() {
+        let mut s = NlProtoSocket::<protocol::Generic>::connect(None, None, false).unwrap();
+        let id = u16::from(GenlId::Ctrl);
 
-    use crate::consts::Nlmsg;
+        let genl = Genlmsghdr::<_, CtrlAttr>::new(CtrlCmd::Getfamily, 2, Vec::new()).unwrap();
+        let msg = Nlmsghdr::new(None, id, vec![NlmF::Request, NlmF::Dump], None, None, genl);
+        s.send_nl(msg).unwrap();
+        let reply = s
+            .recv_nl::<u16, Genlmsghdr<CtrlCmd, CtrlAttr>>(None)
+            .unwrap();
+        assert_eq!(reply.nl_type, id);
+    }
 
     #[test]
-    fn test_socket_nonblock() {
+    fn test_send_buffer_reuse() {
         let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
-        s.nonblock().unwrap();
-        assert_eq!(s.is_blocking().unwrap(), false);
-        let buf = &mut [0; 4];
-        match s.recv(buf, 0) {
-            Err(e) => {
-                if e.kind() != io::ErrorKind::WouldBlock {
-                    panic!("Error: {}", e);
-                }
-            }
-            Ok(_) => {
-                panic!("Should not return data");
-            }
-        }
+        let msg = || {
+            Nlmsghdr::new(
+                None,
+                Nlmsg::Noop,
+                Vec::new(),
+                None,
+                None,
+                vec![1u8, 2, 3, 4, 5, 6, 7, 8],
+            )
+        };
+        s.send_nl(msg()).unwrap();
+        let capacity = s.send_buffer.capacity();
+        assert_eq!(s.send_buffer.len(), 24);
+
+        s.send_nl(msg()).unwrap();
+        assert_eq!(s.send_buffer.len(), 24);
+        assert_eq!(s.send_buffer.capacity(), capacity);
+
+        s.shrink_send_buffer();
+        assert_eq!(s.send_buffer.capacity(), 0);
     }
 
     #[test]
@@ -733,8 +4660,19 @@ mod test {
         let mut s = NlSocket {
             fd: -1,
             buffer: Some(StreamReadBuffer::new(vec)),
+            send_buffer: Vec::new(),
             seq: None,
             pid: None,
+            de_mode: DeMode::default(),
+            recv_info: NlRecvInfo::default(),
+            sent_seqs: None,
+            pid_policy: CheckPolicy::Strict,
+            seq_policy: CheckPolicy::Off,
+            overrun_handler: None,
+            grow_recv_buffer: false,
+            interrupt_policy: InterruptPolicy::Return,
+            genl_cache: None,
+            retry_policy: None,
         };
         let mut iter = s.iter();
         if let Some(Ok(nl_next)) = iter.next() {
@@ -0,0 +1,608 @@
+//! Data types that commonly appear in netlink payloads but have no direct equivalent in the
+//! standard library.
+//!
+//! # Design decisions
+//!
+//! These types are thin wrappers around their binary representation so that they can be used as
+//! attribute payloads and struct members with the same `Nl` workflow as primitive types while
+//! providing the conversions and formatting that a raw `Vec<u8>` cannot.
+//!
+//! The `Be*` integer types exist because `Nl` implementations for integers always use native
+//! byte order. Netfilter and `NLA_F_NET_BYTEORDER` payloads are big endian on the wire so wrapping
+//! those fields in `BeU16`, `BeU32`, or `BeU64` makes the byte order part of the type.
+//!
+//! `NlString` is backed by a `CString` instead of a `String` so that the null terminator
+//! required by the kernel is an invariant of the type rather than something to check for when
+//! serializing.
+
+use std::{
+    ffi::{CStr, CString},
+    fmt::{self, Display},
+    io::{Read, Write},
+    mem,
+    str::FromStr,
+};
+
+use buffering::{StreamReadBuffer, StreamWriteBuffer};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    consts::alignto,
+    err::{DeError, SerError},
+    Nl,
+};
+
+macro_rules! impl_be {
+    ( $(#[$outer:meta])* $name:ident, $ty:ty, $write:ident, $read:ident ) => {
+        $(#[$outer])*
+        #[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct $name($ty);
+
+        impl $name {
+            /// Wrap a value in native byte order
+            pub fn new(v: $ty) -> Self {
+                $name(v)
+            }
+
+            /// Get the wrapped value in native byte order
+            pub fn get(self) -> $ty {
+                self.0
+            }
+        }
+
+        impl From<$ty> for $name {
+            fn from(v: $ty) -> Self {
+                $name(v)
+            }
+        }
+
+        impl From<$name> for $ty {
+            fn from(v: $name) -> Self {
+                v.0
+            }
+        }
+
+        impl Nl for $name {
+            fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+                mem.$write::<BigEndian>(self.0)?;
+                Ok(())
+            }
+
+            fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+            where
+                T: AsRef<[u8]>,
+            {
+                Ok($name(mem.$read::<BigEndian>()?))
+            }
+
+            fn size(&self) -> usize {
+                mem::size_of::<$ty>()
+            }
+
+            fn type_size() -> Option<usize> {
+                Some(mem::size_of::<$ty>())
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+impl_be!(
+    /// `u16` that is serialized in network (big endian) byte order
+    BeU16, u16, write_u16, read_u16
+);
+
+impl_be!(
+    /// `u32` that is serialized in network (big endian) byte order
+    BeU32, u32, write_u32, read_u32
+);
+
+impl_be!(
+    /// `u64` that is serialized in network (big endian) byte order
+    BeU64, u64, write_u64, read_u64
+);
+
+/// Hardware (MAC) address as found in attributes like `IFLA_ADDRESS` or `NDA_LLADDR`
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MacAddress(pub [u8; 6]);
+
+impl MacAddress {
+    /// Create a new MAC address from its octets
+    pub fn new(octets: [u8; 6]) -> Self {
+        MacAddress(octets)
+    }
+
+    /// Get the octets of the MAC address
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+}
+
+impl From<[u8; 6]> for MacAddress {
+    fn from(octets: [u8; 6]) -> Self {
+        MacAddress(octets)
+    }
+}
+
+impl From<MacAddress> for [u8; 6] {
+    fn from(addr: MacAddress) -> Self {
+        addr.0
+    }
+}
+
+impl Nl for MacAddress {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        self.0.serialize(mem)
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        Ok(MacAddress(<[u8; 6]>::deserialize(mem)?))
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+
+    fn type_size() -> Option<usize> {
+        <[u8; 6]>::type_size()
+    }
+}
+
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            self.0[0], self.0[1], self.0[2], self.0[3], self.0[4], self.0[5]
+        )
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = DeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut octets = [0; 6];
+        let mut split = s.split(':');
+        for octet in octets.iter_mut() {
+            let next = split
+                .next()
+                .ok_or_else(|| DeError::new("MAC address has fewer than 6 octets"))?;
+            if next.len() != 2 {
+                return Err(DeError::new("MAC address octets must be two hex digits"));
+            }
+            *octet = u8::from_str_radix(next, 16)
+                .map_err(|_| DeError::new("MAC address octet is not valid hex"))?;
+        }
+        if split.next().is_some() {
+            return Err(DeError::new("MAC address has more than 6 octets"));
+        }
+        Ok(MacAddress(octets))
+    }
+}
+
+/// Null terminated string as found in attributes like `CTRL_ATTR_FAMILY_NAME` or `IFLA_IFNAME`
+///
+/// Serialization always writes the terminating null byte. If a size hint is set on the buffer,
+/// the string is padded with null bytes up to the size hint so that fixed width fields like
+/// `ifname[IFNAMSIZ]` can be written. Alignment padding is added by the attribute or header
+/// containing the string as described in the `nlattr` module.
+///
+/// Deserialization consumes the size hint (or the rest of the buffer if no size hint is
+/// provided) and returns an error if the data is not null terminated or if anything other than
+/// padding follows the terminator.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NlString(CString);
+
+impl NlString {
+    /// Create a new string - returns an error if the string contains a null byte
+    pub fn new<S>(s: S) -> Result<Self, SerError>
+    where
+        S: Into<Vec<u8>>,
+    {
+        Ok(NlString(CString::new(s).map_err(|_| {
+            SerError::new("Unable to serialize string containing null byte")
+        })?))
+    }
+
+    /// Get the string as a `&str` - returns an error if the string is not valid UTF-8
+    pub fn as_str(&self) -> Result<&str, DeError> {
+        Ok(self.0.to_str()?)
+    }
+
+    /// Get the underlying C string
+    pub fn as_c_str(&self) -> &CStr {
+        self.0.as_c_str()
+    }
+
+    /// Convert into a `String` - returns an error if the string is not valid UTF-8
+    pub fn into_string(self) -> Result<String, DeError> {
+        self.0
+            .into_string()
+            .map_err(|_| DeError::new("String is not valid UTF-8"))
+    }
+}
+
+impl From<CString> for NlString {
+    fn from(s: CString) -> Self {
+        NlString(s)
+    }
+}
+
+impl From<NlString> for CString {
+    fn from(s: NlString) -> Self {
+        s.0
+    }
+}
+
+impl Nl for NlString {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        let bytes = self.0.as_bytes_with_nul();
+        mem.write_all(bytes)?;
+        if let Some(sh) = mem.take_size_hint() {
+            let padding_len = sh.checked_sub(bytes.len()).ok_or_else(|| {
+                SerError::new("String does not fit in the space provided by the size hint")
+            })?;
+            mem.write_all(&vec![0; padding_len])?;
+        }
+        Ok(())
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        let mut v = match mem.take_size_hint() {
+            Some(sh) => {
+                let mut v = vec![0; sh];
+                mem.read_exact(v.as_mut_slice())?;
+                v
+            }
+            None => {
+                let mut v = Vec::new();
+                mem.read_to_end(&mut v)?;
+                v
+            }
+        };
+        let idx = v
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| DeError::new("String is not null terminated"))?;
+        if v[idx..].iter().any(|b| *b != 0) {
+            return Err(DeError::new("Found data after null terminator of string"));
+        }
+        v.truncate(idx + 1);
+        Ok(NlString(CStr::from_bytes_with_nul(&v)?.to_owned()))
+    }
+
+    fn size(&self) -> usize {
+        self.0.as_bytes_with_nul().len()
+    }
+}
+
+impl Display for NlString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.to_string_lossy())
+    }
+}
+
+/// Payload of a flag attribute (`NLA_FLAG`)
+///
+/// Flag attributes have no payload and the presence of the attribute means the flag is set.
+/// `Flag` serializes to zero bytes and fails to deserialize if the attribute carries any data.
+/// Use `AttrHandle::has_attribute` to check whether a flag was set in a parsed message.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flag;
+
+impl Nl for Flag {
+    fn serialize(&self, _mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        Ok(())
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        match mem.take_size_hint() {
+            Some(0) | None => Ok(Flag),
+            Some(_) => Err(DeError::new("Flag attribute must not have a payload")),
+        }
+    }
+
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(0)
+    }
+}
+
+/// Padding bytes in a kernel data structure
+///
+/// `Pad<N>` serializes to `N` zero bytes and skips `N` bytes when deserializing so that holes
+/// in C structs can be declared explicitly as fields. The number of bytes needed to reach
+/// alignment can be computed at compile time with `alignto`, for example
+/// `Pad<{ alignto(6) - 6 }>`.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pad<const N: usize>;
+
+impl<const N: usize> Nl for Pad<N> {
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        mem.write_all(&[0; N])?;
+        Ok(())
+    }
+
+    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        mem.read_exact(&mut [0; N])?;
+        Ok(Pad)
+    }
+
+    fn size(&self) -> usize {
+        N
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(N)
+    }
+}
+
+/// Array of `Nl` types where each element is aligned to netlink alignment
+///
+/// This is the representation used by attribute arrays, nexthop lists, and multipart dumps.
+/// Elements are deserialized until the region described by the size hint (or the rest of the
+/// buffer if no size hint is provided) is exhausted. Each element is assumed to occupy
+/// `asize()` bytes in the buffer so element types must be able to deserialize without a size
+/// hint.
+///
+/// `Vec<u8>` is a byte buffer and `Vec<Nlattr<_, _>>` has its own implementation so this is a
+/// separate type rather than an implementation for `Vec<T>`.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NlVec<T>(pub Vec<T>);
+
+impl<T> NlVec<T> {
+    /// Create a new array from a `Vec`
+    pub fn new(vec: Vec<T>) -> Self {
+        NlVec(vec)
+    }
+
+    /// Return the underlying `Vec`
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> From<Vec<T>> for NlVec<T> {
+    fn from(vec: Vec<T>) -> Self {
+        NlVec(vec)
+    }
+}
+
+impl<T> From<NlVec<T>> for Vec<T> {
+    fn from(vec: NlVec<T>) -> Self {
+        vec.0
+    }
+}
+
+impl<T> Nl for NlVec<T>
+where
+    T: Nl,
+{
+    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+        for item in self.0.iter() {
+            let mut item_mem = StreamWriteBuffer::new_growable(None);
+            item.serialize(&mut item_mem)?;
+            let bytes = item_mem.as_ref();
+            mem.write_all(bytes)?;
+            mem.write_all(&vec![0; alignto(bytes.len()) - bytes.len()])?;
+        }
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let region = match mem.take_size_hint() {
+            Some(sh) => {
+                let mut v = vec![0; sh];
+                mem.read_exact(v.as_mut_slice())?;
+                v
+            }
+            None => {
+                let mut v = Vec::new();
+                mem.read_to_end(&mut v)?;
+                v
+            }
+        };
+        let mut vec = Vec::new();
+        let mut offset = 0;
+        while offset < region.len() {
+            let item = T::deserialize(&mut StreamReadBuffer::new(&region[offset..]))?;
+            let len = item.asize();
+            if len == 0 {
+                return Err(DeError::new("Array elements must not be zero sized"));
+            }
+            offset += len;
+            vec.push(item);
+        }
+        if offset > region.len() {
+            return Err(DeError::new(
+                "Deserialization read passed the end of the specified buffer",
+            ));
+        }
+        Ok(NlVec(vec))
+    }
+
+    fn size(&self) -> usize {
+        self.0.iter().map(Nl::asize).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        consts::CtrlAttr,
+        nlattr::{AttrHandle, Nlattr},
+    };
+
+    #[test]
+    fn test_flag() {
+        let attrs = vec![
+            Nlattr::new(None, CtrlAttr::FamilyId, Flag).unwrap(),
+            Nlattr::new(None, CtrlAttr::FamilyName, 1u8).unwrap(),
+        ];
+        assert_eq!(attrs[0].asize(), 4);
+        let handle = AttrHandle::new(attrs);
+        assert!(handle.has_attribute(CtrlAttr::FamilyId));
+        assert!(!handle.has_attribute(CtrlAttr::Version));
+        assert_eq!(
+            handle
+                .get_attr_payload_as::<Flag>(CtrlAttr::FamilyId)
+                .unwrap(),
+            Flag
+        );
+        assert!(handle
+            .get_attr_payload_as::<Flag>(CtrlAttr::FamilyName)
+            .is_err());
+    }
+
+    #[test]
+    fn test_pad() {
+        let pad = Pad::<{ alignto(6) - 6 }>;
+        assert_eq!(pad.size(), 2);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        pad.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), &[0, 0]);
+
+        let mut mem = StreamReadBuffer::new(&[0, 0, 1]);
+        Pad::<2>::deserialize(&mut mem).unwrap();
+        assert_eq!(u8::deserialize(&mut mem).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_nl_vec() {
+        let v = NlVec::new(vec![1u16, 2u16]);
+        assert_eq!(v.size(), 8);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        v.serialize(&mut mem).unwrap();
+        let mut expected = 1u16.to_ne_bytes().to_vec();
+        expected.extend_from_slice(&[0, 0]);
+        expected.extend_from_slice(&2u16.to_ne_bytes());
+        expected.extend_from_slice(&[0, 0]);
+        assert_eq!(mem.as_ref(), expected.as_slice());
+
+        expected.extend_from_slice(&[9, 9, 9, 9]);
+        let mut mem = StreamReadBuffer::new(expected);
+        mem.set_size_hint(8);
+        assert_eq!(NlVec::<u16>::deserialize(&mut mem).unwrap(), v);
+        assert_eq!(u32::deserialize(&mut mem).unwrap(), 0x0909_0909);
+
+        let attrs = NlVec::new(vec![
+            Nlattr::new(None, CtrlAttr::FamilyId, 1u8).unwrap(),
+            Nlattr::new(None, CtrlAttr::FamilyId, 2u8).unwrap(),
+        ]);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        attrs.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref().len(), 16);
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        let parsed = NlVec::<Nlattr<CtrlAttr, Vec<u8>>>::deserialize(&mut mem).unwrap();
+        assert_eq!(parsed.0.len(), 2);
+        assert_eq!(parsed.0[1].get_payload_as::<u8>().unwrap(), 2);
+
+        let mut mem = StreamReadBuffer::new(&[1, 0, 0]);
+        assert!(NlVec::<u16>::deserialize(&mut mem).is_err());
+    }
+
+    #[test]
+    fn test_mac_address_nl() {
+        let addr = MacAddress::new([0x00, 0x1b, 0x21, 0x3a, 0x4f, 0xff]);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        addr.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), &[0x00, 0x1b, 0x21, 0x3a, 0x4f, 0xff]);
+
+        let mut mem = StreamReadBuffer::new(&[0x00, 0x1b, 0x21, 0x3a, 0x4f, 0xff]);
+        assert_eq!(MacAddress::deserialize(&mut mem).unwrap(), addr);
+        assert_eq!(MacAddress::type_size(), Some(6));
+    }
+
+    #[test]
+    fn test_mac_address_string() {
+        let addr = MacAddress::new([0x00, 0x1b, 0x21, 0x3a, 0x4f, 0xff]);
+        assert_eq!(addr.to_string(), "00:1b:21:3a:4f:ff");
+        assert_eq!("00:1B:21:3a:4f:ff".parse::<MacAddress>().unwrap(), addr);
+
+        assert!("00:1b:21:3a:4f".parse::<MacAddress>().is_err());
+        assert!("00:1b:21:3a:4f:ff:00".parse::<MacAddress>().is_err());
+        assert!("00:1b:21:3a:4f:gg".parse::<MacAddress>().is_err());
+        assert!("0:1b:21:3a:4f:ff".parse::<MacAddress>().is_err());
+    }
+
+    #[test]
+    fn test_big_endian() {
+        let port = BeU16::new(8080);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        port.serialize(&mut mem).unwrap();
+        BeU32::from(0x0a00_0001).serialize(&mut mem).unwrap();
+        BeU64::new(1).serialize(&mut mem).unwrap();
+        assert_eq!(
+            mem.as_ref(),
+            &[0x1f, 0x90, 0x0a, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]
+        );
+
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        assert_eq!(BeU16::deserialize(&mut mem).unwrap().get(), 8080);
+        assert_eq!(
+            u32::from(BeU32::deserialize(&mut mem).unwrap()),
+            0x0a00_0001
+        );
+        assert_eq!(BeU64::deserialize(&mut mem).unwrap(), BeU64::new(1));
+        assert_eq!(BeU32::type_size(), Some(4));
+    }
+
+    #[test]
+    fn test_nl_string() {
+        let s = NlString::new("nlctrl").unwrap();
+        assert_eq!(s.size(), 7);
+        assert_eq!(s.asize(), 8);
+
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        s.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), b"nlctrl\0");
+
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        mem.set_size_hint(16);
+        s.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), b"nlctrl\0\0\0\0\0\0\0\0\0\0");
+
+        let mut mem = StreamReadBuffer::new(b"nlctrl\0\0\0");
+        mem.set_size_hint(8);
+        assert_eq!(NlString::deserialize(&mut mem).unwrap(), s);
+        assert_eq!(s.as_str().unwrap(), "nlctrl");
+
+        let mut mem = StreamReadBuffer::new(b"nlctrl");
+        assert!(NlString::deserialize(&mut mem).is_err());
+        let mut mem = StreamReadBuffer::new(b"nl\0ctrl\0");
+        assert!(NlString::deserialize(&mut mem).is_err());
+        assert!(NlString::new("nl\0ctrl").is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use neli::{
+    consts::{Cmd, NlAttrType},
+    impl_trait, impl_typed_attr, impl_var, impl_var_trait,
+    nlattr::{AttrHandle, Nlattr},
+    Nl, StreamReadBuffer, StreamWriteBuffer,
+};
+
+impl_var_trait!(
+    /// Commands for an out of tree generic netlink family
+    MyCmd, u8, Cmd,
+    Get => 1,
+    Set => 2
+);
+
+impl_var_trait!(MyAttr, u16, NlAttrType,
+    Unspec => 0,
+    Name => 1
+);
+
+impl_typed_attr!(MyAttr,
+    /// Name of the object
+    MyAttrName => Name: String
+);
+
+impl_trait!(
+    /// Marker trait for values of a custom field
+    MyField, u32
+);
+
+impl_var_trait!(MyFieldValue, u32, MyField,
+    First => 10
+);
+
+impl_var!(MyPlainEnum, u8,
+    One => 1
+);
+
+#[test]
+fn test_downstream_impl_var() {
+    let mut mem = StreamWriteBuffer::new_growable(None);
+    MyCmd::Set.serialize(&mut mem).unwrap();
+    MyAttr::Name.serialize(&mut mem).unwrap();
+    MyFieldValue::First.serialize(&mut mem).unwrap();
+    let mut expected = vec![2];
+    expected.extend_from_slice(&1u16.to_ne_bytes());
+    expected.extend_from_slice(&10u32.to_ne_bytes());
+    assert_eq!(mem.as_ref(), expected.as_slice());
+
+    let mut mem = StreamReadBuffer::new(expected);
+    assert_eq!(MyCmd::deserialize(&mut mem).unwrap(), MyCmd::Set);
+    assert_eq!(MyAttr::deserialize(&mut mem).unwrap(), MyAttr::Name);
+    assert_eq!(
+        MyFieldValue::deserialize(&mut mem).unwrap(),
+        MyFieldValue::First
+    );
+
+    assert_eq!(MyCmd::from(7), MyCmd::UnrecognizedVariant(7));
+    assert_eq!(u8::from(MyPlainEnum::One), 1);
+}
+
+#[test]
+fn test_downstream_typed_attr() {
+    let handle = AttrHandle::new(vec![
+        Nlattr::new_typed::<MyAttrName>("neli".to_string()).unwrap()
+    ]);
+    assert_eq!(
+        handle.get::<MyAttrName>().unwrap(),
+        Some("neli".to_string())
+    );
+}